@@ -0,0 +1,329 @@
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, Read},
+    path::Path,
+};
+
+use anyhow::{anyhow, Result};
+use png::{BitDepth, BlendOp, ColorType, DisposeOp, FrameControl, Transformations};
+
+use crate::coder::layout::Layout;
+
+const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+const GIF_SIGNATURE: [u8; 3] = *b"GIF";
+
+#[derive(Debug, Clone)]
+pub struct Frame {
+    /// Frame control of an animation frame, `None` for a default image outside of animation.
+    pub control: Option<FrameControl>,
+    pub data: Vec<u8>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Animation {
+    pub width: u32,
+    pub height: u32,
+    pub color_type: ColorType,
+    pub bit_depth: BitDepth,
+    pub num_plays: u32,
+    pub frames: Vec<Frame>,
+}
+
+impl Animation {
+    pub fn layout(&self) -> Layout {
+        Layout::from_channel_count(self.color_type.samples() as u8).unwrap()
+    }
+
+    pub fn is_16bit(&self) -> bool {
+        self.bit_depth == BitDepth::Sixteen
+    }
+
+    pub fn samples(&self) -> Vec<u8> {
+        self.frames
+            .iter()
+            .flat_map(|frame| frame.data.iter().copied())
+            .collect()
+    }
+
+    pub fn set_samples(&mut self, samples: &[u8]) {
+        let mut samples = samples.iter();
+        for frame in self.frames.iter_mut() {
+            frame
+                .data
+                .iter_mut()
+                .zip(samples.by_ref())
+                .for_each(|(byte, sample)| *byte = *sample);
+        }
+    }
+
+    pub fn samples_u16(&self) -> Vec<u16> {
+        self.frames
+            .iter()
+            .flat_map(|frame| frame.data.chunks_exact(2))
+            .map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]))
+            .collect()
+    }
+
+    pub fn set_samples_u16(&mut self, samples: &[u16]) {
+        let mut samples = samples.iter();
+        for frame in self.frames.iter_mut() {
+            frame
+                .data
+                .chunks_exact_mut(2)
+                .zip(samples.by_ref())
+                .for_each(|(bytes, sample)| bytes.copy_from_slice(&sample.to_be_bytes()));
+        }
+    }
+}
+
+/// Checks whether the file is a carrier for [`read`]: an APNG or a GIF image.
+pub fn is_animation(path: &Path) -> Result<bool> {
+    let mut signature = [0; 8];
+    let mut file = File::open(path)?;
+    let read = file.read(&mut signature)?;
+
+    if signature[..read].starts_with(&GIF_SIGNATURE) {
+        return Ok(true);
+    }
+    if signature[..read] != PNG_SIGNATURE {
+        return Ok(false);
+    }
+
+    let reader = png::Decoder::new(BufReader::new(File::open(path)?)).read_info()?;
+    Ok(reader.info().animation_control().is_some())
+}
+
+pub fn read(path: &Path) -> Result<Animation> {
+    let mut signature = [0; 3];
+    File::open(path)?.read_exact(&mut signature)?;
+
+    if signature == GIF_SIGNATURE {
+        read_gif(path)
+    } else {
+        read_apng(path)
+    }
+}
+
+fn read_apng(path: &Path) -> Result<Animation> {
+    let mut decoder = png::Decoder::new(BufReader::new(File::open(path)?));
+    decoder.set_transformations(Transformations::EXPAND);
+    let mut reader = decoder.read_info()?;
+
+    let animation_control = reader
+        .info()
+        .animation_control()
+        .copied()
+        .ok_or(anyhow!("Image is not animated: {}", path.to_string_lossy()))?;
+    let separate_default_image = reader.info().frame_control().is_none();
+    let frames_count = animation_control.num_frames as usize + separate_default_image as usize;
+
+    let (color_type, bit_depth) = reader.output_color_type();
+    let (width, height) = reader.info().size();
+
+    let mut frames = Vec::with_capacity(frames_count);
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    for _ in 0..frames_count {
+        let output_info = reader.next_frame(&mut buffer)?;
+        frames.push(Frame {
+            control: reader.info().frame_control().copied(),
+            data: buffer[..output_info.buffer_size()].to_vec(),
+        });
+    }
+
+    Ok(Animation {
+        width,
+        height,
+        color_type,
+        bit_depth,
+        num_plays: animation_control.num_plays,
+        frames,
+    })
+}
+
+fn read_gif(path: &Path) -> Result<Animation> {
+    let mut options = gif::DecodeOptions::new();
+    options.set_color_output(gif::ColorOutput::RGBA);
+    let mut decoder = options.read_info(BufReader::new(File::open(path)?))?;
+
+    let width = decoder.width() as u32;
+    let height = decoder.height() as u32;
+
+    let mut frames = Vec::new();
+    while let Some(frame) = decoder.read_next_frame()? {
+        let control = FrameControl {
+            sequence_number: 0,
+            width: frame.width as u32,
+            height: frame.height as u32,
+            x_offset: frame.left as u32,
+            y_offset: frame.top as u32,
+            delay_num: frame.delay,
+            delay_den: 100,
+            dispose_op: dispose_op(frame.dispose),
+            blend_op: BlendOp::Over,
+        };
+        frames.push(Frame {
+            control: Some(control),
+            data: frame.buffer.to_vec(),
+        });
+    }
+
+    // APNG requires the first frame to cover the whole canvas.
+    if let Some(first) = frames.first_mut() {
+        *first = expand_to_canvas(first, width, height);
+    }
+
+    let num_plays = match decoder.repeat() {
+        gif::Repeat::Infinite => 0,
+        gif::Repeat::Finite(repeat) => repeat as u32 + 1,
+    };
+
+    Ok(Animation {
+        width,
+        height,
+        color_type: ColorType::Rgba,
+        bit_depth: BitDepth::Eight,
+        num_plays,
+        frames,
+    })
+}
+
+fn dispose_op(dispose: gif::DisposalMethod) -> DisposeOp {
+    match dispose {
+        gif::DisposalMethod::Any | gif::DisposalMethod::Keep => DisposeOp::None,
+        gif::DisposalMethod::Background => DisposeOp::Background,
+        gif::DisposalMethod::Previous => DisposeOp::Previous,
+    }
+}
+
+fn expand_to_canvas(frame: &Frame, width: u32, height: u32) -> Frame {
+    let control = frame.control.unwrap();
+    let mut data = vec![0; width as usize * height as usize * 4];
+
+    let row_length = control.width as usize * 4;
+    for (row, source) in frame.data.chunks_exact(row_length).enumerate() {
+        let start =
+            ((control.y_offset as usize + row) * width as usize + control.x_offset as usize) * 4;
+        data[start..start + row_length].copy_from_slice(source);
+    }
+
+    Frame {
+        control: Some(FrameControl {
+            width,
+            height,
+            x_offset: 0,
+            y_offset: 0,
+            ..control
+        }),
+        data,
+    }
+}
+
+/// Writes the animation as APNG, keeping frame timing, placement, disposal and blending.
+pub fn write(path: &Path, animation: &Animation) -> Result<()> {
+    let file = BufWriter::new(File::create(path)?);
+    let mut encoder = png::Encoder::new(file, animation.width, animation.height);
+    encoder.set_color(animation.color_type);
+    encoder.set_depth(animation.bit_depth);
+
+    let separate_default_image = animation
+        .frames
+        .first()
+        .is_some_and(|frame| frame.control.is_none());
+    let animation_frames = animation.frames.len() - separate_default_image as usize;
+    encoder.set_animated(animation_frames as u32, animation.num_plays)?;
+    encoder.set_sep_def_img(separate_default_image)?;
+
+    let mut writer = encoder.write_header()?;
+    for frame in &animation.frames {
+        if let Some(control) = frame.control {
+            writer.set_frame_delay(control.delay_num, control.delay_den)?;
+            writer.set_dispose_op(control.dispose_op)?;
+            writer.set_blend_op(control.blend_op)?;
+            writer.reset_frame_position()?;
+            writer.set_frame_dimension(control.width, control.height)?;
+            writer.set_frame_position(control.x_offset, control.y_offset)?;
+        }
+        writer.write_image_data(&frame.data)?;
+    }
+    writer.finish()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use png::{BitDepth, BlendOp, ColorType, DisposeOp, FrameControl};
+
+    use super::{Animation, Frame};
+
+    #[test]
+    fn samples() {
+        let animation = create_animation(BitDepth::Eight, vec![vec![1, 2], vec![3, 4, 5]]);
+        assert_eq!(animation.samples(), vec![1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn set_samples() {
+        let mut animation = create_animation(BitDepth::Eight, vec![vec![0, 0], vec![0, 0, 0]]);
+        animation.set_samples(&[1, 2, 3, 4, 5]);
+        assert_eq!(animation.frames[0].data, vec![1, 2]);
+        assert_eq!(animation.frames[1].data, vec![3, 4, 5]);
+    }
+
+    #[test]
+    fn samples_u16() {
+        let animation =
+            create_animation(BitDepth::Sixteen, vec![vec![0x01, 0x02], vec![0xAB, 0xCD]]);
+        assert_eq!(animation.samples_u16(), vec![0x0102, 0xABCD]);
+    }
+
+    #[test]
+    fn set_samples_u16() {
+        let mut animation = create_animation(BitDepth::Sixteen, vec![vec![0, 0], vec![0, 0]]);
+        animation.set_samples_u16(&[0x0102, 0xABCD]);
+        assert_eq!(animation.frames[0].data, vec![0x01, 0x02]);
+        assert_eq!(animation.frames[1].data, vec![0xAB, 0xCD]);
+    }
+
+    #[test]
+    fn expand_to_canvas() {
+        let frame = Frame {
+            control: Some(FrameControl {
+                width: 1,
+                height: 1,
+                x_offset: 1,
+                y_offset: 1,
+                dispose_op: DisposeOp::Background,
+                blend_op: BlendOp::Over,
+                ..Default::default()
+            }),
+            data: vec![1, 2, 3, 4],
+        };
+
+        let expanded = super::expand_to_canvas(&frame, 2, 2);
+        let control = expanded.control.unwrap();
+        assert_eq!((control.width, control.height), (2, 2));
+        assert_eq!((control.x_offset, control.y_offset), (0, 0));
+        assert_eq!(control.dispose_op, DisposeOp::Background);
+        assert_eq!(
+            expanded.data,
+            vec![0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 2, 3, 4]
+        );
+    }
+
+    fn create_animation(bit_depth: BitDepth, frames: Vec<Vec<u8>>) -> Animation {
+        Animation {
+            width: 1,
+            height: 1,
+            color_type: ColorType::Grayscale,
+            bit_depth,
+            num_plays: 0,
+            frames: frames
+                .into_iter()
+                .map(|data| Frame {
+                    control: Some(FrameControl::default()),
+                    data,
+                })
+                .collect(),
+        }
+    }
+}
@@ -0,0 +1,153 @@
+use anyhow::{anyhow, Result};
+
+pub(crate) const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+pub(crate) const IHDR: [u8; 4] = *b"IHDR";
+pub(crate) const IDAT: [u8; 4] = *b"IDAT";
+pub(crate) const IEND: [u8; 4] = *b"IEND";
+
+/// Private, ancillary, safe-to-copy chunk holding encoded data.
+pub const DATA_CHUNK: [u8; 4] = *b"mtGr";
+
+/// Maximal length of PNG chunk data.
+pub const MAX_CHUNK_LENGTH: usize = i32::MAX as usize;
+
+pub(crate) struct Chunk {
+    pub kind: [u8; 4],
+    pub start: usize,
+    pub data: std::ops::Range<usize>,
+    pub end: usize,
+}
+
+pub fn is_png(file: &[u8]) -> bool {
+    file.starts_with(&PNG_SIGNATURE)
+}
+
+/// Inserts chunk right before `IEND`, leaving the rest of the file byte-identical.
+pub fn insert(png: &[u8], kind: [u8; 4], data: &[u8]) -> Result<Vec<u8>> {
+    if data.len() > MAX_CHUNK_LENGTH {
+        return Err(anyhow!("Too much data to fit in PNG chunk."));
+    }
+
+    let iend = chunks(png)?
+        .into_iter()
+        .find(|chunk| chunk.kind == IEND)
+        .ok_or(anyhow!("Missing IEND chunk in PNG file."))?;
+
+    let mut output = Vec::with_capacity(png.len() + data.len() + 12);
+    output.extend_from_slice(&png[..iend.start]);
+    write(&mut output, kind, data);
+    output.extend_from_slice(&png[iend.start..]);
+    Ok(output)
+}
+
+pub(crate) fn write(output: &mut Vec<u8>, kind: [u8; 4], data: &[u8]) {
+    output.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    output.extend_from_slice(&kind);
+    output.extend_from_slice(data);
+    output.extend_from_slice(&crc(&kind, data).to_be_bytes());
+}
+
+/// Finds data of the first chunk of given kind.
+pub fn find(png: &[u8], kind: [u8; 4]) -> Result<Option<Vec<u8>>> {
+    let chunk = chunks(png)?.into_iter().find(|chunk| chunk.kind == kind);
+    Ok(chunk.map(|chunk| png[chunk.data].to_vec()))
+}
+
+pub(crate) fn chunks(png: &[u8]) -> Result<Vec<Chunk>> {
+    if !is_png(png) {
+        return Err(anyhow!("Not a PNG file."));
+    }
+
+    let mut chunks = Vec::new();
+    let mut position = PNG_SIGNATURE.len();
+    while position < png.len() {
+        let header = png
+            .get(position..position + 8)
+            .ok_or(anyhow!("Truncated PNG chunk header."))?;
+        let length = u32::from_be_bytes(header[..4].try_into().unwrap()) as usize;
+        let kind: [u8; 4] = header[4..].try_into().unwrap();
+
+        let data = position + 8..position + 8 + length;
+        let end = data.end + 4;
+        if end > png.len() {
+            return Err(anyhow!("Truncated PNG chunk data."));
+        }
+        if png[data.end..end] != crc(&kind, &png[data.clone()]).to_be_bytes() {
+            return Err(anyhow!("CRC mismatch in PNG chunk."));
+        }
+
+        chunks.push(Chunk {
+            kind,
+            start: position,
+            data,
+            end,
+        });
+        // Bytes after IEND are not part of the image, and other decoders ignore them as well
+        if kind == IEND {
+            break;
+        }
+        position = end;
+    }
+    Ok(chunks)
+}
+
+fn crc(kind: &[u8; 4], data: &[u8]) -> u32 {
+    let mut hasher = crc32fast::Hasher::new();
+    hasher.update(kind);
+    hasher.update(data);
+    hasher.finalize()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DATA_CHUNK;
+
+    #[test]
+    fn insert_find() {
+        let png = minimal_png();
+        let inserted = super::insert(&png, DATA_CHUNK, b"secret").unwrap();
+
+        assert_eq!(inserted.len(), png.len() + 6 + 12);
+        assert_eq!(&inserted[..png.len() - 12], &png[..png.len() - 12]);
+        assert_eq!(&inserted[inserted.len() - 12..], &png[png.len() - 12..]);
+        assert_eq!(
+            super::find(&inserted, DATA_CHUNK).unwrap(),
+            Some(b"secret".to_vec())
+        );
+    }
+
+    #[test]
+    fn find_missing() {
+        assert_eq!(super::find(&minimal_png(), DATA_CHUNK).unwrap(), None);
+    }
+
+    #[test]
+    fn find_not_png() {
+        assert!(super::find(b"GIF89a", DATA_CHUNK).is_err());
+    }
+
+    #[test]
+    fn find_crc_mismatch() {
+        let mut png = super::insert(&minimal_png(), DATA_CHUNK, b"secret").unwrap();
+        let index = png.len() - 12 - 4 - 1;
+        png[index] ^= 0xFF;
+        assert!(super::find(&png, DATA_CHUNK).is_err());
+    }
+
+    #[test]
+    fn find_ignores_trailing_bytes() {
+        let mut png = super::insert(&minimal_png(), DATA_CHUNK, b"secret").unwrap();
+        png.extend_from_slice(b"trailing junk");
+        assert_eq!(
+            super::find(&png, DATA_CHUNK).unwrap(),
+            Some(b"secret".to_vec())
+        );
+    }
+
+    fn minimal_png() -> Vec<u8> {
+        let mut png = super::PNG_SIGNATURE.to_vec();
+        super::write(&mut png, super::IHDR, &[0; 13]);
+        super::write(&mut png, super::IEND, &[]);
+        png
+    }
+}
@@ -1,6 +1,14 @@
-mod error;
+mod checksum;
+mod copies;
 mod header;
+mod stealth;
 mod util;
 
+pub mod crypto;
 pub mod decoder;
 pub mod encoder;
+pub mod error;
+pub mod layout;
+pub mod metadata;
+pub mod sample;
+pub mod signature;
@@ -0,0 +1,134 @@
+use anyhow::{anyhow, Result};
+use sha2::{Digest, Sha256};
+use zeroize::Zeroizing;
+
+use super::{
+    error::DecodeError,
+    header::{ChecksumHeader, Header},
+};
+
+const CRC32_LENGTH: usize = 4;
+const SHA256_LENGTH: usize = 32;
+
+impl ChecksumHeader {
+    fn length(&self) -> usize {
+        match self {
+            ChecksumHeader::Crc32 => CRC32_LENGTH,
+            ChecksumHeader::Sha256 => SHA256_LENGTH,
+        }
+    }
+}
+
+/// Appends checksum over the stored file name and data, after any signature.
+pub(crate) fn append(
+    header: &Header,
+    data: Zeroizing<Vec<u8>>,
+    file_name: &str,
+) -> Zeroizing<Vec<u8>> {
+    let checksum = digest(header.checksum, file_name, &data);
+    // Copied into a buffer of final size, as growing the data in place would leave copies behind
+    let mut checked = Zeroizing::new(Vec::with_capacity(data.len() + checksum.len()));
+    checked.extend_from_slice(&data);
+    checked.extend_from_slice(&checksum);
+    checked
+}
+
+/// Splits checksum appended by [`append`] off the data and checks it.
+pub(crate) fn verify(
+    header: &Header,
+    mut data: Zeroizing<Vec<u8>>,
+    file_name: &str,
+) -> Result<Zeroizing<Vec<u8>>> {
+    let checksum_start = data
+        .len()
+        .checked_sub(header.checksum.length())
+        .ok_or(DecodeError(
+            "Not enough data to read payload checksum.".to_string(),
+        ))?;
+    let checksum = data.split_off(checksum_start);
+    if checksum != digest(header.checksum, file_name, &data) {
+        return Err(anyhow!(DecodeError(
+            "Payload checksum mismatch, the data is corrupted.".to_string()
+        )));
+    }
+    Ok(data)
+}
+
+/// Lengths are included, so that bytes moving between file name and data are detected.
+fn digest(method: ChecksumHeader, file_name: &str, data: &[u8]) -> Vec<u8> {
+    let parts: [&[u8]; 4] = [
+        &(file_name.len() as u64).to_be_bytes(),
+        file_name.as_bytes(),
+        &(data.len() as u64).to_be_bytes(),
+        data,
+    ];
+    match method {
+        ChecksumHeader::Crc32 => {
+            let mut hasher = crc32fast::Hasher::new();
+            parts.iter().for_each(|part| hasher.update(part));
+            hasher.finalize().to_be_bytes().to_vec()
+        }
+        ChecksumHeader::Sha256 => {
+            let mut hasher = Sha256::new();
+            parts.iter().for_each(|part| hasher.update(part));
+            hasher.finalize().to_vec()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use zeroize::Zeroizing;
+
+    use crate::coder::{
+        error::DecodeError,
+        header::{ChecksumHeader, Header},
+    };
+
+    #[test]
+    fn append_verify_crc32() {
+        let header = Header::new_chunk().with_checksum(ChecksumHeader::Crc32);
+        let checked = super::append(&header, Zeroizing::new(b"data".to_vec()), "secret.txt");
+        assert_eq!(checked.len(), 4 + 4);
+
+        let data = super::verify(&header, checked, "secret.txt").unwrap();
+        assert_eq!(*data, b"data");
+    }
+
+    #[test]
+    fn append_verify_sha256() {
+        let header = Header::new_chunk();
+        let checked = super::append(&header, Zeroizing::new(b"data".to_vec()), "secret.txt");
+        assert_eq!(checked.len(), 4 + 32);
+
+        let data = super::verify(&header, checked, "secret.txt").unwrap();
+        assert_eq!(*data, b"data");
+    }
+
+    #[test]
+    fn verify_corrupted() {
+        let header = Header::new_chunk();
+        let mut checked = super::append(&header, Zeroizing::new(b"data".to_vec()), "secret.txt");
+        checked[1] ^= 1;
+        assert_eq!(
+            super::verify(&header, checked, "secret.txt")
+                .unwrap_err()
+                .downcast::<DecodeError>()
+                .unwrap(),
+            DecodeError("Payload checksum mismatch, the data is corrupted.".to_string())
+        );
+
+        let checked = super::append(&header, Zeroizing::new(b"data".to_vec()), "secret.txt");
+        assert!(super::verify(&header, checked, "secret.tx").is_err());
+    }
+
+    #[test]
+    fn verify_not_enough_data() {
+        let header = Header::new_chunk().with_checksum(ChecksumHeader::Crc32);
+        let verified = super::verify(&header, Zeroizing::new(vec![0; 3]), "");
+        assert_eq!(
+            verified.unwrap_err().downcast::<DecodeError>().unwrap(),
+            DecodeError("Not enough data to read payload checksum.".to_string())
+        );
+    }
+}
@@ -0,0 +1,81 @@
+use super::layout::Layout;
+
+/// Number of header copies stored in image samples, from format version 4. Spread evenly over
+/// the image, they keep the header readable when a part of it (an overlay, a stamp or a
+/// watermark) is damaged, and the bitwise majority of three copies repairs scattered flips.
+pub const HEADER_COPIES: usize = 3;
+
+/// Start of each header copy in a buffer of `samples`, evenly spread and rounded down to whole
+/// pixels. Offsets do not depend on the header, so decoders find copies before reading one.
+pub fn offsets(samples: usize, layout: Layout) -> [usize; HEADER_COPIES] {
+    let channels = layout.channels();
+    std::array::from_fn(|copy| copy * samples / HEADER_COPIES / channels * channels)
+}
+
+/// Whether copies of `length` samples, a whole number of pixels, fit between the offsets.
+pub fn fit(samples: usize, layout: Layout, length: usize) -> bool {
+    length <= samples / HEADER_COPIES / layout.channels() * layout.channels()
+}
+
+/// Moves the copies of `length` samples starting at `offsets` behind all other samples, keeping
+/// the order of both, so that data can be coded on one slice. Returns the number of other samples.
+pub fn gather<T>(buffer: &mut [T], offsets: &[usize], length: usize) -> usize {
+    let mut end = buffer.len();
+    for &offset in offsets.iter().rev() {
+        buffer[offset..end].rotate_left(length);
+        end -= length;
+    }
+    end
+}
+
+/// Moves gathered copies back to their offsets, undoing [`gather`].
+pub fn scatter<T>(buffer: &mut [T], offsets: &[usize], length: usize) {
+    let mut end = buffer.len() - offsets.len() * length;
+    for &offset in offsets {
+        buffer[offset..end + length].rotate_right(length);
+        end += length;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::coder::layout::Layout;
+
+    #[test]
+    fn offsets() {
+        assert_eq!(super::offsets(300, Layout::L), [0, 100, 200]);
+        assert_eq!(super::offsets(300, Layout::Rgba), [0, 100, 200]);
+        assert_eq!(super::offsets(24, Layout::Rgba), [0, 8, 16]);
+        assert_eq!(super::offsets(36, Layout::Rgba), [0, 12, 24]);
+        assert_eq!(super::offsets(40, Layout::Rgba), [0, 12, 24]);
+        assert_eq!(super::offsets(0, Layout::Rgb), [0, 0, 0]);
+    }
+
+    #[test]
+    fn fit() {
+        assert!(super::fit(300, Layout::L, 100));
+        assert!(!super::fit(300, Layout::L, 101));
+        assert!(super::fit(40, Layout::Rgba, 12));
+        assert!(!super::fit(40, Layout::Rgba, 16));
+    }
+
+    #[test]
+    fn gather_scatter() {
+        let mut buffer: Vec<u8> = (0..30).collect();
+        let offsets = super::offsets(buffer.len(), Layout::L);
+        let data_length = super::gather(&mut buffer, &offsets, 4);
+
+        assert_eq!(data_length, 18);
+        assert_eq!(
+            buffer[..data_length],
+            [4, 5, 6, 7, 8, 9, 14, 15, 16, 17, 18, 19, 24, 25, 26, 27, 28, 29]
+        );
+        assert_eq!(
+            buffer[data_length..],
+            [0, 1, 2, 3, 10, 11, 12, 13, 20, 21, 22, 23]
+        );
+
+        super::scatter(&mut buffer, &offsets, 4);
+        assert!(buffer.into_iter().eq(0..30));
+    }
+}
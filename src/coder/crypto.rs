@@ -0,0 +1,637 @@
+use std::{fmt, str::FromStr};
+
+use anyhow::{anyhow, Result};
+use argon2::{Argon2, Params, Version};
+use bech32::{FromBase32, ToBase32, Variant};
+use chacha20poly1305::{
+    aead::{Aead, KeyInit, Payload},
+    ChaCha20Poly1305, Key, Nonce,
+};
+use hkdf::Hkdf;
+use rand::{rngs::OsRng, RngCore};
+use sha2::{Digest, Sha256};
+use x25519_dalek::{EphemeralSecret, PublicKey, StaticSecret};
+use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
+
+use super::{
+    error::{DecryptError, EncodeError},
+    header::{
+        EncryptionHeader, Header, PassphraseHeader, RecipientHeader, NONCE_LENGTH, SALT_LENGTH,
+    },
+};
+
+/// Argon2id costs recommended by OWASP: 19 MiB of memory, 2 iterations, 1 lane.
+pub(super) const MEMORY_COST: u32 = 19 * 1024;
+pub(super) const TIME_COST: u32 = 2;
+pub(super) const PARALLELISM: u32 = 1;
+
+/// Limits of costs accepted from a header, so that a crafted image cannot exhaust resources.
+const MAX_MEMORY_COST: u32 = 1024 * 1024;
+const MAX_TIME_COST: u32 = 64;
+const MAX_PARALLELISM: u32 = 16;
+
+const PUBLIC_KEY_PREFIX: &str = "mtg";
+const SECRET_KEY_PREFIX: &str = "mtg-secret-key-";
+const WRAP_KEY_INFO: &[u8] = b"mythic-telegram/x25519";
+
+pub(super) const KEY_LENGTH: usize = 32;
+pub(super) const TAG_LENGTH: usize = 16;
+/// Ephemeral public key followed by the file key encrypted to a single recipient.
+const STANZA_LENGTH: usize = KEY_LENGTH + KEY_LENGTH + TAG_LENGTH;
+
+/// Secret used to protect hidden data.
+#[derive(Debug, Clone, Default)]
+pub enum Protection {
+    #[default]
+    None,
+    Passphrase(Passphrase),
+    /// Public keys of recipients data is encrypted to.
+    Recipients(Vec<Recipient>),
+    /// Secret key of one of the recipients, used to decrypt data.
+    Identity(Identity),
+}
+
+/// Passphrase, optionally combined with a keyfile as a second factor.
+/// Digest of the keyfile is used as Argon2 secret, so both are needed to derive the key.
+#[derive(Clone, PartialEq, Zeroize, ZeroizeOnDrop)]
+pub struct Passphrase {
+    passphrase: String,
+    keyfile: Option<[u8; KEY_LENGTH]>,
+}
+
+/// X25519 public key, written in Bech32 as `mtg1...`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Recipient(PublicKey);
+
+/// X25519 secret key, written in Bech32 as `MTG-SECRET-KEY-1...`.
+#[derive(Clone)]
+pub struct Identity(StaticSecret);
+
+impl Identity {
+    pub fn generate() -> Self {
+        Identity(StaticSecret::random_from_rng(OsRng))
+    }
+
+    pub fn recipient(&self) -> Recipient {
+        Recipient(PublicKey::from(&self.0))
+    }
+}
+
+impl Passphrase {
+    pub fn new(passphrase: String) -> Self {
+        Passphrase {
+            passphrase,
+            keyfile: None,
+        }
+    }
+
+    pub fn with_keyfile(mut self, contents: &[u8]) -> Self {
+        self.keyfile = Some(Sha256::digest(contents).into());
+        self
+    }
+}
+
+impl From<&str> for Passphrase {
+    fn from(passphrase: &str) -> Self {
+        Passphrase::new(passphrase.to_string())
+    }
+}
+
+impl fmt::Debug for Passphrase {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Passphrase")
+            .field("keyfile", &self.keyfile.is_some())
+            .finish_non_exhaustive()
+    }
+}
+
+impl fmt::Debug for Identity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Identity({})", self.recipient())
+    }
+}
+
+impl fmt::Display for Identity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let key = encode_key(SECRET_KEY_PREFIX, self.0.as_bytes()).map_err(|_| fmt::Error)?;
+        write!(f, "{}", key.to_uppercase())
+    }
+}
+
+/// Parses identity file contents, see [`key_line`].
+impl FromStr for Identity {
+    type Err = anyhow::Error;
+
+    fn from_str(contents: &str) -> Result<Self> {
+        let key = decode_key(key_line(contents)?, SECRET_KEY_PREFIX)
+            .ok_or(anyhow!("Invalid identity key."))?;
+        Ok(Identity(StaticSecret::from(key)))
+    }
+}
+
+impl fmt::Display for Recipient {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let key = encode_key(PUBLIC_KEY_PREFIX, self.0.as_bytes()).map_err(|_| fmt::Error)?;
+        write!(f, "{}", key)
+    }
+}
+
+impl FromStr for Recipient {
+    type Err = anyhow::Error;
+
+    fn from_str(key: &str) -> Result<Self> {
+        let key = decode_key(key.trim(), PUBLIC_KEY_PREFIX)
+            .ok_or(anyhow!("Invalid recipient key: {}", key))?;
+        Ok(Recipient(PublicKey::from(key)))
+    }
+}
+
+/// Encrypts data according to the protection, returning header describing the encryption.
+/// The file name is authenticated along with the data, but stays readable.
+pub(crate) fn seal(
+    header: Header,
+    protection: &Protection,
+    data: Zeroizing<Vec<u8>>,
+    file_name: &str,
+) -> Result<(Header, Zeroizing<Vec<u8>>)> {
+    match protection {
+        Protection::None => Ok((header, data)),
+        Protection::Passphrase(passphrase) => {
+            let encryption = new_passphrase_header();
+            let cipher = passphrase_cipher(&encryption, passphrase)?;
+            let data = cipher
+                .encrypt(
+                    Nonce::from_slice(&encryption.nonce),
+                    Payload {
+                        msg: &data,
+                        aad: file_name.as_bytes(),
+                    },
+                )
+                .map_err(|_| anyhow!(EncodeError("Could not encrypt data.".to_string())))?;
+            Ok((
+                header.with_encryption(EncryptionHeader::Passphrase(encryption)),
+                Zeroizing::new(data),
+            ))
+        }
+        Protection::Recipients(recipients) => {
+            let mut file_key = Zeroizing::new([0; KEY_LENGTH]);
+            OsRng.fill_bytes(&mut *file_key);
+            let mut payload = wrap_file_key(&file_key, recipients)?;
+            // The file key is fresh for every message, thus a fixed nonce is never reused.
+            let data = ChaCha20Poly1305::new(Key::from_slice(&*file_key))
+                .encrypt(
+                    &Nonce::default(),
+                    Payload {
+                        msg: &data,
+                        aad: file_name.as_bytes(),
+                    },
+                )
+                .map_err(|_| anyhow!(EncodeError("Could not encrypt data.".to_string())))?;
+            payload.extend(data);
+            Ok((
+                header.with_encryption(EncryptionHeader::Recipient(RecipientHeader {})),
+                Zeroizing::new(payload),
+            ))
+        }
+        Protection::Identity(_) => Err(anyhow!(EncodeError(
+            "Identity can only be used to decrypt data, recipient is required.".to_string()
+        ))),
+    }
+}
+
+/// Decrypts data sealed with [`seal`], failing with [`DecryptError`] on a wrong secret or tampered data.
+pub(crate) fn open(
+    header: &Header,
+    protection: &Protection,
+    data: Zeroizing<Vec<u8>>,
+    file_name: &str,
+) -> Result<Zeroizing<Vec<u8>>> {
+    match (&header.encryption, protection) {
+        (EncryptionHeader::None, _) => Ok(data),
+        (EncryptionHeader::Passphrase(encryption), Protection::Passphrase(passphrase)) => {
+            let cipher = passphrase_cipher(encryption, passphrase)?;
+            cipher
+                .decrypt(
+                    Nonce::from_slice(&encryption.nonce),
+                    Payload {
+                        msg: &data,
+                        aad: file_name.as_bytes(),
+                    },
+                )
+                .map(Zeroizing::new)
+                .map_err(|_| {
+                    anyhow!(DecryptError(
+                        "Wrong passphrase or tampered data.".to_string()
+                    ))
+                })
+        }
+        (EncryptionHeader::Passphrase(_), _) => Err(anyhow!(DecryptError(
+            "Data is encrypted, passphrase is required.".to_string()
+        ))),
+        (EncryptionHeader::Recipient(_), Protection::Identity(identity)) => {
+            let (file_key, data) = unwrap_file_key(identity, &data)?;
+            ChaCha20Poly1305::new(Key::from_slice(&file_key))
+                .decrypt(
+                    &Nonce::default(),
+                    Payload {
+                        msg: data,
+                        aad: file_name.as_bytes(),
+                    },
+                )
+                .map(Zeroizing::new)
+                .map_err(|_| anyhow!(DecryptError("Tampered data.".to_string())))
+        }
+        (EncryptionHeader::Recipient(_), _) => Err(anyhow!(DecryptError(
+            "Data is encrypted to recipients, identity is required.".to_string()
+        ))),
+    }
+}
+
+/// Encrypts the file key to every recipient, prefixed with the number of recipients.
+fn wrap_file_key(file_key: &[u8; KEY_LENGTH], recipients: &[Recipient]) -> Result<Vec<u8>> {
+    let count = u8::try_from(recipients.len())
+        .ok()
+        .filter(|count| *count > 0)
+        .ok_or(EncodeError(
+            "Number of recipients must be between 1 and 255.".to_string(),
+        ))?;
+
+    let mut stanzas = vec![count];
+    for Recipient(public_key) in recipients {
+        let ephemeral = EphemeralSecret::random_from_rng(OsRng);
+        let ephemeral_public = PublicKey::from(&ephemeral);
+        let shared = ephemeral.diffie_hellman(public_key);
+        if !shared.was_contributory() {
+            return Err(anyhow!(EncodeError("Invalid recipient key.".to_string())));
+        }
+
+        let wrapped = wrap_cipher(shared.as_bytes(), &ephemeral_public, public_key)?
+            .encrypt(&Nonce::default(), file_key.as_slice())
+            .map_err(|_| anyhow!(EncodeError("Could not wrap file key.".to_string())))?;
+        stanzas.extend_from_slice(ephemeral_public.as_bytes());
+        stanzas.extend_from_slice(&wrapped);
+    }
+    Ok(stanzas)
+}
+
+/// Finds the file key wrapped to the identity, returning it along with the remaining data.
+fn unwrap_file_key<'a>(
+    identity: &Identity,
+    payload: &'a [u8],
+) -> Result<(Zeroizing<Vec<u8>>, &'a [u8])> {
+    let stanzas_end = payload
+        .first()
+        .map(|&count| 1 + count as usize * STANZA_LENGTH)
+        .filter(|&end| end <= payload.len())
+        .ok_or(DecryptError(
+            "Not enough data to read wrapped keys.".to_string(),
+        ))?;
+
+    let public_key = PublicKey::from(&identity.0);
+    let file_key = payload[1..stanzas_end]
+        .chunks_exact(STANZA_LENGTH)
+        .find_map(|stanza| {
+            let (ephemeral, wrapped) = stanza.split_at(KEY_LENGTH);
+            let ephemeral = PublicKey::from(<[u8; KEY_LENGTH]>::try_from(ephemeral).ok()?);
+            let shared = identity.0.diffie_hellman(&ephemeral);
+            wrap_cipher(shared.as_bytes(), &ephemeral, &public_key)
+                .ok()?
+                .decrypt(&Nonce::default(), wrapped)
+                .ok()
+                .map(Zeroizing::new)
+        })
+        .ok_or(DecryptError(
+            "Data is not encrypted to this identity.".to_string(),
+        ))?;
+    Ok((file_key, &payload[stanzas_end..]))
+}
+
+/// Derives the key wrapping cipher from the shared secret, bound to both public keys.
+fn wrap_cipher(
+    shared: &[u8],
+    ephemeral: &PublicKey,
+    recipient: &PublicKey,
+) -> Result<ChaCha20Poly1305> {
+    let salt = [ephemeral.as_bytes().as_slice(), recipient.as_bytes()].concat();
+    let mut key = Zeroizing::new([0; KEY_LENGTH]);
+    Hkdf::<Sha256>::new(Some(&salt), shared)
+        .expand(WRAP_KEY_INFO, &mut *key)
+        .map_err(|error| anyhow!(error.to_string()))?;
+    Ok(ChaCha20Poly1305::new(Key::from_slice(&*key)))
+}
+
+/// Finds the key in a key file: the first line which is neither empty nor a `#` comment.
+pub(super) fn key_line(contents: &str) -> Result<&str> {
+    contents
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty() && !line.starts_with('#'))
+        .ok_or(anyhow!("Key file does not contain any key."))
+}
+
+pub(super) fn encode_key(prefix: &str, key: &[u8; KEY_LENGTH]) -> Result<String, bech32::Error> {
+    bech32::encode(prefix, key.to_base32(), Variant::Bech32)
+}
+
+pub(super) fn decode_key(key: &str, prefix: &str) -> Option<[u8; KEY_LENGTH]> {
+    let (hrp, data, variant) = bech32::decode(key).ok()?;
+    if hrp != prefix || variant != Variant::Bech32 {
+        return None;
+    }
+    Vec::<u8>::from_base32(&data).ok()?.try_into().ok()
+}
+
+fn new_passphrase_header() -> PassphraseHeader {
+    let mut salt = [0; SALT_LENGTH];
+    let mut nonce = [0; NONCE_LENGTH];
+    OsRng.fill_bytes(&mut salt);
+    OsRng.fill_bytes(&mut nonce);
+
+    PassphraseHeader {
+        salt,
+        nonce,
+        memory_cost: MEMORY_COST,
+        time_cost: TIME_COST,
+        parallelism: PARALLELISM,
+    }
+}
+
+fn passphrase_cipher(
+    header: &PassphraseHeader,
+    passphrase: &Passphrase,
+) -> Result<ChaCha20Poly1305> {
+    if header.memory_cost > MAX_MEMORY_COST
+        || header.time_cost > MAX_TIME_COST
+        || header.parallelism > MAX_PARALLELISM
+    {
+        return Err(anyhow!(DecryptError(
+            "Unsupported key derivation parameters.".to_string()
+        )));
+    }
+
+    let params = Params::new(
+        header.memory_cost,
+        header.time_cost,
+        header.parallelism,
+        None,
+    )
+    .map_err(|error| anyhow!(DecryptError(error.to_string())))?;
+
+    let key = derive_key(passphrase, &header.salt, params)?;
+    Ok(ChaCha20Poly1305::new(Key::from_slice(&*key)))
+}
+
+/// Derives a key from the passphrase with Argon2id, using the keyfile digest as its secret.
+pub(super) fn derive_key(
+    passphrase: &Passphrase,
+    salt: &[u8],
+    params: Params,
+) -> Result<Zeroizing<[u8; KEY_LENGTH]>> {
+    let argon2 = match &passphrase.keyfile {
+        Some(keyfile) => {
+            Argon2::new_with_secret(keyfile, argon2::Algorithm::Argon2id, Version::V0x13, params)
+                .map_err(|error| anyhow!(DecryptError(error.to_string())))?
+        }
+        None => Argon2::new(argon2::Algorithm::Argon2id, Version::V0x13, params),
+    };
+    let mut key = Zeroizing::new([0; KEY_LENGTH]);
+    argon2
+        .hash_password_into(passphrase.passphrase.as_bytes(), salt, &mut *key)
+        .map_err(|error| anyhow!(DecryptError(error.to_string())))?;
+    Ok(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::coder::{
+        error::DecryptError,
+        header::{EncryptionHeader, Header, PassphraseHeader},
+    };
+
+    use zeroize::{Zeroize, Zeroizing};
+
+    use super::{Identity, Passphrase, Protection, Recipient};
+
+    #[test]
+    fn seal_open() {
+        let protection = Protection::Passphrase("correct horse".into());
+        let (header, sealed) = seal(&protection, b"secret data");
+
+        assert_ne!(*sealed, b"secret data");
+        assert_eq!(sealed.len(), b"secret data".len() + 16);
+        let opened = super::open(&header, &protection, sealed, "secret.txt").unwrap();
+        assert_eq!(*opened, b"secret data");
+    }
+
+    #[test]
+    fn seal_without_protection() {
+        let (header, sealed) = seal(&Protection::None, b"secret data");
+        assert_eq!(header, Header::new_chunk());
+        assert_eq!(*sealed, b"secret data");
+    }
+
+    #[test]
+    fn open_wrong_passphrase() {
+        let (header, sealed) = seal(&Protection::Passphrase("right".into()), b"data");
+        let wrong = Protection::Passphrase("wrong".into());
+        assert_decrypt_error(super::open(&header, &wrong, sealed, "secret.txt"));
+    }
+
+    #[test]
+    fn seal_open_with_keyfile() {
+        let passphrase = Passphrase::from("correct horse").with_keyfile(b"keyfile");
+        let protection = Protection::Passphrase(passphrase.clone());
+        let (header, sealed) = seal(&protection, b"data");
+
+        let opened = super::open(&header, &protection, sealed.clone(), "secret.txt").unwrap();
+        assert_eq!(*opened, b"data");
+
+        let without_keyfile = Protection::Passphrase(Passphrase::from("correct horse"));
+        assert_decrypt_error(super::open(
+            &header,
+            &without_keyfile,
+            sealed.clone(),
+            "secret.txt",
+        ));
+        let other_keyfile = Protection::Passphrase(passphrase.with_keyfile(b"other"));
+        assert_decrypt_error(super::open(&header, &other_keyfile, sealed, "secret.txt"));
+    }
+
+    #[test]
+    fn passphrase_zeroize() {
+        let mut passphrase = Passphrase::from("correct horse").with_keyfile(b"keyfile");
+        passphrase.zeroize();
+        assert_eq!(passphrase, Passphrase::from(""));
+    }
+
+    #[test]
+    fn passphrase_debug_redacted() {
+        let passphrase = Passphrase::from("correct horse").with_keyfile(b"keyfile");
+        assert!(!format!("{:?}", passphrase).contains("horse"));
+    }
+
+    #[test]
+    fn open_tampered_data() {
+        let protection = Protection::Passphrase("passphrase".into());
+        let (header, mut sealed) = seal(&protection, b"data");
+        sealed[0] ^= 1;
+        assert_decrypt_error(super::open(&header, &protection, sealed, "secret.txt"));
+    }
+
+    #[test]
+    fn open_tampered_file_name() {
+        let protection = Protection::Passphrase("passphrase".into());
+        let (header, sealed) = seal(&protection, b"data");
+        assert_decrypt_error(super::open(&header, &protection, sealed, "other.txt"));
+    }
+
+    #[test]
+    fn open_without_passphrase() {
+        let protection = Protection::Passphrase("passphrase".into());
+        let (header, sealed) = seal(&protection, b"data");
+        assert_decrypt_error(super::open(
+            &header,
+            &Protection::None,
+            sealed,
+            "secret.txt",
+        ));
+    }
+
+    #[test]
+    fn open_excessive_costs() {
+        let header =
+            Header::new_chunk().with_encryption(EncryptionHeader::Passphrase(PassphraseHeader {
+                salt: [0; 16],
+                nonce: [0; 12],
+                memory_cost: u32::MAX,
+                time_cost: 1,
+                parallelism: 1,
+            }));
+        let protection = Protection::Passphrase("passphrase".into());
+        assert_decrypt_error(super::open(
+            &header,
+            &protection,
+            Zeroizing::new(vec![0; 16]),
+            "secret.txt",
+        ));
+    }
+
+    #[test]
+    fn seal_open_recipients() {
+        let (alice, bob) = (Identity::generate(), Identity::generate());
+        let protection = Protection::Recipients(vec![alice.recipient(), bob.recipient()]);
+        let (header, sealed) = seal(&protection, b"secret data");
+
+        assert_eq!(
+            sealed.len(),
+            1 + 2 * super::STANZA_LENGTH + b"secret data".len() + 16
+        );
+        for identity in [alice, bob] {
+            let opened = super::open(
+                &header,
+                &Protection::Identity(identity),
+                sealed.clone(),
+                "secret.txt",
+            )
+            .unwrap();
+            assert_eq!(*opened, b"secret data");
+        }
+    }
+
+    #[test]
+    fn open_other_identity() {
+        let protection = Protection::Recipients(vec![Identity::generate().recipient()]);
+        let (header, sealed) = seal(&protection, b"data");
+
+        let other = Protection::Identity(Identity::generate());
+        assert_decrypt_error(super::open(&header, &other, sealed.clone(), "secret.txt"));
+        assert_decrypt_error(super::open(
+            &header,
+            &Protection::None,
+            sealed,
+            "secret.txt",
+        ));
+    }
+
+    #[test]
+    fn open_recipients_tampered_data() {
+        let identity = Identity::generate();
+        let protection = Protection::Recipients(vec![identity.recipient()]);
+        let (header, mut sealed) = seal(&protection, b"data");
+        *sealed.last_mut().unwrap() ^= 1;
+
+        let identity = Protection::Identity(identity);
+        assert_decrypt_error(super::open(
+            &header,
+            &identity,
+            sealed.clone(),
+            "secret.txt",
+        ));
+        assert_decrypt_error(super::open(
+            &header,
+            &identity,
+            Zeroizing::new(sealed[..10].to_vec()),
+            "x",
+        ));
+    }
+
+    #[test]
+    fn seal_without_recipients() {
+        let protection = Protection::Recipients(vec![]);
+        assert!(super::seal(
+            Header::new_chunk(),
+            &protection,
+            Zeroizing::default(),
+            "secret.txt"
+        )
+        .is_err());
+
+        let protection = Protection::Identity(Identity::generate());
+        assert!(super::seal(
+            Header::new_chunk(),
+            &protection,
+            Zeroizing::default(),
+            "secret.txt"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn recipient_from_str() {
+        let recipient = Identity::generate().recipient();
+        let encoded = recipient.to_string();
+        assert!(encoded.starts_with("mtg1"));
+        assert_eq!(encoded.parse::<Recipient>().unwrap(), recipient);
+
+        assert!("mtg1invalid".parse::<Recipient>().is_err());
+        let identity = Identity::generate().to_string();
+        assert!(identity.parse::<Recipient>().is_err());
+    }
+
+    #[test]
+    fn identity_from_str() {
+        let identity = Identity::generate();
+        let encoded = identity.to_string();
+        assert!(encoded.starts_with("MTG-SECRET-KEY-1"));
+
+        let file = format!("# public key: {}\n\n{}\n", identity.recipient(), encoded);
+        let parsed: Identity = file.parse().unwrap();
+        assert_eq!(parsed.recipient(), identity.recipient());
+
+        assert!("# no key\n".parse::<Identity>().is_err());
+        assert!(identity
+            .recipient()
+            .to_string()
+            .parse::<Identity>()
+            .is_err());
+    }
+
+    fn seal(protection: &Protection, data: &[u8]) -> (Header, Zeroizing<Vec<u8>>) {
+        let data = Zeroizing::new(data.to_vec());
+        super::seal(Header::new_chunk(), protection, data, "secret.txt").unwrap()
+    }
+
+    fn assert_decrypt_error(result: anyhow::Result<Zeroizing<Vec<u8>>>) {
+        assert!(result.unwrap_err().downcast::<DecryptError>().is_ok());
+    }
+}
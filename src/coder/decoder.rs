@@ -1,28 +1,192 @@
 mod alpha_decoder;
+mod byte_decoder;
 mod decode;
 mod header_decoder;
 mod rgb_decoder;
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
+use zeroize::Zeroizing;
 
-use self::{alpha_decoder::AlphaDecoder, decode::Decode, rgb_decoder::RgbDecoder};
+use self::{
+    alpha_decoder::AlphaDecoder, byte_decoder::ByteDecoder, decode::Decode, rgb_decoder::RgbDecoder,
+};
 
-use super::header::{AlgHeader, Header};
+use super::{
+    checksum, copies,
+    crypto::{self, Passphrase, Protection},
+    error::{DecodeError, HeaderDecodeError},
+    header::{AlgHeader, Header, MetadataHeader, RecordHeader, LOW_BIT_HEADER_VERSION},
+    layout::Layout,
+    metadata::{self, Metadata},
+    sample::Sample,
+    signature::{self, SignatureStatus, VerifyingKey},
+    stealth::{Stealth, PREFIX_LENGTH},
+    util::BITS_IN_BYTE,
+};
 
-pub fn decode(mut image_buffer: Vec<u8>) -> Result<(String, Vec<u8>)> {
-    let header = header_decoder::decode(&image_buffer)?;
-    let buffer = image_buffer.split_off(header.size() * 4);
+/// Hidden file along with the outcome of checking its signature.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Decoded {
+    pub file_name: Zeroizing<String>,
+    pub data: Zeroizing<Vec<u8>>,
+    pub signature: SignatureStatus,
+    pub metadata: Metadata,
+}
+
+pub fn decode<T: Sample>(
+    layout: Layout,
+    image_buffer: Vec<T>,
+    protection: &Protection,
+    verifying_keys: &[VerifyingKey],
+) -> Result<Decoded> {
+    let header = match header_decoder::decode(layout, &image_buffer) {
+        Ok(header) => header,
+        // Stealth mode leaves no header, so its costly key derivation is only tried without one
+        Err(error) if is_no_payload(&error) => {
+            if let Protection::Passphrase(passphrase) = protection {
+                if let Some(decoded) = decode_stealth(image_buffer, passphrase)? {
+                    return Ok(decoded);
+                }
+            }
+            return Err(error);
+        }
+        Err(error) => return Err(error),
+    };
+    let buffer = data_samples(&header, layout, image_buffer)?;
 
-    let decoder = create_decoder(&header, &buffer);
-    let decoded = decoder.decode()?;
-    Ok(decoded)
+    let decoder = create_decoder(&header, layout, &buffer)?;
+    let (file_name, data) = decoder.decode_sized(header.length_size())?;
+    reveal(&header, protection, verifying_keys, file_name, data)
 }
 
-fn create_decoder<'a>(header: &Header, buffer: &'a [u8]) -> Box<dyn Decode + 'a> {
-    match &header.alg_header {
-        AlgHeader::Alpha(_) => Box::new(AlphaDecoder::new(buffer)),
-        AlgHeader::Rgb(rgb_header) => {
-            Box::new(RgbDecoder::new(buffer, rgb_header.bits_per_channel))
+/// Drops the header copies from image samples, leaving the samples data is stored on.
+fn data_samples<T: Sample>(header: &Header, layout: Layout, mut buffer: Vec<T>) -> Result<Vec<T>> {
+    let header_size = header.sample_count(layout);
+    if header.version < LOW_BIT_HEADER_VERSION {
+        return Ok(buffer.split_off(header_size.min(buffer.len())));
+    }
+    if !copies::fit(buffer.len(), layout, header_size) {
+        return Err(anyhow!(HeaderDecodeError(
+            "Not enough data to decode header copies.".to_string()
+        )));
+    }
+    let offsets = copies::offsets(buffer.len(), layout);
+    let data_size = copies::gather(&mut buffer, &offsets, header_size);
+    buffer.truncate(data_size);
+    Ok(buffer)
+}
+
+/// Whether decoding failed because the carrier holds no payload at all, rather than a damaged or
+/// unreadable one.
+pub fn is_no_payload(error: &anyhow::Error) -> bool {
+    error
+        .downcast_ref::<HeaderDecodeError>()
+        .is_some_and(|error| error.0 == header_decoder::NO_PAYLOAD)
+}
+
+/// Decodes header and data from a plain byte stream produced by [`super::encoder::encode_bytes`].
+pub fn decode_bytes(
+    buffer: &[u8],
+    protection: &Protection,
+    verifying_keys: &[VerifyingKey],
+) -> Result<Decoded> {
+    let header = header_decoder::decode_bytes(buffer)?;
+    if !header.is_byte_stream() {
+        return Err(anyhow!(DecodeError(
+            "Header mode does not store data as a byte stream".to_string()
+        )));
+    }
+
+    let buffer = &buffer[header.size().min(buffer.len())..];
+    let (file_name, data) =
+        Box::new(ByteDecoder::new(buffer)).decode_sized(header.length_size())?;
+    reveal(&header, protection, verifying_keys, file_name, data)
+}
+
+/// Trial decodes headerless data embedded in stealth mode, returning `None` unless
+/// the authentication tag confirms it was embedded with this passphrase.
+fn decode_stealth<T: Sample>(
+    mut buffer: Vec<T>,
+    passphrase: &Passphrase,
+) -> Result<Option<Decoded>> {
+    let stealth = Stealth::derive(passphrase)?;
+    let start = stealth.start(buffer.len());
+    buffer.rotate_left(start);
+    let capacity = buffer.len() * stealth.bits_per_channel as usize / BITS_IN_BYTE as usize;
+
+    let mut decoder = RgbDecoder::new(&buffer, stealth.bits_per_channel);
+    let Some(prefix) = decoder
+        .decode_data(PREFIX_LENGTH)
+        .and_then(|prefix| <[u8; PREFIX_LENGTH]>::try_from(prefix).ok())
+    else {
+        return Ok(None);
+    };
+    let length = stealth.ciphertext_length(&prefix);
+    if length > capacity - PREFIX_LENGTH {
+        return Ok(None);
+    }
+    let Some(record) = decoder
+        .decode_data(length)
+        .and_then(|ciphertext| stealth.open(&prefix, &ciphertext))
+    else {
+        return Ok(None);
+    };
+
+    let (file_name, data) = Box::new(ByteDecoder::new(&record)).decode()?;
+    Ok(Some(Decoded {
+        file_name,
+        data,
+        signature: SignatureStatus::Unsigned,
+        metadata: Metadata::default(),
+    }))
+}
+
+/// Checks the checksum and signature of the stored data, then decrypts it and splits off metadata.
+fn reveal(
+    header: &Header,
+    protection: &Protection,
+    verifying_keys: &[VerifyingKey],
+    file_name: Zeroizing<String>,
+    data: Zeroizing<Vec<u8>>,
+) -> Result<Decoded> {
+    let data = checksum::verify(header, data, &file_name)?;
+    let (signature, data) = signature::verify(header, verifying_keys, data, &file_name)?;
+    let data = crypto::open(header, protection, data, &file_name)?;
+    let (file_name, data) = match header.record {
+        RecordHeader::Plain => (file_name, data),
+        RecordHeader::Sealed => {
+            Box::new(ByteDecoder::new(&data)).decode_sized(header.length_size())?
         }
+    };
+    let (metadata, data) = match header.metadata {
+        MetadataHeader::None => (Metadata::default(), data),
+        MetadataHeader::Attributes => metadata::split(data)?,
+    };
+    Ok(Decoded {
+        file_name,
+        data,
+        signature,
+        metadata,
+    })
+}
+
+fn create_decoder<'a, T: Sample>(
+    header: &Header,
+    layout: Layout,
+    buffer: &'a [T],
+) -> Result<Box<dyn Decode + 'a>> {
+    match &header.alg_header {
+        AlgHeader::Alpha(_) => Ok(Box::new(AlphaDecoder::new(buffer, layout))),
+        AlgHeader::Rgb(rgb_header) => Ok(Box::new(RgbDecoder::new(
+            buffer,
+            rgb_header.bits_per_channel,
+        ))),
+        AlgHeader::Chunk(_)
+        | AlgHeader::Filter(_)
+        | AlgHeader::Palette(_)
+        | AlgHeader::Text(_)
+        | AlgHeader::Svg(_) => Err(anyhow!(DecodeError(
+            "Header mode does not store data in image pixels".to_string()
+        ))),
     }
 }
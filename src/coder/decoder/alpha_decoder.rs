@@ -3,30 +3,41 @@ use std::{
     slice::Iter,
 };
 
+use crate::coder::{layout::Layout, sample::Sample, util::BITS_IN_BYTE};
+
 use super::decode::Decode;
 
-pub struct AlphaDecoder<'a> {
-    iter: StepBy<Skip<Iter<'a, u8>>>,
+pub struct AlphaDecoder<'a, T: Sample> {
+    iter: StepBy<Skip<Iter<'a, T>>>,
 }
 
-impl<'a> AlphaDecoder<'a> {
-    pub fn new(buffer: &'a [u8]) -> Self {
+impl<'a, T: Sample> AlphaDecoder<'a, T> {
+    pub fn new(buffer: &'a [T], layout: Layout) -> Self {
         Self {
-            iter: buffer.iter().skip(3).step_by(4),
+            iter: buffer
+                .iter()
+                .skip(layout.alpha_mode_channel())
+                .step_by(layout.channels()),
         }
     }
 }
 
-impl<'a> Decode for AlphaDecoder<'a> {
+impl<'a, T: Sample> Decode for AlphaDecoder<'a, T> {
     fn decode_byte(&mut self) -> Option<u8> {
-        let byte = self.iter.next()?;
-        Some(*byte)
+        let channel = self.iter.next()?;
+        Some(channel.low_bits(BITS_IN_BYTE))
+    }
+
+    fn max_bytes_to_decode(&self) -> usize {
+        self.iter.len()
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::coder::{decoder::decode::Decode, error::DecodeError};
+    use crate::coder::{
+        decoder::decode::Decode, error::DecodeError, header::LENGTH_SIZE, layout::Layout,
+    };
 
     use super::AlphaDecoder;
 
@@ -52,7 +63,16 @@ mod tests {
 
         fill_encoded(
             &mut iter,
-            &[0b0000_0000, 0b0000_0000, 0b0000_0000, filename_length as u8],
+            &[
+                0b0000_0000,
+                0b0000_0000,
+                0b0000_0000,
+                0b0000_0000,
+                0b0000_0000,
+                0b0000_0000,
+                0b0000_0000,
+                filename_length as u8,
+            ],
         );
 
         let decoder = create_decoder(&buffer);
@@ -72,7 +92,16 @@ mod tests {
 
         fill_encoded(
             &mut iter,
-            &[0b0000_0000, 0b0000_0000, 0b0000_0000, filename_length as u8],
+            &[
+                0b0000_0000,
+                0b0000_0000,
+                0b0000_0000,
+                0b0000_0000,
+                0b0000_0000,
+                0b0000_0000,
+                0b0000_0000,
+                filename_length as u8,
+            ],
         );
 
         let decoder = create_decoder(&buffer);
@@ -97,6 +126,10 @@ mod tests {
                 0b0000_0000,
                 0b0000_0000,
                 0b0000_0000,
+                0b0000_0000,
+                0b0000_0000,
+                0b0000_0000,
+                0b0000_0000,
                 filename_length as u8,
                 // Filename
                 0b0000_0000,
@@ -115,6 +148,10 @@ mod tests {
                 0b0000_0000,
                 0b0000_0000,
                 0b0000_0000,
+                0b0000_0000,
+                0b0000_0000,
+                0b0000_0000,
+                0b0000_0000,
                 data_length as u8,
             ],
         );
@@ -137,7 +174,16 @@ mod tests {
         // Filename length
         fill_encoded(
             &mut iter,
-            &[0b0000_0000, 0b0000_0000, 0b0000_0000, filename_length as u8],
+            &[
+                0b0000_0000,
+                0b0000_0000,
+                0b0000_0000,
+                0b0000_0000,
+                0b0000_0000,
+                0b0000_0000,
+                0b0000_0000,
+                filename_length as u8,
+            ],
         );
 
         // Filename
@@ -149,7 +195,16 @@ mod tests {
         // Message length
         fill_encoded(
             &mut iter,
-            &[0b0000_0000, 0b0000_0000, 0b0000_0000, data_length as u8],
+            &[
+                0b0000_0000,
+                0b0000_0000,
+                0b0000_0000,
+                0b0000_0000,
+                0b0000_0000,
+                0b0000_0000,
+                0b0000_0000,
+                data_length as u8,
+            ],
         );
         // Message
         fill_encoded(
@@ -160,12 +215,12 @@ mod tests {
         let decoder = create_decoder(&buffer);
         let (filename, data) = decoder.decode().unwrap();
 
-        assert_eq!(filename, "x.png");
-        assert_eq!(String::from_utf8(data).unwrap(), "wolf");
+        assert_eq!(*filename, "x.png");
+        assert_eq!(*data, *b"wolf");
     }
 
-    fn create_decoder<'a>(buffer: &'a Vec<u8>) -> Box<dyn Decode + 'a> {
-        Box::new(AlphaDecoder::new(buffer))
+    fn create_decoder<'a>(buffer: &'a [u8]) -> Box<dyn Decode + 'a> {
+        Box::new(AlphaDecoder::new(buffer, Layout::Rgba))
     }
 
     fn fill_encoded<'a>(iter: &mut impl Iterator<Item = &'a mut u8>, bytes: &[u8]) {
@@ -175,6 +230,6 @@ mod tests {
     }
 
     fn min_required_data(filename_length: usize, data_length: usize) -> usize {
-        (4 + filename_length + 4 + data_length) * 4
+        (LENGTH_SIZE + filename_length + LENGTH_SIZE + data_length) * 4
     }
 }
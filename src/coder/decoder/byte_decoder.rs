@@ -0,0 +1,104 @@
+use std::slice::Iter;
+
+use super::decode::Decode;
+
+pub struct ByteDecoder<'a> {
+    iter: Iter<'a, u8>,
+}
+
+impl<'a> ByteDecoder<'a> {
+    pub fn new(buffer: &'a [u8]) -> Self {
+        Self {
+            iter: buffer.iter(),
+        }
+    }
+}
+
+impl<'a> Decode for ByteDecoder<'a> {
+    fn decode_byte(&mut self) -> Option<u8> {
+        self.iter.next().copied()
+    }
+
+    fn max_bytes_to_decode(&self) -> usize {
+        self.iter.len()
+    }
+
+    /// Copies data at once, as collecting it byte by byte would leave copies in reallocated buffers.
+    fn decode_data(&mut self, length: usize) -> Option<Vec<u8>> {
+        let remaining = self.iter.as_slice();
+        let data = remaining.get(..length)?.to_vec();
+        self.iter = remaining[length..].iter();
+        Some(data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::coder::{decoder::decode::Decode, error::DecodeError, header::V1_LENGTH_SIZE};
+
+    use super::ByteDecoder;
+
+    #[test]
+    fn not_enough_data_to_decode_data() {
+        let buffer = vec![0, 0, 0, 0, 0, 0, 0, 1, b'x', 0, 0, 0, 0, 0, 0, 0, 2, b'y'];
+        let decoder = create_decoder(&buffer);
+        assert_eq!(
+            decoder
+                .decode()
+                .unwrap_err()
+                .downcast::<DecodeError>()
+                .unwrap(),
+            DecodeError("Not enough data to decode data".to_string())
+        );
+    }
+
+    #[test]
+    fn decode() {
+        let buffer = vec![
+            0, 0, 0, 0, 0, 0, 0, 5, // Filename length
+            b'x', b'.', b'p', b'n', b'g', // Filename
+            0, 0, 0, 0, 0, 0, 0, 4, // Message length
+            b'w', b'o', b'l', b'f', // Message
+        ];
+
+        let decoder = create_decoder(&buffer);
+        let (filename, data) = decoder.decode().unwrap();
+
+        assert_eq!(*filename, "x.png");
+        assert_eq!(*data, *b"wolf");
+    }
+
+    #[test]
+    fn reject_length_exceeding_capacity() {
+        let buffer = vec![0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, b'x'];
+        let decoder = create_decoder(&buffer);
+        assert_eq!(
+            decoder
+                .decode()
+                .unwrap_err()
+                .downcast::<DecodeError>()
+                .unwrap(),
+            DecodeError("Not enough data to decode filename".to_string())
+        );
+    }
+
+    #[test]
+    fn decode_32bit_lengths() {
+        let buffer = vec![
+            0, 0, 0, 5, // Filename length
+            b'x', b'.', b'p', b'n', b'g', // Filename
+            0, 0, 0, 4, // Message length
+            b'w', b'o', b'l', b'f', // Message
+        ];
+
+        let decoder = create_decoder(&buffer);
+        let (filename, data) = decoder.decode_sized(V1_LENGTH_SIZE).unwrap();
+
+        assert_eq!(*filename, "x.png");
+        assert_eq!(*data, *b"wolf");
+    }
+
+    fn create_decoder(buffer: &[u8]) -> Box<dyn Decode + '_> {
+        Box::new(ByteDecoder::new(buffer))
+    }
+}
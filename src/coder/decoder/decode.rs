@@ -1,43 +1,72 @@
 use anyhow::Result;
+use zeroize::Zeroizing;
 
-use crate::coder::error::DecodeError;
+use crate::coder::{error::DecodeError, header::LENGTH_SIZE, util::BITS_IN_BYTE};
 
 pub trait Decode {
-    fn decode(mut self: Box<Self>) -> Result<(String, Vec<u8>)> {
+    fn decode(self: Box<Self>) -> Result<(Zeroizing<String>, Zeroizing<Vec<u8>>)> {
+        self.decode_sized(LENGTH_SIZE)
+    }
+
+    /// Decodes the record with lengths stored on `length_size` bytes, as set by the format version.
+    fn decode_sized(
+        mut self: Box<Self>,
+        length_size: usize,
+    ) -> Result<(Zeroizing<String>, Zeroizing<Vec<u8>>)> {
         let file_name_length = self
-            .decode_length()
+            .decode_length(length_size)
             .ok_or(self.not_available("filename length"))?;
 
-        let file_name: Vec<u8> = self
-            .decode_data(file_name_length)
+        let file_name = self
+            .fit(file_name_length)
+            .and_then(|length| self.decode_data(length))
             .ok_or(self.not_available("filename"))?;
-        let file_name = String::from_utf8(file_name)?;
+        let file_name = String::from_utf8(file_name).map_err(|error| {
+            let message = error.utf8_error().to_string();
+            drop(Zeroizing::new(error.into_bytes()));
+            DecodeError(message)
+        })?;
+        let file_name = Zeroizing::new(file_name);
 
         let data_length = self
-            .decode_length()
+            .decode_length(length_size)
             .ok_or(self.not_available("data length"))?;
 
-        let data = self
-            .decode_data(data_length)
-            .ok_or(self.not_available("data"))?;
+        let data = Zeroizing::new(
+            self.fit(data_length)
+                .and_then(|length| self.decode_data(length))
+                .ok_or(self.not_available("data"))?,
+        );
 
         Ok((file_name, data))
     }
 
-    fn decode_length(&mut self) -> Option<usize> {
-        Some(u32::from_be_bytes([
-            self.decode_byte()?,
-            self.decode_byte()?,
-            self.decode_byte()?,
-            self.decode_byte()?,
-        ]) as usize)
+    fn decode_length(&mut self, length_size: usize) -> Option<u64> {
+        (0..length_size).try_fold(0, |length: u64, _| {
+            Some(length << BITS_IN_BYTE | self.decode_byte()? as u64)
+        })
+    }
+
+    /// Checks the stored length against the remaining capacity, before anything is allocated.
+    fn fit(&self, length: u64) -> Option<usize> {
+        usize::try_from(length)
+            .ok()
+            .filter(|length| *length <= self.max_bytes_to_decode())
     }
 
+    /// Decodes into a buffer allocated upfront, as collecting data byte by byte would leave copies
+    /// in reallocated buffers. Data decoded before running out of bytes is zeroized.
     fn decode_data(&mut self, length: usize) -> Option<Vec<u8>> {
-        (0..length).map(|_| self.decode_byte()).collect()
+        let mut data = Zeroizing::new(Vec::with_capacity(length));
+        for _ in 0..length {
+            data.push(self.decode_byte()?);
+        }
+        Some(std::mem::take(&mut *data))
     }
 
     fn decode_byte(&mut self) -> Option<u8>;
+    /// Number of bytes left in the carrier.
+    fn max_bytes_to_decode(&self) -> usize;
 
     fn not_available(&self, data_to_check: &str) -> DecodeError {
         DecodeError(format!("Not enough data to decode {data_to_check}"))
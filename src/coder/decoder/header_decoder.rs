@@ -1,27 +1,306 @@
+use std::{iter::Copied, slice};
+
 use anyhow::{anyhow, Result};
 
 use crate::coder::{
+    copies::{self, HEADER_COPIES},
     error::HeaderDecodeError,
-    header::{AlgHeader, AlphaHeader, Header, RgbHeader, ALPHA_MODE, RGB_MODE},
+    header::{
+        AlgHeader, AlphaHeader, ChecksumHeader, ChunkHeader, EncryptionHeader, FilterHeader,
+        Header, HeaderField, MetadataHeader, PaletteHeader, PassphraseHeader, RecipientHeader,
+        RecordHeader, RgbHeader, SignatureHeader, SvgHeader, TextHeader, ALPHA_MODE,
+        CHECKSUM_FIELD, CHUNK_MODE, CRC32_CHECKSUM, ED25519_SIGNATURE, ENCRYPTION_FIELD, END_FIELD,
+        FILE_METADATA, FILTER_MODE, FORMAT_VERSION, HEADER_CHECKSUM_LENGTH, LOW_BIT_HEADER_VERSION,
+        MAGIC, METADATA_FIELD, MODE_FIELD, NO_ENCRYPTION, NO_METADATA, NO_SIGNATURE,
+        OPTIONAL_FIELD, PALETTE_MODE, PASSPHRASE_ENCRYPTION, PLAIN_RECORD, RECIPIENT_ENCRYPTION,
+        RGB_MODE, SEALED_RECORD, SHA256_CHECKSUM, SIGNATURE_FIELD, SVG_MODE, TEXT_MODE,
+    },
+    layout::Layout,
+    sample::Sample,
+    util::BITS_IN_BYTE,
 };
 
-pub fn decode(buffer: &[u8]) -> Result<Header> {
-    let mut iter = buffer.iter().skip(3).step_by(4);
-    let mode = *iter.next().ok_or(HeaderDecodeError(
-        "Not enough data to decode mode.".to_string(),
+use super::is_no_payload;
+
+/// Error message of carriers without any header, as opposed to a damaged one.
+pub(super) const NO_PAYLOAD: &str = "No mythic-telegram payload found.";
+
+/// Reads the header from the least significant bit of samples, most significant bit of each byte
+/// first, as written by the header encoder. Copies are tried in order and the first one passing
+/// its checksum is used. When all are damaged, their bitwise majority is tried, reporting the
+/// error of the first copy if it fails too. Images without a header there are read as written
+/// before format version 4.
+pub fn decode<T: Sample>(layout: Layout, buffer: &[T]) -> Result<Header> {
+    let copies = copies::offsets(buffer.len(), layout).map(|offset| &buffer[offset..]);
+    let header = decode_stream(decode_low_bits(copies[0]), T::BITS).or_else(|error| {
+        copies[1..]
+            .iter()
+            .map(|copy| decode_stream(decode_low_bits(copy), T::BITS))
+            .find(Result::is_ok)
+            .unwrap_or_else(|| decode_majority(copies).map_err(|_| error))
+    });
+    match header {
+        Err(error) if is_no_payload(&error) => decode_whole_bytes(layout, buffer),
+        header => check_storage(header?, true),
+    }
+}
+
+/// Reads the header from the bitwise majority of the copies, which repairs bits flipped in no more
+/// than one copy.
+fn decode_majority<T: Sample>(copies: [&[T]; HEADER_COPIES]) -> Result<Header> {
+    let [first, second, third] = copies.map(decode_low_bits);
+    let majority = first
+        .zip(second)
+        .zip(third)
+        .map(|((a, b), c)| a & b | a & c | b & c);
+    decode_stream(majority, T::BITS)
+}
+
+/// Bytes held in the least significant bit of samples, eight samples per byte.
+fn decode_low_bits<T: Sample>(buffer: &[T]) -> impl Iterator<Item = u8> + '_ {
+    buffer.chunks_exact(BITS_IN_BYTE as usize).map(decode_byte)
+}
+
+/// Reads the header of format versions before 4, stored as whole bytes in the low bits of the
+/// alpha mode channel.
+fn decode_whole_bytes<T: Sample>(layout: Layout, buffer: &[T]) -> Result<Header> {
+    let bytes = buffer
+        .iter()
+        .skip(layout.alpha_mode_channel())
+        .step_by(layout.channels())
+        .map(|sample| sample.low_bits(BITS_IN_BYTE));
+    check_storage(decode_stream(bytes, T::BITS)?, false)
+}
+
+/// Rejects a header stored in image samples differently than its version specifies.
+fn check_storage(header: Header, low_bits: bool) -> Result<Header> {
+    if (header.version >= LOW_BIT_HEADER_VERSION) != low_bits {
+        return Err(anyhow!(HeaderDecodeError(format!(
+            "Header of format version {} is not stored where this version stores it.",
+            header.version
+        ))));
+    }
+    Ok(header)
+}
+
+/// Reads a byte from the least significant bit of samples, most significant bit first.
+fn decode_byte<T: Sample>(samples: &[T]) -> u8 {
+    samples
+        .iter()
+        .fold(0, |byte, sample| byte << 1 | sample.low_bits(1))
+}
+
+/// Reads the header from leading bytes of a raw byte stream.
+pub fn decode_bytes(buffer: &[u8]) -> Result<Header> {
+    decode_stream(buffer.iter().copied(), u8::BITS as u8)
+}
+
+fn decode_stream<I>(iter: I, sample_bits: u8) -> Result<Header>
+where
+    I: Iterator<Item = u8>,
+{
+    let mut iter = Recorded {
+        iter,
+        bytes: Vec::new(),
+    };
+    let mut magic = [0; MAGIC.len()];
+    for byte in magic.iter_mut() {
+        *byte = iter.next().unwrap_or(0);
+    }
+    if magic != MAGIC {
+        return Err(anyhow!(HeaderDecodeError(NO_PAYLOAD.to_string())));
+    }
+
+    let version = next_byte(&mut iter, "format version")?;
+    let header = match version {
+        1 | 2 => decode_fixed_fields(&mut iter, version, sample_bits)?,
+        3..=FORMAT_VERSION => decode_tagged_fields(&mut iter, version, sample_bits)?,
+        _ => {
+            return Err(anyhow!(HeaderDecodeError(format!(
+                "Unsupported format version {}, this build reads up to {}.",
+                version, FORMAT_VERSION
+            ))))
+        }
+    };
+
+    let hash = crc32fast::hash(&iter.bytes);
+    let mut checksum = [0; HEADER_CHECKSUM_LENGTH];
+    for byte in checksum.iter_mut() {
+        *byte = next_byte(&mut iter, "header checksum")?;
+    }
+    if u32::from_be_bytes(checksum) != hash {
+        return Err(anyhow!(HeaderDecodeError(
+            "Header checksum mismatch, the header is corrupted.".to_string()
+        )));
+    }
+    Ok(header)
+}
+
+/// Byte iterator keeping a copy of the bytes read, for the header checksum.
+struct Recorded<I> {
+    iter: I,
+    bytes: Vec<u8>,
+}
+
+impl<I: Iterator<Item = u8>> Iterator for Recorded<I> {
+    type Item = u8;
+
+    fn next(&mut self) -> Option<u8> {
+        let byte = self.iter.next()?;
+        self.bytes.push(byte);
+        Some(byte)
+    }
+}
+
+/// Decodes field values stored one after another by versions 1 and 2. Version 1 lacks the
+/// metadata method.
+fn decode_fixed_fields<I>(iter: &mut I, version: u8, sample_bits: u8) -> Result<Header>
+where
+    I: Iterator<Item = u8>,
+{
+    let (mode, sample_bits, alg_header) = decode_mode(iter, sample_bits)?;
+    let (encryption, record) = decode_encryption(iter)?;
+    let signature = decode_signature(iter)?;
+    let header = Header::new(mode, sample_bits, alg_header)
+        .with_version(version)
+        .with_encryption(encryption)
+        .with_record(record)
+        .with_signature(signature);
+    let header = if header.has_metadata_field() {
+        header.with_metadata(decode_metadata(iter)?)
+    } else {
+        header
+    };
+    let checksum = decode_checksum(iter)?;
+    Ok(header.with_checksum(checksum))
+}
+
+/// Decodes fields stored as type, length and value, keeping unknown optional fields aside.
+fn decode_tagged_fields<I>(iter: &mut I, version: u8, sample_bits: u8) -> Result<Header>
+where
+    I: Iterator<Item = u8>,
+{
+    let mut mode = None;
+    let mut encryption = (EncryptionHeader::None, RecordHeader::Plain);
+    let mut signature = SignatureHeader::None;
+    let mut metadata = MetadataHeader::None;
+    let mut checksum = None;
+    let mut unknown_fields = Vec::new();
+    let mut previous_type = END_FIELD;
+    loop {
+        let field_type = next_byte(iter, "header field")?;
+        if field_type == END_FIELD {
+            break;
+        }
+        // Ascending order makes the header serialize back unchanged, which the CRC32 and
+        // signature rely on
+        if field_type <= previous_type {
+            return Err(anyhow!(HeaderDecodeError(
+                "Header fields are out of order or repeated.".to_string()
+            )));
+        }
+        previous_type = field_type;
+        let length = next_byte(iter, "header field")? as usize;
+        let value: Vec<u8> = iter.by_ref().take(length).collect();
+        if value.len() != length {
+            return Err(anyhow!(HeaderDecodeError(
+                "Not enough data to decode header field.".to_string()
+            )));
+        }
+
+        match field_type {
+            MODE_FIELD => {
+                mode = Some(decode_field(field_type, &value, |iter| {
+                    decode_mode(iter, sample_bits)
+                })?)
+            }
+            ENCRYPTION_FIELD => encryption = decode_field(field_type, &value, decode_encryption)?,
+            SIGNATURE_FIELD => signature = decode_field(field_type, &value, decode_signature)?,
+            METADATA_FIELD => metadata = decode_field(field_type, &value, decode_metadata)?,
+            CHECKSUM_FIELD => checksum = Some(decode_field(field_type, &value, decode_checksum)?),
+            _ if field_type & OPTIONAL_FIELD != 0 => {
+                unknown_fields.push(HeaderField { field_type, value })
+            }
+            _ => {
+                return Err(anyhow!(HeaderDecodeError(format!(
+                "Unsupported header field {}, a newer version is needed to decode this payload.",
+                field_type
+            ))))
+            }
+        }
+    }
+
+    let (mode, sample_bits, alg_header) =
+        mode.ok_or(HeaderDecodeError("Header has no mode field.".to_string()))?;
+    let checksum = checksum.ok_or(HeaderDecodeError(
+        "Header has no checksum field.".to_string(),
     ))?;
+    let (encryption, record) = encryption;
+    Header::new(mode, sample_bits, alg_header)
+        .with_version(version)
+        .with_encryption(encryption)
+        .with_record(record)
+        .with_signature(signature)
+        .with_metadata(metadata)
+        .with_checksum(checksum)
+        .with_unknown_fields(unknown_fields)
+}
 
-    let alg_header = decode_alg_header(mode, &mut iter)?;
-    Ok(Header::new(mode, alg_header))
+/// Decodes a field value, which must be consumed whole.
+fn decode_field<'a, V, F>(field_type: u8, value: &'a [u8], decode: F) -> Result<V>
+where
+    F: FnOnce(&mut Copied<slice::Iter<'a, u8>>) -> Result<V>,
+{
+    let mut iter = value.iter().copied();
+    let decoded = decode(&mut iter)?;
+    if iter.next().is_some() {
+        return Err(anyhow!(HeaderDecodeError(format!(
+            "Invalid length of header field {}.",
+            field_type
+        ))));
+    }
+    Ok(decoded)
 }
 
-fn decode_alg_header<'a, I>(mode: u8, iter: &mut I) -> Result<AlgHeader>
+fn next_byte<I>(iter: &mut I, name: &str) -> Result<u8>
 where
-    I: Iterator<Item = &'a u8>,
+    I: Iterator<Item = u8>,
+{
+    iter.next().ok_or(anyhow!(HeaderDecodeError(format!(
+        "Not enough data to decode {}.",
+        name
+    ))))
+}
+
+/// Decodes mode, sample width and mode specific parameters. The sample width has to match
+/// `sample_bits` of the image.
+fn decode_mode<I>(iter: &mut I, sample_bits: u8) -> Result<(u8, u8, AlgHeader)>
+where
+    I: Iterator<Item = u8>,
+{
+    let mode = next_byte(iter, "mode")?;
+    let encoded_bits = next_byte(iter, "sample width")?;
+    if encoded_bits != sample_bits {
+        return Err(anyhow!(HeaderDecodeError(format!(
+            "Sample width mismatch: encoded on {} bits, image has {} bits.",
+            encoded_bits, sample_bits
+        ))));
+    }
+    Ok((mode, sample_bits, decode_alg_header(mode, iter)?))
+}
+
+fn decode_alg_header<I>(mode: u8, iter: &mut I) -> Result<AlgHeader>
+where
+    I: Iterator<Item = u8>,
 {
     match mode {
         ALPHA_MODE => Ok(AlgHeader::Alpha(decode_alpha())),
         RGB_MODE => Ok(AlgHeader::Rgb(decode_rgb(iter)?)),
+        CHUNK_MODE => Ok(AlgHeader::Chunk(decode_chunk())),
+        FILTER_MODE => Ok(AlgHeader::Filter(decode_filter())),
+        PALETTE_MODE => Ok(AlgHeader::Palette(decode_palette())),
+        TEXT_MODE => Ok(AlgHeader::Text(decode_text())),
+        SVG_MODE => Ok(AlgHeader::Svg(decode_svg())),
         _ => Err(anyhow!(HeaderDecodeError(
             "Unknown mode in header.".to_string()
         ))),
@@ -32,85 +311,740 @@ fn decode_alpha() -> AlphaHeader {
     AlphaHeader {}
 }
 
-fn decode_rgb<'a, I>(iter: &mut I) -> Result<RgbHeader>
+fn decode_chunk() -> ChunkHeader {
+    ChunkHeader {}
+}
+
+fn decode_filter() -> FilterHeader {
+    FilterHeader {}
+}
+
+fn decode_palette() -> PaletteHeader {
+    PaletteHeader {}
+}
+
+fn decode_text() -> TextHeader {
+    TextHeader {}
+}
+
+fn decode_svg() -> SvgHeader {
+    SvgHeader {}
+}
+
+fn decode_rgb<I>(iter: &mut I) -> Result<RgbHeader>
 where
-    I: Iterator<Item = &'a u8>,
+    I: Iterator<Item = u8>,
 {
-    let bits_per_channel = *iter.next().ok_or(HeaderDecodeError(
-        "Not enough data to decode bits per channel.".to_string(),
-    ))?;
-
+    let bits_per_channel = next_byte(iter, "bits per channel")?;
     Ok(RgbHeader { bits_per_channel })
 }
 
+/// Decodes encryption method and parameters, followed by the record method when encrypted.
+fn decode_encryption<I>(iter: &mut I) -> Result<(EncryptionHeader, RecordHeader)>
+where
+    I: Iterator<Item = u8>,
+{
+    let [method] = decode_encryption_bytes(iter)?;
+    let encryption = match method {
+        NO_ENCRYPTION => return Ok((EncryptionHeader::None, RecordHeader::Plain)),
+        PASSPHRASE_ENCRYPTION => EncryptionHeader::Passphrase(PassphraseHeader {
+            salt: decode_encryption_bytes(iter)?,
+            nonce: decode_encryption_bytes(iter)?,
+            memory_cost: u32::from_be_bytes(decode_encryption_bytes(iter)?),
+            time_cost: u32::from_be_bytes(decode_encryption_bytes(iter)?),
+            parallelism: u32::from_be_bytes(decode_encryption_bytes(iter)?),
+        }),
+        RECIPIENT_ENCRYPTION => EncryptionHeader::Recipient(RecipientHeader {}),
+        _ => {
+            return Err(anyhow!(HeaderDecodeError(
+                "Unknown encryption method in header.".to_string()
+            )))
+        }
+    };
+    Ok((encryption, decode_record(iter)?))
+}
+
+fn decode_record<I>(iter: &mut I) -> Result<RecordHeader>
+where
+    I: Iterator<Item = u8>,
+{
+    let [method] = decode_encryption_bytes(iter)?;
+    match method {
+        PLAIN_RECORD => Ok(RecordHeader::Plain),
+        SEALED_RECORD => Ok(RecordHeader::Sealed),
+        _ => Err(anyhow!(HeaderDecodeError(
+            "Unknown record encryption in header.".to_string()
+        ))),
+    }
+}
+
+fn decode_signature<I>(iter: &mut I) -> Result<SignatureHeader>
+where
+    I: Iterator<Item = u8>,
+{
+    match next_byte(iter, "signature")? {
+        NO_SIGNATURE => Ok(SignatureHeader::None),
+        ED25519_SIGNATURE => Ok(SignatureHeader::Ed25519),
+        _ => Err(anyhow!(HeaderDecodeError(
+            "Unknown signature method in header.".to_string()
+        ))),
+    }
+}
+
+fn decode_metadata<I>(iter: &mut I) -> Result<MetadataHeader>
+where
+    I: Iterator<Item = u8>,
+{
+    match next_byte(iter, "metadata method")? {
+        NO_METADATA => Ok(MetadataHeader::None),
+        FILE_METADATA => Ok(MetadataHeader::Attributes),
+        _ => Err(anyhow!(HeaderDecodeError(
+            "Unknown metadata method in header.".to_string()
+        ))),
+    }
+}
+
+fn decode_checksum<I>(iter: &mut I) -> Result<ChecksumHeader>
+where
+    I: Iterator<Item = u8>,
+{
+    match next_byte(iter, "checksum method")? {
+        CRC32_CHECKSUM => Ok(ChecksumHeader::Crc32),
+        SHA256_CHECKSUM => Ok(ChecksumHeader::Sha256),
+        _ => Err(anyhow!(HeaderDecodeError(
+            "Unknown checksum method in header.".to_string()
+        ))),
+    }
+}
+
+fn decode_encryption_bytes<I, const N: usize>(iter: &mut I) -> Result<[u8; N]>
+where
+    I: Iterator<Item = u8>,
+{
+    let mut bytes = [0; N];
+    for byte in bytes.iter_mut() {
+        *byte = next_byte(iter, "encryption")?;
+    }
+    Ok(bytes)
+}
+
 #[cfg(test)]
 mod tests {
     use crate::coder::{
+        copies::{self, HEADER_COPIES},
         error::HeaderDecodeError,
-        header::{Header, ALPHA_MODE, RGB_MODE},
+        header::{
+            ChecksumHeader, EncryptionHeader, Header, HeaderField, MetadataHeader,
+            PassphraseHeader, RecipientHeader, RecordHeader, SignatureHeader, ALPHA_MODE,
+            CHECKSUM_FIELD, CHUNK_MODE, CRC32_CHECKSUM, ED25519_SIGNATURE, ENCRYPTION_FIELD,
+            END_FIELD, FILE_METADATA, FILTER_MODE, FORMAT_VERSION, MAGIC, METADATA_FIELD,
+            MODE_FIELD, NONCE_LENGTH, NO_ENCRYPTION, NO_METADATA, NO_SIGNATURE, OPTIONAL_FIELD,
+            PASSPHRASE_ENCRYPTION, PLAIN_RECORD, RECIPIENT_ENCRYPTION, RGB_MODE, SALT_LENGTH,
+            SEALED_RECORD, SHA256_CHECKSUM, SIGNATURE_FIELD, V1_LENGTH_SIZE,
+        },
+        layout::Layout,
+        sample::Sample,
+        util::BITS_IN_BYTE,
     };
 
+    /// Samples set to `fill` with the bits of `bytes` in their least significant bit.
+    fn spread<T: Sample>(fill: T, bytes: &[u8]) -> Vec<T> {
+        bytes
+            .iter()
+            .flat_map(|&byte| (0..BITS_IN_BYTE).rev().map(move |i| (byte >> i) & 1))
+            .map(|bit| fill.with_low_bits(1, bit))
+            .collect()
+    }
+
+    /// Buffer holding magic, `version` and `bytes`, one bit per sample, with other bits of the
+    /// samples taken from `fill`.
+    fn with_preamble<T: Sample>(fill: T, version: u8, bytes: &[u8]) -> Vec<T> {
+        let header: Vec<u8> = MAGIC
+            .iter()
+            .chain([version].iter())
+            .chain(bytes)
+            .copied()
+            .collect();
+        spread(fill, &header)
+    }
+
+    /// Appends CRC32 of the header bits in `buffer`.
+    fn with_checksum<T: Sample>(mut buffer: Vec<T>, fill: T) -> Vec<T> {
+        let bytes: Vec<u8> = buffer
+            .chunks_exact(BITS_IN_BYTE as usize)
+            .map(|bits| bits.iter().fold(0, |byte, s| byte << 1 | s.low_bits(1)))
+            .collect();
+        buffer.extend(spread(fill, &crc32fast::hash(&bytes).to_be_bytes()));
+        buffer
+    }
+
+    /// Header of format versions before 4 holding `version` and `bytes`, one byte per pixel in the
+    /// alpha mode channel, with other channels set to `fill`.
+    fn whole_bytes<T: Sample>(layout: Layout, fill: T, version: u8, bytes: &[u8]) -> Vec<T> {
+        let mut header: Vec<u8> = MAGIC
+            .iter()
+            .chain([version].iter())
+            .chain(bytes)
+            .copied()
+            .collect();
+        header.extend(crc32fast::hash(&header).to_be_bytes());
+        header
+            .into_iter()
+            .flat_map(|byte| {
+                let mut pixel = vec![fill; layout.channels()];
+                pixel[layout.alpha_mode_channel()] = fill.with_low_bits(BITS_IN_BYTE, byte);
+                pixel
+            })
+            .collect()
+    }
+
+    /// Fields stored as type, length and value, ended by the end field.
+    fn tagged(fields: &[(u8, &[u8])]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        for (field_type, value) in fields {
+            bytes.push(*field_type);
+            bytes.push(value.len() as u8);
+            bytes.extend_from_slice(value);
+        }
+        bytes.push(END_FIELD);
+        bytes
+    }
+
+    /// Header of the current version with `fields` and CRC32, stored in a grayscale image.
+    fn tagged_header(fields: &[(u8, &[u8])]) -> Vec<u8> {
+        let buffer = with_preamble(0u8, FORMAT_VERSION, &tagged(fields));
+        with_checksum(buffer, 0)
+    }
+
+    fn decode_error(buffer: &[u8]) -> HeaderDecodeError {
+        super::decode(Layout::L, buffer)
+            .unwrap_err()
+            .downcast::<HeaderDecodeError>()
+            .unwrap()
+    }
+
+    const CHUNK: (u8, &[u8]) = (MODE_FIELD, &[CHUNK_MODE, 8]);
+    const SHA256: (u8, &[u8]) = (CHECKSUM_FIELD, &[SHA256_CHECKSUM]);
+
     #[test]
     fn decode_alpha() {
-        let mut buffer = vec![0; 4];
-        let mut iter = buffer.iter_mut().skip(3).step_by(4);
-        *iter.next().unwrap() = ALPHA_MODE;
+        let bytes = tagged(&[(MODE_FIELD, &[ALPHA_MODE, 8]), SHA256]);
+        let buffer = with_preamble(0u8, FORMAT_VERSION, &bytes);
+        let buffer = with_checksum(buffer, 0);
 
-        let decoded = super::decode(&buffer).unwrap();
-        assert_eq!(decoded, Header::new_alpha());
+        let decoded = super::decode(Layout::L, &buffer).unwrap();
+        assert_eq!(decoded, Header::new_alpha(8));
     }
 
     #[test]
     fn decode_rgb() {
         let bits_per_channel = 2;
-        let mut buffer = vec![0; 8];
-        let mut iter = buffer.iter_mut().skip(3).step_by(4);
-        *iter.next().unwrap() = RGB_MODE;
-        *iter.next().unwrap() = bits_per_channel;
+        let bytes = tagged(&[(MODE_FIELD, &[RGB_MODE, 8, bits_per_channel]), SHA256]);
+        let buffer = with_preamble(0u8, FORMAT_VERSION, &bytes);
+        let buffer = with_checksum(buffer, 0);
 
-        let decoded = super::decode(&buffer).unwrap();
-        assert_eq!(decoded, Header::new_rgb(bits_per_channel));
+        let decoded = super::decode(Layout::L, &buffer).unwrap();
+        assert_eq!(decoded, Header::new_rgb(8, bits_per_channel));
     }
 
     #[test]
-    fn decode_error_missing_mode_data() {
-        let buffer = Vec::new();
-        let decoded = super::decode(&buffer);
+    fn decode_rgb_16bit() {
+        let bits_per_channel = 4;
+        let bytes = tagged(&[(MODE_FIELD, &[RGB_MODE, 16, bits_per_channel]), SHA256]);
+        let buffer = with_preamble(0xFFFFu16, FORMAT_VERSION, &bytes);
+        let buffer = with_checksum(buffer, 0xFFFF);
+        assert!(buffer.iter().all(|&sample| sample | 1 == 0xFFFF));
+
+        let decoded = super::decode(Layout::L, &buffer).unwrap();
+        assert_eq!(decoded, Header::new_rgb(16, bits_per_channel));
+    }
+
+    #[test]
+    fn decode_rgb_without_alpha() {
+        let bits_per_channel = 4;
+        let bytes = tagged(&[(MODE_FIELD, &[RGB_MODE, 8, bits_per_channel]), SHA256]);
+        let buffer = with_preamble(0u8, FORMAT_VERSION, &bytes);
+        let buffer = with_checksum(buffer, 0);
+
+        let decoded = super::decode(Layout::L, &buffer).unwrap();
+        assert_eq!(decoded, Header::new_rgb(8, bits_per_channel));
+    }
+
+    #[test]
+    fn decode_alpha_grayscale() {
+        let bytes = tagged(&[(MODE_FIELD, &[ALPHA_MODE, 8]), SHA256]);
+        let buffer = with_preamble(0u8, FORMAT_VERSION, &bytes);
+        let buffer = with_checksum(buffer, 0);
+
+        let decoded = super::decode(Layout::L, &buffer).unwrap();
+        assert_eq!(decoded, Header::new_alpha(8));
+    }
+
+    #[test]
+    fn decode_chunk() {
+        let decoded = super::decode(Layout::L, &tagged_header(&[CHUNK, SHA256])).unwrap();
+        assert_eq!(decoded, Header::new_chunk());
+    }
+
+    #[test]
+    fn decode_bytes() {
+        let header = Header::new_chunk().with_signature(SignatureHeader::Ed25519);
+        let mut buffer = header.to_bytes();
+        buffer.extend_from_slice(b"data");
+        assert_eq!(super::decode_bytes(&buffer).unwrap(), header);
+
+        buffer[MAGIC.len() + 3] = FILTER_MODE;
         assert_eq!(
-            decoded
+            super::decode_bytes(&buffer)
                 .unwrap_err()
                 .downcast::<HeaderDecodeError>()
                 .unwrap(),
-            HeaderDecodeError("Not enough data to decode mode.".to_string())
+            HeaderDecodeError("Header checksum mismatch, the header is corrupted.".to_string())
         );
     }
 
+    /// Buffer of `samples` holding `header` at every copy offset, other samples set to `fill`.
+    fn with_copies(header: &[u8], samples: usize, fill: u8) -> Vec<u8> {
+        let mut buffer = vec![fill; samples];
+        for offset in copies::offsets(samples, Layout::L) {
+            buffer[offset..offset + header.len()].copy_from_slice(header);
+        }
+        buffer
+    }
+
     #[test]
-    fn decode_error_missing_bits_per_channel_data() {
-        let buffer = vec![0, 0, 0, RGB_MODE];
-        let decoded = super::decode(&buffer);
+    fn decode_middle_copy() {
+        let header = tagged_header(&[CHUNK, SHA256]);
+        let mut buffer = with_copies(&header, header.len() * HEADER_COPIES + 30, 0xAA);
+        // Only the middle copy is intact
+        let [first, _, last] = copies::offsets(buffer.len(), Layout::L);
+        buffer[first..first + header.len()].fill(0xFF);
+        buffer[last..last + header.len()].fill(0);
+
+        let decoded = super::decode(Layout::L, &buffer).unwrap();
+        assert_eq!(decoded, Header::new_chunk());
+    }
+
+    #[test]
+    fn decode_majority() {
+        let header = tagged_header(&[CHUNK, SHA256]);
+        let mut buffer = with_copies(&header, header.len() * HEADER_COPIES, 0);
+        // Every copy has a different byte flipped, so none passes its checksum alone
+        for (copy, offset) in copies::offsets(buffer.len(), Layout::L)
+            .into_iter()
+            .enumerate()
+        {
+            let byte = offset + (MAGIC.len() + 3 + copy) * BITS_IN_BYTE as usize;
+            buffer[byte] ^= 1;
+        }
+
+        let decoded = super::decode(Layout::L, &buffer).unwrap();
+        assert_eq!(decoded, Header::new_chunk());
+
+        let offset = copies::offsets(buffer.len(), Layout::L)[1];
+        buffer[offset + (MAGIC.len() + 3) * BITS_IN_BYTE as usize] ^= 1;
+        // The majority keeps a bit flipped in two copies, reporting the error of the first one
+        assert_eq!(
+            decode_error(&buffer),
+            HeaderDecodeError("Unknown mode in header.".to_string())
+        );
+    }
+
+    #[test]
+    fn decode_filter() {
+        let buffer = tagged_header(&[(MODE_FIELD, &[FILTER_MODE, 8]), SHA256]);
+        let decoded = super::decode(Layout::L, &buffer).unwrap();
+        assert_eq!(decoded, Header::new_filter());
+    }
+
+    #[test]
+    fn decode_passphrase_encryption() {
+        let mut encryption = vec![PASSPHRASE_ENCRYPTION];
+        encryption.extend_from_slice(&[0xAA; SALT_LENGTH]);
+        encryption.extend_from_slice(&[0xBB; NONCE_LENGTH]);
+        encryption.extend_from_slice(&[0, 0, 1, 2, 0, 0, 0, 3, 0, 0, 0, 4]);
+        encryption.push(PLAIN_RECORD);
+        let buffer = tagged_header(&[CHUNK, (ENCRYPTION_FIELD, &encryption), SHA256]);
+
+        let decoded = super::decode(Layout::L, &buffer).unwrap();
+        assert_eq!(
+            decoded,
+            Header::new_chunk().with_encryption(EncryptionHeader::Passphrase(PassphraseHeader {
+                salt: [0xAA; SALT_LENGTH],
+                nonce: [0xBB; NONCE_LENGTH],
+                memory_cost: 0x0102,
+                time_cost: 3,
+                parallelism: 4,
+            }))
+        );
+    }
+
+    #[test]
+    fn decode_recipient_encryption() {
+        let buffer = tagged_header(&[
+            CHUNK,
+            (ENCRYPTION_FIELD, &[RECIPIENT_ENCRYPTION, SEALED_RECORD]),
+            SHA256,
+        ]);
+        let decoded = super::decode(Layout::L, &buffer).unwrap();
+        assert_eq!(
+            decoded,
+            Header::new_chunk()
+                .with_encryption(EncryptionHeader::Recipient(RecipientHeader {}))
+                .with_record(RecordHeader::Sealed)
+        );
+    }
+
+    #[test]
+    fn decode_signature() {
+        let buffer = tagged_header(&[CHUNK, (SIGNATURE_FIELD, &[ED25519_SIGNATURE]), SHA256]);
+        let decoded = super::decode(Layout::L, &buffer).unwrap();
+        assert_eq!(
+            decoded,
+            Header::new_chunk().with_signature(SignatureHeader::Ed25519)
+        );
+    }
+
+    #[test]
+    fn decode_metadata() {
+        let buffer = tagged_header(&[CHUNK, (METADATA_FIELD, &[FILE_METADATA]), SHA256]);
+        let decoded = super::decode(Layout::L, &buffer).unwrap();
+        assert_eq!(
+            decoded,
+            Header::new_chunk().with_metadata(MetadataHeader::Attributes)
+        );
+    }
+
+    #[test]
+    fn decode_crc32_checksum() {
+        let buffer = tagged_header(&[CHUNK, (CHECKSUM_FIELD, &[CRC32_CHECKSUM])]);
+        let decoded = super::decode(Layout::L, &buffer).unwrap();
+        assert_eq!(
+            decoded,
+            Header::new_chunk().with_checksum(ChecksumHeader::Crc32)
+        );
+    }
+
+    #[test]
+    fn decode_unknown_optional_field() {
+        let buffer = tagged_header(&[CHUNK, SHA256, (OPTIONAL_FIELD | 1, &[1, 2, 3])]);
+        let decoded = super::decode(Layout::L, &buffer).unwrap();
+        assert_eq!(
+            decoded,
+            Header::new_chunk()
+                .with_unknown_fields(vec![HeaderField {
+                    field_type: OPTIONAL_FIELD | 1,
+                    value: vec![1, 2, 3],
+                }])
+                .unwrap()
+        );
+        assert_eq!(spread(0, &decoded.to_bytes()), buffer);
+    }
+
+    #[test]
+    fn decode_error_unsupported_field() {
+        let buffer = tagged_header(&[CHUNK, SHA256, (CHECKSUM_FIELD + 1, &[0])]);
+        assert_eq!(
+            decode_error(&buffer),
+            HeaderDecodeError(format!(
+                "Unsupported header field {}, a newer version is needed to decode this payload.",
+                CHECKSUM_FIELD + 1
+            ))
+        );
+    }
+
+    #[test]
+    fn decode_error_fields_out_of_order() {
+        let buffer = tagged_header(&[SHA256, CHUNK]);
+        assert_eq!(
+            decode_error(&buffer),
+            HeaderDecodeError("Header fields are out of order or repeated.".to_string())
+        );
+
+        let buffer = tagged_header(&[CHUNK, CHUNK, SHA256]);
+        assert_eq!(
+            decode_error(&buffer),
+            HeaderDecodeError("Header fields are out of order or repeated.".to_string())
+        );
+    }
+
+    #[test]
+    fn decode_error_invalid_field_length() {
+        let buffer = tagged_header(&[(MODE_FIELD, &[CHUNK_MODE, 8, 0]), SHA256]);
+        assert_eq!(
+            decode_error(&buffer),
+            HeaderDecodeError(format!("Invalid length of header field {}.", MODE_FIELD))
+        );
+    }
+
+    #[test]
+    fn decode_error_missing_fields() {
+        assert_eq!(
+            decode_error(&tagged_header(&[SHA256])),
+            HeaderDecodeError("Header has no mode field.".to_string())
+        );
+        assert_eq!(
+            decode_error(&tagged_header(&[CHUNK])),
+            HeaderDecodeError("Header has no checksum field.".to_string())
+        );
+    }
+
+    #[test]
+    fn decode_error_missing_field_data() {
+        let buffer = with_preamble(0u8, FORMAT_VERSION, &[MODE_FIELD, 2, CHUNK_MODE]);
+        assert_eq!(
+            decode_error(&buffer),
+            HeaderDecodeError("Not enough data to decode header field.".to_string())
+        );
+
+        let buffer = with_preamble(0u8, FORMAT_VERSION, &[MODE_FIELD]);
+        assert_eq!(
+            decode_error(&buffer),
+            HeaderDecodeError("Not enough data to decode header field.".to_string())
+        );
+    }
+
+    #[test]
+    fn decode_error_unknown_metadata() {
+        let buffer = tagged_header(&[CHUNK, (METADATA_FIELD, &[0xFF]), SHA256]);
+        assert_eq!(
+            decode_error(&buffer),
+            HeaderDecodeError("Unknown metadata method in header.".to_string())
+        );
+    }
+
+    #[test]
+    fn decode_error_unknown_checksum() {
+        let buffer = tagged_header(&[CHUNK, (CHECKSUM_FIELD, &[0xFF])]);
+        assert_eq!(
+            decode_error(&buffer),
+            HeaderDecodeError("Unknown checksum method in header.".to_string())
+        );
+    }
+
+    #[test]
+    fn decode_error_missing_header_checksum() {
+        let buffer = with_preamble(0u8, FORMAT_VERSION, &tagged(&[CHUNK, SHA256]));
+        assert_eq!(
+            decode_error(&buffer),
+            HeaderDecodeError("Not enough data to decode header checksum.".to_string())
+        );
+    }
+
+    #[test]
+    fn decode_error_corrupted_header() {
+        let mut buffer = tagged_header(&[CHUNK, SHA256]);
+        // Filter mode instead of chunk mode still parses, only the checksum tells
+        let mode = (MAGIC.len() + 3) * BITS_IN_BYTE as usize;
+        buffer.splice(
+            mode..mode + BITS_IN_BYTE as usize,
+            spread(0, &[FILTER_MODE]),
+        );
+        assert_eq!(
+            decode_error(&buffer),
+            HeaderDecodeError("Header checksum mismatch, the header is corrupted.".to_string())
+        );
+    }
+
+    #[test]
+    fn decode_error_missing_signature() {
+        let buffer = tagged_header(&[CHUNK, (SIGNATURE_FIELD, &[]), SHA256]);
+        assert_eq!(
+            decode_error(&buffer),
+            HeaderDecodeError("Not enough data to decode signature.".to_string())
+        );
+    }
+
+    #[test]
+    fn decode_error_unknown_signature() {
+        let buffer = tagged_header(&[CHUNK, (SIGNATURE_FIELD, &[0xFF]), SHA256]);
+        assert_eq!(
+            decode_error(&buffer),
+            HeaderDecodeError("Unknown signature method in header.".to_string())
+        );
+    }
+
+    #[test]
+    fn decode_error_unknown_record() {
+        let buffer = tagged_header(&[CHUNK, (ENCRYPTION_FIELD, &[RECIPIENT_ENCRYPTION, 0xFF])]);
+        assert_eq!(
+            decode_error(&buffer),
+            HeaderDecodeError("Unknown record encryption in header.".to_string())
+        );
+    }
+
+    #[test]
+    fn decode_error_missing_encryption_data() {
+        let buffer = tagged_header(&[CHUNK, (ENCRYPTION_FIELD, &[PASSPHRASE_ENCRYPTION, 0xAA])]);
+        assert_eq!(
+            decode_error(&buffer),
+            HeaderDecodeError("Not enough data to decode encryption.".to_string())
+        );
+    }
+
+    #[test]
+    fn decode_error_unknown_encryption() {
+        let buffer = tagged_header(&[CHUNK, (ENCRYPTION_FIELD, &[0xFF])]);
+        assert_eq!(
+            decode_error(&buffer),
+            HeaderDecodeError("Unknown encryption method in header.".to_string())
+        );
+    }
+
+    #[test]
+    fn decode_error_no_payload() {
+        let buffer: Vec<u8> = vec![0xFF; 40];
+        let decoded = super::decode(Layout::L, &buffer);
         assert_eq!(
             decoded
                 .unwrap_err()
                 .downcast::<HeaderDecodeError>()
                 .unwrap(),
-            HeaderDecodeError("Not enough data to decode bits per channel.".to_string())
+            HeaderDecodeError("No mythic-telegram payload found.".to_string())
         );
-    }
-
-    #[test]
-    fn decode_error_unknown_mode() {
-        let unknown_mode = 4;
-        let mut buffer = vec![0; 4];
-        let mut iter = buffer.iter_mut().skip(3).step_by(4);
-        *iter.next().unwrap() = unknown_mode;
 
-        let decoded = super::decode(&buffer);
+        let decoded = super::decode(Layout::L, &Vec::<u8>::new());
         assert_eq!(
             decoded
                 .unwrap_err()
                 .downcast::<HeaderDecodeError>()
                 .unwrap(),
+            HeaderDecodeError("No mythic-telegram payload found.".to_string())
+        );
+    }
+
+    #[test]
+    fn decode_version_1() {
+        let buffer = whole_bytes(
+            Layout::L,
+            0u8,
+            1,
+            &[CHUNK_MODE, 8, NO_ENCRYPTION, NO_SIGNATURE, SHA256_CHECKSUM],
+        );
+
+        let decoded = super::decode(Layout::L, &buffer).unwrap();
+        assert_eq!(decoded, Header::new_chunk().with_version(1));
+        assert_eq!(decoded.length_size(), V1_LENGTH_SIZE);
+    }
+
+    #[test]
+    fn decode_version_2() {
+        let buffer = whole_bytes(
+            Layout::Rgba,
+            0xFFu8,
+            2,
+            &[
+                RGB_MODE,
+                8,
+                2,
+                RECIPIENT_ENCRYPTION,
+                SEALED_RECORD,
+                ED25519_SIGNATURE,
+                NO_METADATA,
+                CRC32_CHECKSUM,
+            ],
+        );
+
+        let decoded = super::decode(Layout::Rgba, &buffer).unwrap();
+        assert_eq!(
+            decoded,
+            Header::new_rgb(8, 2)
+                .with_version(2)
+                .with_encryption(EncryptionHeader::Recipient(RecipientHeader {}))
+                .with_record(RecordHeader::Sealed)
+                .with_signature(SignatureHeader::Ed25519)
+                .with_checksum(ChecksumHeader::Crc32)
+        );
+        assert_eq!(decoded.sample_count(Layout::Rgba), buffer.len());
+    }
+
+    #[test]
+    fn decode_version_3() {
+        let bytes = tagged(&[(MODE_FIELD, &[RGB_MODE, 8, 4]), SHA256]);
+        let buffer = whole_bytes(Layout::Rgb, 0u8, 3, &bytes);
+
+        let decoded = super::decode(Layout::Rgb, &buffer).unwrap();
+        assert_eq!(decoded, Header::new_rgb(8, 4).with_version(3));
+        assert_eq!(decoded.sample_count(Layout::Rgb), buffer.len());
+    }
+
+    #[test]
+    fn decode_error_misplaced_version() {
+        let buffer = with_checksum(with_preamble(0u8, 3, &tagged(&[CHUNK, SHA256])), 0);
+        assert_eq!(
+            decode_error(&buffer),
+            HeaderDecodeError(
+                "Header of format version 3 is not stored where this version stores it."
+                    .to_string()
+            )
+        );
+
+        let buffer = whole_bytes(Layout::L, 0u8, FORMAT_VERSION, &tagged(&[CHUNK, SHA256]));
+        assert_eq!(
+            decode_error(&buffer),
+            HeaderDecodeError(format!(
+                "Header of format version {} is not stored where this version stores it.",
+                FORMAT_VERSION
+            ))
+        );
+    }
+
+    #[test]
+    fn decode_error_unsupported_version() {
+        let buffer = with_preamble(0u8, FORMAT_VERSION + 1, &tagged(&[CHUNK]));
+        assert_eq!(
+            decode_error(&buffer),
+            HeaderDecodeError(format!(
+                "Unsupported format version {}, this build reads up to {}.",
+                FORMAT_VERSION + 1,
+                FORMAT_VERSION
+            ))
+        );
+    }
+
+    #[test]
+    fn decode_error_missing_mode_data() {
+        let buffer = with_preamble(0u8, 2, &[]);
+        assert_eq!(
+            decode_error(&buffer),
+            HeaderDecodeError("Not enough data to decode mode.".to_string())
+        );
+    }
+
+    #[test]
+    fn decode_error_missing_sample_width_data() {
+        let buffer = with_preamble(0u8, 2, &[ALPHA_MODE]);
+        assert_eq!(
+            decode_error(&buffer),
+            HeaderDecodeError("Not enough data to decode sample width.".to_string())
+        );
+    }
+
+    #[test]
+    fn decode_error_sample_width_mismatch() {
+        let buffer = tagged_header(&[(MODE_FIELD, &[ALPHA_MODE, 16]), SHA256]);
+        assert_eq!(
+            decode_error(&buffer),
+            HeaderDecodeError(
+                "Sample width mismatch: encoded on 16 bits, image has 8 bits.".to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn decode_error_missing_bits_per_channel_data() {
+        let buffer = tagged_header(&[(MODE_FIELD, &[RGB_MODE, 8]), SHA256]);
+        assert_eq!(
+            decode_error(&buffer),
+            HeaderDecodeError("Not enough data to decode bits per channel.".to_string())
+        );
+    }
+
+    #[test]
+    fn decode_error_unknown_mode() {
+        let buffer = tagged_header(&[(MODE_FIELD, &[0xFF, 8]), SHA256]);
+        assert_eq!(
+            decode_error(&buffer),
             HeaderDecodeError("Unknown mode in header.".to_string())
         );
     }
@@ -1,33 +1,31 @@
 use std::slice::Iter;
 
-use crate::coder::util::{create_mask, BITS_IN_BYTE};
+use crate::coder::{sample::Sample, util::BITS_IN_BYTE};
 
 use super::decode::Decode;
 
-pub struct RgbDecoder<'a> {
-    buffer: Iter<'a, u8>,
+pub struct RgbDecoder<'a, T: Sample> {
+    buffer: Iter<'a, T>,
     bits_per_channel: u8,
-    mask: u8,
 }
 
-impl<'a> RgbDecoder<'a> {
-    pub fn new(buffer: &'a [u8], bits_per_channel: u8) -> Self {
+impl<'a, T: Sample> RgbDecoder<'a, T> {
+    pub fn new(buffer: &'a [T], bits_per_channel: u8) -> Self {
         RgbDecoder {
             buffer: buffer.iter(),
             bits_per_channel,
-            mask: create_mask(bits_per_channel),
         }
     }
 }
 
-impl<'a> Decode for RgbDecoder<'a> {
+impl<'a, T: Sample> Decode for RgbDecoder<'a, T> {
     fn decode_byte(&mut self) -> Option<u8> {
         let mut byte: u8 = 0;
         let mut left = BITS_IN_BYTE;
 
         while left > 0 {
-            let channel = *self.buffer.next()?;
-            let bits = channel & self.mask;
+            let channel = self.buffer.next()?;
+            let bits = channel.low_bits(self.bits_per_channel);
             byte = byte.checked_shl(self.bits_per_channel as u32).unwrap_or(0);
             byte |= bits;
 
@@ -36,13 +34,17 @@ impl<'a> Decode for RgbDecoder<'a> {
 
         Some(byte)
     }
+
+    fn max_bytes_to_decode(&self) -> usize {
+        self.buffer.len() * self.bits_per_channel as usize / BITS_IN_BYTE as usize
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use std::slice::IterMut;
 
-    use crate::coder::{decoder::decode::Decode, error::DecodeError};
+    use crate::coder::{decoder::decode::Decode, error::DecodeError, header::LENGTH_SIZE};
 
     #[test]
     fn not_enough_data_to_decode_filename_length() {
@@ -64,14 +66,17 @@ mod tests {
         let data_length = 0;
         let bits_per_channel: u8 = 4;
 
+        // Missing the data length and the last filename sample
+        let data_length_samples = LENGTH_SIZE * 2;
         let mut buffer = vec![
             0;
             min_required_data(filename_length, data_length, bits_per_channel)
-                - filename_length
+                - data_length_samples
+                - 1
         ];
         let mut iter = buffer.iter_mut();
 
-        fill_encoded(&mut iter, &[0; 7]);
+        fill_encoded(&mut iter, &[0; 15]);
         fill_encoded(&mut iter, &[filename_length as u8]);
 
         let decoder = create_decoder(&buffer, bits_per_channel);
@@ -87,12 +92,13 @@ mod tests {
         let filename_length = 12;
         let bits_per_channel: u8 = 2;
 
-        let min_required_data =
-            ((4 + 4 + filename_length) * super::BITS_IN_BYTE / bits_per_channel) as usize;
+        let min_required_data = (LENGTH_SIZE + LENGTH_SIZE + filename_length)
+            * super::BITS_IN_BYTE as usize
+            / bits_per_channel as usize;
         let mut buffer = vec![0; min_required_data - 1];
         let mut iter = buffer.iter_mut();
 
-        fill_encoded(&mut iter, &[0; 14]);
+        fill_encoded(&mut iter, &[0; 30]);
         fill_encoded(&mut iter, &[0b0000_0011, 0b0000_0000]); // 1100 = 12
 
         let decoder = create_decoder(&buffer, bits_per_channel);
@@ -105,17 +111,15 @@ mod tests {
 
     #[test]
     fn not_enough_data_to_decode_data() {
-        let filename_length: i32 = 3;
-        let data_length: i32 = 13;
+        let filename_length = 3;
+        let data_length = 13;
         let bits_per_channel: u8 = 4;
 
-        let min_required_data = ((4 + 4 + filename_length + data_length)
-            * super::BITS_IN_BYTE as i32
-            / bits_per_channel as i32) as usize;
+        let min_required_data = min_required_data(filename_length, data_length, bits_per_channel);
         let mut buffer = vec![0; min_required_data - 1];
         let mut iter = buffer.iter_mut();
 
-        fill_encoded(&mut iter, &[0; 7]);
+        fill_encoded(&mut iter, &[0; 15]);
         fill_encoded(&mut iter, &[filename_length as u8]);
 
         // Filename
@@ -131,7 +135,7 @@ mod tests {
             ],
         );
 
-        fill_encoded(&mut iter, &[0; 7]);
+        fill_encoded(&mut iter, &[0; 15]);
         fill_encoded(&mut iter, &[data_length as u8]);
 
         let decoder = create_decoder(&buffer, bits_per_channel);
@@ -144,12 +148,12 @@ mod tests {
 
     #[test]
     fn decode_2bits() {
-        let mut buffer = vec![0; 64];
+        let mut buffer = vec![0; 96];
         let mut iter = buffer.iter_mut();
         let bits_per_channel = 2;
 
         // Filename length
-        fill_encoded(&mut iter, &[0; 14]);
+        fill_encoded(&mut iter, &[0; 30]);
         fill_encoded(&mut iter, &[0b0000_0001, 0b0000_0001]);
 
         // x = 0111 1000
@@ -179,7 +183,7 @@ mod tests {
         );
 
         // Message Length
-        fill_encoded(&mut iter, &[0; 15]);
+        fill_encoded(&mut iter, &[0; 31]);
         fill_encoded(&mut iter, &[0b0000_0011]);
 
         // Data
@@ -202,8 +206,8 @@ mod tests {
         let decoder = create_decoder(&buffer, bits_per_channel);
         let (filename, data) = decoder.decode().unwrap();
 
-        assert_eq!(filename, "x.png");
-        assert_eq!(String::from_utf8(data).unwrap(), "xyz");
+        assert_eq!(*filename, "x.png");
+        assert_eq!(*data, *b"xyz");
     }
 
     #[test]
@@ -213,7 +217,7 @@ mod tests {
         let bits_per_channel = 4;
 
         // Filename length
-        fill_encoded(&mut iter, &[0; 7]);
+        fill_encoded(&mut iter, &[0; 15]);
         fill_encoded(&mut iter, &[0b0000_0101]);
 
         // x = 0111 1000
@@ -228,7 +232,7 @@ mod tests {
         fill_encoded(&mut iter, &[0b0000_0110, 0b0000_0111]);
 
         // Message Length
-        fill_encoded(&mut iter, &[0; 7]);
+        fill_encoded(&mut iter, &[0; 15]);
         fill_encoded(&mut iter, &[0b0000_0100]);
 
         // Data
@@ -244,11 +248,11 @@ mod tests {
         let decoder = create_decoder(&buffer, bits_per_channel);
         let (filename, data) = decoder.decode().unwrap();
 
-        assert_eq!(filename, "x.png");
-        assert_eq!(String::from_utf8(data).unwrap(), "wolf");
+        assert_eq!(*filename, "x.png");
+        assert_eq!(*data, *b"wolf");
     }
 
-    fn create_decoder<'a>(buffer: &'a Vec<u8>, bits_per_channel: u8) -> Box<dyn Decode + 'a> {
+    fn create_decoder<'a>(buffer: &'a [u8], bits_per_channel: u8) -> Box<dyn Decode + 'a> {
         Box::new(super::RgbDecoder::new(buffer, bits_per_channel))
     }
 
@@ -263,7 +267,7 @@ mod tests {
         data_length: usize,
         bits_per_channel: u8,
     ) -> usize {
-        (4 + 4 + filename_length + data_length) * super::BITS_IN_BYTE as usize
+        (LENGTH_SIZE + LENGTH_SIZE + filename_length + data_length) * super::BITS_IN_BYTE as usize
             / bits_per_channel as usize
     }
 }
@@ -1,49 +1,321 @@
 mod alpha_encoder;
+mod byte_encoder;
 mod encode;
 mod header_encoder;
 mod rgb_encoder;
 
-use anyhow::{Ok, Result};
+use anyhow::{anyhow, Ok, Result};
+use rand::{rngs::OsRng, RngCore, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use zeroize::Zeroizing;
 
-use crate::{coder::header::Header, config::Algorithm};
+use crate::{
+    coder::{
+        checksum, copies,
+        crypto::{self, Protection},
+        error::{EncodeError, HeaderEncodeError},
+        header::{ChecksumHeader, Header, MetadataHeader, RecordHeader, LENGTH_SIZE},
+        layout::Layout,
+        metadata::{self, Metadata},
+        sample::Sample,
+        signature::{self, SigningKey},
+        stealth::Stealth,
+    },
+    config::{Algorithm, Checksum},
+};
 
-use self::{alpha_encoder::AlphaEncoder, encode::Encode, rgb_encoder::RgbEncoder};
+use self::{
+    alpha_encoder::AlphaEncoder, byte_encoder::ByteEncoder, encode::Encode, rgb_encoder::RgbEncoder,
+};
 
-pub fn encode(
+/// Protection applied to hidden data before it is stored.
+#[derive(Debug, Clone, Default)]
+pub struct Envelope {
+    pub protection: Protection,
+    pub signing_key: Option<SigningKey>,
+    /// Encrypts file name and lengths along with data, leaving only the header readable.
+    pub seal_record: bool,
+    /// Fills the rest of the carrier capacity with pseudo-random bytes, hiding where data ends.
+    pub pad: bool,
+    pub checksum: Checksum,
+    /// File attributes stored along with data, none by default.
+    pub metadata: Metadata,
+}
+
+pub fn encode<T: Sample>(
     algorithm: &Algorithm,
-    mut image_buffer: Vec<u8>,
-    secret_data: Vec<u8>,
+    layout: Layout,
+    mut image_buffer: Vec<T>,
+    secret_data: impl Into<Zeroizing<Vec<u8>>>,
     secret_filename: String,
+    envelope: &Envelope,
+) -> Result<Vec<T>> {
+    let secret_data = secret_data.into();
+    let secret_filename = Zeroizing::new(secret_filename);
+    if matches!(algorithm, Algorithm::Stealth) {
+        return encode_stealth(image_buffer, secret_data, secret_filename, envelope);
+    }
+    if matches!(algorithm, Algorithm::Alpha) && !layout.has_alpha() {
+        return Err(anyhow!(EncodeError(
+            "Alpha mode requires an image with alpha channel.".to_string()
+        )));
+    }
+    // Alpha mode writes whole alpha bytes, so padding would make the whole image visibly opaque
+    // or transparent at random
+    if matches!(algorithm, Algorithm::Alpha) && envelope.pad {
+        return Err(anyhow!(EncodeError(
+            "Padding is not supported in alpha mode.".to_string()
+        )));
+    }
+
+    let (header, secret_data, secret_filename) = wrap(
+        create_header::<T>(algorithm)?,
+        envelope,
+        secret_data,
+        secret_filename,
+    )?;
+    let header_buffer_size = header.sample_count(layout);
+    if !copies::fit(image_buffer.len(), layout, header_buffer_size) {
+        return Err(anyhow!(HeaderEncodeError(
+            "Not enough to encode header.".to_string()
+        )));
+    }
+    // Data is coded on the samples between header copies, moved next to each other meanwhile
+    let offsets = copies::offsets(image_buffer.len(), layout);
+    let data_buffer_size = copies::gather(&mut image_buffer, &offsets, header_buffer_size);
+    let (data_buffer, header_buffers) = image_buffer.split_at_mut(data_buffer_size);
+
+    let encoder = create_encoder(algorithm, layout, data_buffer, secret_data, secret_filename)?;
+    for header_buffer in header_buffers.chunks_exact_mut(header_buffer_size) {
+        header_encoder::encode(header.clone(), header_buffer)?;
+    }
+    encode_record(encoder, envelope)?;
+    copies::scatter(&mut image_buffer, &offsets, header_buffer_size);
+
+    Ok(image_buffer)
+}
+
+/// Encodes header and data as a plain byte stream of at most `capacity` bytes,
+/// for modes storing data outside of image pixels.
+pub fn encode_bytes(
+    algorithm: &Algorithm,
+    capacity: usize,
+    secret_data: impl Into<Zeroizing<Vec<u8>>>,
+    secret_filename: String,
+    envelope: &Envelope,
 ) -> Result<Vec<u8>> {
-    let header: Header = create_header(algorithm);
-    let (header_buffer, data_buffer) = image_buffer.split_at_mut(header.size() * 4);
+    let secret_data = secret_data.into();
+    let secret_filename = Zeroizing::new(secret_filename);
+    if matches!(algorithm, Algorithm::Stealth) {
+        return Err(anyhow!(EncodeError(
+            "Selected mode encodes data in image pixels.".to_string()
+        )));
+    }
+    let header: Header = create_header::<u8>(algorithm)?;
+    if !header.is_byte_stream() {
+        return Err(anyhow!(EncodeError(
+            "Selected mode encodes data in image pixels.".to_string()
+        )));
+    }
+    // Chunk and text carriers grow with data, so padding would fill their nominal capacity
+    if envelope.pad && matches!(algorithm, Algorithm::Chunk | Algorithm::Text) {
+        return Err(anyhow!(EncodeError(
+            "Padding is not supported in chunk and text modes.".to_string()
+        )));
+    }
+    let (header, secret_data, secret_filename) =
+        wrap(header, envelope, secret_data, secret_filename)?;
+
+    let mut buffer = header.to_bytes();
+
+    let capacity = capacity.saturating_sub(buffer.len());
+    let encoder = Box::new(ByteEncoder::new(
+        &mut buffer,
+        capacity,
+        secret_data,
+        secret_filename,
+    ));
+    encode_record(encoder, envelope)?;
+
+    Ok(buffer)
+}
+
+/// Encrypts, signs and checksums data as requested by the envelope. File metadata is prepended to
+/// data, and with a sealed record, the file name and lengths are encrypted along with data, and an
+/// empty file name is stored in the clear.
+fn wrap(
+    header: Header,
+    envelope: &Envelope,
+    secret_data: Zeroizing<Vec<u8>>,
+    secret_filename: Zeroizing<String>,
+) -> Result<(Header, Zeroizing<Vec<u8>>, Zeroizing<String>)> {
+    // Set upfront, as the signature covers the header
+    let header = header.with_checksum(match envelope.checksum {
+        Checksum::Crc32 => ChecksumHeader::Crc32,
+        Checksum::Sha256 => ChecksumHeader::Sha256,
+    });
+    let (header, secret_data) = if envelope.metadata.is_empty() {
+        (header, secret_data)
+    } else {
+        (
+            header.with_metadata(MetadataHeader::Attributes),
+            metadata::prepend(&envelope.metadata, secret_data)?,
+        )
+    };
+    let (header, secret_data, secret_filename) = if envelope.seal_record {
+        if matches!(envelope.protection, Protection::None) {
+            return Err(anyhow!(EncodeError(
+                "Sealing record requires encryption.".to_string()
+            )));
+        }
+        // Allocated upfront, so that growing the record leaves no copies behind
+        let record_length = LENGTH_SIZE + secret_filename.len() + LENGTH_SIZE + secret_data.len();
+        let mut record = Zeroizing::new(Vec::with_capacity(record_length));
+        Box::new(ByteEncoder::new(
+            &mut record,
+            usize::MAX,
+            secret_data,
+            secret_filename,
+        ))
+        .encode()?;
+        (
+            header.with_record(RecordHeader::Sealed),
+            record,
+            Zeroizing::new(String::new()),
+        )
+    } else {
+        (header, secret_data, secret_filename)
+    };
 
-    header_encoder::encode(header.clone(), header_buffer)?;
-    create_encoder(algorithm, data_buffer, secret_data, secret_filename).encode()?;
+    let (header, secret_data) =
+        crypto::seal(header, &envelope.protection, secret_data, &secret_filename)?;
+    let (header, secret_data) = signature::sign(
+        header,
+        envelope.signing_key.as_ref(),
+        secret_data,
+        &secret_filename,
+    )?;
+    let secret_data = checksum::append(&header, secret_data, &secret_filename);
+    Ok((header, secret_data, secret_filename))
+}
+
+fn encode_record(encoder: Box<dyn Encode + '_>, envelope: &Envelope) -> Result<()> {
+    if !envelope.pad {
+        return encoder.encode();
+    }
+    encoder.encode_padded(&mut padding())
+}
+
+/// ChaCha20 keystream under a random, never stored key looks the same as encrypted payload.
+fn padding() -> ChaCha20Rng {
+    let mut key = [0; 32];
+    OsRng.fill_bytes(&mut key);
+    ChaCha20Rng::from_seed(key)
+}
+
+/// Encrypts the whole record and embeds it without any header, see [`Stealth`].
+fn encode_stealth<T: Sample>(
+    mut image_buffer: Vec<T>,
+    secret_data: Zeroizing<Vec<u8>>,
+    secret_filename: Zeroizing<String>,
+    envelope: &Envelope,
+) -> Result<Vec<T>> {
+    let Protection::Passphrase(passphrase) = &envelope.protection else {
+        return Err(anyhow!(EncodeError(
+            "Stealth mode requires a passphrase.".to_string()
+        )));
+    };
+    if envelope.signing_key.is_some() {
+        return Err(anyhow!(EncodeError(
+            "Stealth mode does not support signing.".to_string()
+        )));
+    }
+    if !envelope.metadata.is_empty() {
+        return Err(anyhow!(EncodeError(
+            "Stealth mode does not support file metadata.".to_string()
+        )));
+    }
+    let stealth = Stealth::derive(passphrase)?;
+
+    let record_length = LENGTH_SIZE + secret_filename.len() + LENGTH_SIZE + secret_data.len();
+    let mut record = Zeroizing::new(Vec::with_capacity(record_length));
+    Box::new(ByteEncoder::new(
+        &mut record,
+        usize::MAX,
+        secret_data,
+        secret_filename,
+    ))
+    .encode()?;
+    let payload = stealth.seal(&record)?;
+
+    let start = stealth.start(image_buffer.len());
+    image_buffer.rotate_left(start);
+    let mut encoder = RgbEncoder::new(
+        &mut image_buffer,
+        Zeroizing::default(),
+        stealth.bits_per_channel,
+        Zeroizing::default(),
+    );
+    if payload.len() > encoder.max_bytes_to_encode() {
+        return Err(anyhow!(EncodeError(
+            "Too much data to encode in the image.".to_string()
+        )));
+    }
+    encoder.encode_data(&payload);
+    if envelope.pad {
+        let mut bytes = vec![0; encoder.max_bytes_to_encode()];
+        padding().fill_bytes(&mut bytes);
+        encoder.encode_data(&bytes);
+    }
+    image_buffer.rotate_right(start);
 
     Ok(image_buffer)
 }
 
-fn create_header(algorithm: &Algorithm) -> Header {
+fn create_header<T: Sample>(algorithm: &Algorithm) -> Result<Header> {
     match algorithm {
-        Algorithm::Alpha => Header::new_alpha(),
-        Algorithm::Rgb(alg_config) => Header::new_rgb(alg_config.bits_per_channel),
+        Algorithm::Alpha => Ok(Header::new_alpha(T::BITS)),
+        Algorithm::Rgb(alg_config) => Ok(Header::new_rgb(T::BITS, alg_config.bits_per_channel)),
+        Algorithm::Chunk => Ok(Header::new_chunk()),
+        Algorithm::Filter => Ok(Header::new_filter()),
+        Algorithm::Palette => Ok(Header::new_palette()),
+        Algorithm::Text => Ok(Header::new_text()),
+        Algorithm::Svg => Ok(Header::new_svg()),
+        Algorithm::Stealth => Err(anyhow!(EncodeError(
+            "Stealth mode stores no header.".to_string()
+        ))),
     }
 }
 
-fn create_encoder<'a>(
+fn create_encoder<'a, T: Sample>(
     algorithm: &Algorithm,
-    buffer: &'a mut [u8],
-    secret_data: Vec<u8>,
-    secret_filename: String,
-) -> Box<dyn Encode + 'a> {
+    layout: Layout,
+    buffer: &'a mut [T],
+    secret_data: Zeroizing<Vec<u8>>,
+    secret_filename: Zeroizing<String>,
+) -> Result<Box<dyn Encode + 'a>> {
     match algorithm {
-        Algorithm::Rgb(alg_config) => Box::new(RgbEncoder::new(
+        Algorithm::Rgb(alg_config) => Ok(Box::new(RgbEncoder::new(
             buffer,
             secret_data,
             alg_config.bits_per_channel,
             secret_filename,
-        )),
-        Algorithm::Alpha => Box::new(AlphaEncoder::new(buffer, secret_data, secret_filename)),
+        ))),
+        Algorithm::Alpha => Ok(Box::new(AlphaEncoder::new(
+            buffer,
+            layout,
+            secret_data,
+            secret_filename,
+        ))),
+        Algorithm::Chunk
+        | Algorithm::Filter
+        | Algorithm::Palette
+        | Algorithm::Text
+        | Algorithm::Svg => Err(anyhow!(EncodeError(
+            "Selected mode does not encode data in image pixels.".to_string()
+        ))),
+        Algorithm::Stealth => Err(anyhow!(EncodeError(
+            "Stealth mode stores no header.".to_string()
+        ))),
     }
 }
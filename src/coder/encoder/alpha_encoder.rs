@@ -1,30 +1,43 @@
 use std::{
     iter::{Skip, StepBy},
+    mem,
     slice::IterMut,
 };
 
+use zeroize::Zeroizing;
+
+use crate::coder::{header::LENGTH_SIZE, layout::Layout, sample::Sample, util::BITS_IN_BYTE};
+
 use super::Encode;
 
-pub struct AlphaEncoder<'a> {
-    buffer: StepBy<Skip<IterMut<'a, u8>>>,
-    data: Vec<u8>,
-    file_name: String,
+pub struct AlphaEncoder<'a, T: Sample> {
+    buffer: StepBy<Skip<IterMut<'a, T>>>,
+    data: Zeroizing<Vec<u8>>,
+    file_name: Zeroizing<String>,
 }
 
-impl<'a> AlphaEncoder<'a> {
-    pub fn new(buffer: &'a mut [u8], data: Vec<u8>, file_name: String) -> Self {
+impl<'a, T: Sample> AlphaEncoder<'a, T> {
+    pub fn new(
+        buffer: &'a mut [T],
+        layout: Layout,
+        data: Zeroizing<Vec<u8>>,
+        file_name: Zeroizing<String>,
+    ) -> Self {
         AlphaEncoder {
-            buffer: buffer.iter_mut().skip(3).step_by(4),
+            buffer: buffer
+                .iter_mut()
+                .skip(layout.alpha_mode_channel())
+                .step_by(layout.channels()),
             data,
             file_name,
         }
     }
 }
 
-impl<'a> Encode for AlphaEncoder<'a> {
+impl<'a, T: Sample> Encode for AlphaEncoder<'a, T> {
     fn encode_byte(&mut self, byte: u8) {
         let channel = self.buffer.next().unwrap();
-        *channel = byte;
+        *channel = channel.with_low_bits(BITS_IN_BYTE, byte);
     }
 
     fn max_bytes_to_encode(&self) -> usize {
@@ -32,15 +45,15 @@ impl<'a> Encode for AlphaEncoder<'a> {
     }
 
     fn bytes_to_encode(&self) -> usize {
-        self.data.len() + self.file_name.len() + 4 + 4
+        self.data.len() + self.file_name.len() + LENGTH_SIZE + LENGTH_SIZE
     }
 
-    fn file_name_bytes(&self) -> Vec<u8> {
-        self.file_name.as_bytes().to_vec()
+    fn take_file_name(&mut self) -> Zeroizing<Vec<u8>> {
+        Zeroizing::new(mem::take(&mut *self.file_name).into_bytes())
     }
 
-    fn data_bytes(&self) -> Vec<u8> {
-        self.data.clone()
+    fn take_data(&mut self) -> Zeroizing<Vec<u8>> {
+        Zeroizing::new(mem::take(&mut *self.data))
     }
 }
 
@@ -48,7 +61,9 @@ impl<'a> Encode for AlphaEncoder<'a> {
 mod tests {
     use std::slice::Iter;
 
-    use crate::coder::{encoder::Encode, error::EncodeError};
+    use crate::coder::{encoder::Encode, error::EncodeError, header::LENGTH_SIZE, layout::Layout};
+
+    use zeroize::Zeroizing;
 
     use super::AlphaEncoder;
 
@@ -58,11 +73,11 @@ mod tests {
         let file_name = "x.png";
 
         // Needed buffer length:
-        //   4 (filename size)
+        //   8 (filename size)
         // + 5 (filename)
-        // + 4 (message size)
+        // + 8 (message size)
         // + 3 (data)
-        // = 16 (bytes) = 16 * 4 (only alpha channel use from rgba) = 64 needed channels/bytes
+        // = 24 (bytes) = 24 * 4 (only alpha channel use from rgba) = 96 needed channels/bytes
         let min_required_buffer = min_required_buffer(file_name.len(), data.len());
 
         {
@@ -111,16 +126,13 @@ mod tests {
         let mut encoded_it = buffer.iter();
 
         // Encoded layout:
-        // Filename length = 4 bytes (encoded on 32 bits) = 0b00000000'00000000'00000000'00000101
+        // Filename length = 8 bytes (encoded on 64 bits) = 0b00000000'...'00000000'00000101
         // Filename = 01111000 00101110 01110000 01101110 01100111
-        // Message length = 4 bytes (encoded on 32 bits) = 0b00000000'00000000'00000000'00000100
+        // Message length = 8 bytes (encoded on 64 bits) = 0b00000000'...'00000000'00000100
         // Message bytes [...]
 
         // Filename Length
-        verify_encoded(
-            &mut encoded_it,
-            &[0b0000_0000, 0b0000_0000, 0b0000_0000, 0b00000101],
-        );
+        verify_encoded(&mut encoded_it, &[0, 0, 0, 0, 0, 0, 0, 0b00000101]);
 
         // Filename
         verify_encoded(
@@ -129,10 +141,7 @@ mod tests {
         );
 
         // Message length
-        verify_encoded(
-            &mut encoded_it,
-            &[0b0000_0000, 0b0000_0000, 0b0000_0000, 0b00000100],
-        );
+        verify_encoded(&mut encoded_it, &[0, 0, 0, 0, 0, 0, 0, 0b00000100]);
 
         // Message
         verify_encoded(
@@ -142,11 +151,16 @@ mod tests {
     }
 
     fn create_encoder<'a>(
-        buffer: &'a mut Vec<u8>,
+        buffer: &'a mut [u8],
         data: Vec<u8>,
         file_name: String,
     ) -> Box<dyn Encode + 'a> {
-        Box::new(AlphaEncoder::new(buffer, data, file_name))
+        Box::new(AlphaEncoder::new(
+            buffer,
+            Layout::Rgba,
+            Zeroizing::new(data),
+            Zeroizing::new(file_name),
+        ))
     }
 
     fn verify_encoded(iter: &mut Iter<u8>, bytes: &[u8]) {
@@ -157,6 +171,6 @@ mod tests {
     }
 
     fn min_required_buffer(filename_length: usize, data_length: usize) -> usize {
-        (4 + filename_length + 4 + data_length) * 4
+        (LENGTH_SIZE + filename_length + LENGTH_SIZE + data_length) * 4
     }
 }
@@ -0,0 +1,132 @@
+use std::mem;
+
+use zeroize::Zeroizing;
+
+use crate::coder::header::LENGTH_SIZE;
+
+use super::Encode;
+
+pub struct ByteEncoder<'a> {
+    buffer: &'a mut Vec<u8>,
+    capacity: usize,
+    data: Zeroizing<Vec<u8>>,
+    file_name: Zeroizing<String>,
+}
+
+impl<'a> ByteEncoder<'a> {
+    pub fn new(
+        buffer: &'a mut Vec<u8>,
+        capacity: usize,
+        data: Zeroizing<Vec<u8>>,
+        file_name: Zeroizing<String>,
+    ) -> Self {
+        ByteEncoder {
+            buffer,
+            capacity,
+            data,
+            file_name,
+        }
+    }
+}
+
+impl<'a> Encode for ByteEncoder<'a> {
+    fn encode_byte(&mut self, byte: u8) {
+        self.buffer.push(byte);
+    }
+
+    fn max_bytes_to_encode(&self) -> usize {
+        self.capacity
+    }
+
+    fn bytes_to_encode(&self) -> usize {
+        self.data.len() + self.file_name.len() + LENGTH_SIZE + LENGTH_SIZE
+    }
+
+    fn take_file_name(&mut self) -> Zeroizing<Vec<u8>> {
+        Zeroizing::new(mem::take(&mut *self.file_name).into_bytes())
+    }
+
+    fn take_data(&mut self) -> Zeroizing<Vec<u8>> {
+        Zeroizing::new(mem::take(&mut *self.data))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::coder::{encoder::Encode, error::EncodeError};
+
+    use rand::rngs::mock::StepRng;
+    use zeroize::Zeroizing;
+
+    use super::ByteEncoder;
+
+    #[test]
+    fn not_enough_capacity() {
+        let data = "xyz".as_bytes();
+        let file_name = "x.png";
+        let mut buffer = Vec::new();
+
+        let encoder = create_encoder(&mut buffer, 15, data.to_vec(), file_name.to_string());
+        assert_eq!(
+            encoder
+                .encode()
+                .unwrap_err()
+                .downcast::<EncodeError>()
+                .unwrap(),
+            EncodeError("Too much data to encode in the image.".to_string())
+        );
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn encode() {
+        let data = "wolf".as_bytes();
+        let file_name = "x.png";
+        let mut buffer = vec![0xFF];
+
+        let encoder = create_encoder(&mut buffer, 25, data.to_vec(), file_name.to_string());
+        assert!(encoder.encode().is_ok());
+
+        // Encoded bytes are appended after existing buffer content
+        assert_eq!(
+            buffer,
+            vec![
+                0xFF, // Existing content
+                0, 0, 0, 0, 0, 0, 0, 5, // Filename length
+                b'x', b'.', b'p', b'n', b'g', // Filename
+                0, 0, 0, 0, 0, 0, 0, 4, // Message length
+                b'w', b'o', b'l', b'f' // Message
+            ]
+        );
+    }
+
+    #[test]
+    fn encode_padded() {
+        let data = "wolf".as_bytes();
+        let file_name = "x.png";
+        let mut buffer = Vec::new();
+
+        let encoder = create_encoder(&mut buffer, 40, data.to_vec(), file_name.to_string());
+        let mut padding = StepRng::new(0xABAB_ABAB_ABAB_ABAB, 0);
+        assert!(encoder.encode_padded(&mut padding).is_ok());
+
+        assert_eq!(buffer.len(), 40);
+        assert_eq!(buffer[..8], [0, 0, 0, 0, 0, 0, 0, 5]);
+        assert_eq!(buffer[21..25], *b"wolf");
+        assert!(buffer[25..].iter().all(|byte| *byte == 0xAB));
+    }
+
+    fn create_encoder<'a>(
+        buffer: &'a mut Vec<u8>,
+        capacity: usize,
+        data: Vec<u8>,
+        file_name: String,
+    ) -> Box<dyn Encode + 'a> {
+        Box::new(ByteEncoder::new(
+            buffer,
+            capacity,
+            Zeroizing::new(data),
+            Zeroizing::new(file_name),
+        ))
+    }
+}
@@ -1,18 +1,36 @@
 use anyhow::{anyhow, Result};
+use rand::RngCore;
+use zeroize::Zeroizing;
 
 use crate::coder::error::EncodeError;
 
 pub trait Encode {
     fn encode(mut self: Box<Self>) -> Result<()> {
+        self.encode_record()
+    }
+
+    /// Encodes data and fills the rest of the capacity with bytes taken from `padding`.
+    fn encode_padded(mut self: Box<Self>, padding: &mut dyn RngCore) -> Result<()> {
+        self.validate()?;
+        // Capacity of some encoders shrinks as bytes are encoded, so padding is sized upfront
+        let mut bytes = vec![0; self.max_bytes_to_encode() - self.bytes_to_encode()];
+        padding.fill_bytes(&mut bytes);
+
+        self.encode_record()?;
+        self.encode_data(&bytes);
+        Ok(())
+    }
+
+    fn encode_record(&mut self) -> Result<()> {
         self.validate()?;
 
-        let file_name = self.file_name_bytes();
-        self.encode_length(file_name.len() as u32);
-        self.encode_data(file_name);
+        let file_name = self.take_file_name();
+        self.encode_length(file_name.len() as u64);
+        self.encode_data(&file_name);
 
-        let data = self.data_bytes();
-        self.encode_length(data.len() as u32);
-        self.encode_data(data);
+        let data = self.take_data();
+        self.encode_length(data.len() as u64);
+        self.encode_data(&data);
 
         Ok(())
     }
@@ -25,18 +43,20 @@ pub trait Encode {
         }
         Ok(())
     }
-    fn encode_length(&mut self, length: u32) {
+    fn encode_length(&mut self, length: u64) {
         length
             .to_be_bytes()
             .into_iter()
             .for_each(|byte| self.encode_byte(byte));
     }
-    fn encode_data(&mut self, data: Vec<u8>) {
+    fn encode_data(&mut self, data: &[u8]) {
         data.iter().for_each(|byte| self.encode_byte(*byte));
     }
     fn encode_byte(&mut self, byte: u8);
     fn max_bytes_to_encode(&self) -> usize;
     fn bytes_to_encode(&self) -> usize;
-    fn file_name_bytes(&self) -> Vec<u8>;
-    fn data_bytes(&self) -> Vec<u8>;
+    /// Moves the file name out of the encoder, so that secrets are not copied.
+    fn take_file_name(&mut self) -> Zeroizing<Vec<u8>>;
+    /// Moves the data out of the encoder, so that secrets are not copied.
+    fn take_data(&mut self) -> Zeroizing<Vec<u8>>;
 }
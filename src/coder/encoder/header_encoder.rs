@@ -1,90 +1,234 @@
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 
-use crate::coder::{
-    error::HeaderEncodeError,
-    header::{AlgHeader, Header, RgbHeader},
-};
+use crate::coder::{error::HeaderEncodeError, header::Header, sample::Sample, util::BITS_IN_BYTE};
 
-pub fn encode(header: Header, buffer: &mut [u8]) -> Result<()> {
-    let mut iter = buffer.iter_mut().skip(3).step_by(4);
-    let mode_byte = iter.next().ok_or(HeaderEncodeError(
-        "Not enough to encode header mode.".to_string(),
-    ))?;
-    *mode_byte = header.mode;
-
-    match header.alg_header {
-        AlgHeader::Alpha(_) => encode_alpha(&mut iter)?,
-        AlgHeader::Rgb(alg_header) => encode_rgb(&mut iter, &alg_header)?,
+/// Writes the bytes of [`Header::to_bytes`] into the least significant bit of leading samples,
+/// most significant bit of each byte first. One bit per sample keeps the header as inconspicuous
+/// as the least aggressive mode and lets decoders read it before knowing the mode.
+pub fn encode<T: Sample>(header: Header, buffer: &mut [T]) -> Result<()> {
+    let bytes = header.to_bytes();
+    if buffer.len() < bytes.len() * BITS_IN_BYTE as usize {
+        return Err(anyhow!(HeaderEncodeError(
+            "Not enough to encode header.".to_string()
+        )));
+    }
+    for (&byte, samples) in bytes
+        .iter()
+        .zip(buffer.chunks_exact_mut(BITS_IN_BYTE as usize))
+    {
+        encode_byte(byte, samples);
     }
-
-    Ok(())
-}
-
-fn encode_alpha<'a, I>(_iter: &mut I) -> Result<()>
-where
-    I: Iterator<Item = &'a mut u8>,
-{
     Ok(())
 }
 
-fn encode_rgb<'a, I>(iter: &mut I, header: &RgbHeader) -> Result<()>
-where
-    I: Iterator<Item = &'a mut u8>,
-{
-    let bits_per_channel_byte = iter.next().ok_or(HeaderEncodeError(
-        "Not enough to encode header bits per channel.".to_string(),
-    ))?;
-    *bits_per_channel_byte = header.bits_per_channel;
-    Ok(())
+/// Writes bits of `byte` into samples, most significant bit first.
+fn encode_byte<T: Sample>(byte: u8, samples: &mut [T]) {
+    for (sample, i) in samples.iter_mut().zip((0..BITS_IN_BYTE).rev()) {
+        *sample = sample.with_low_bits(1, byte >> i & 1);
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use crate::coder::{
         error::HeaderEncodeError,
-        header::{Header, ALPHA_MODE, RGB_MODE},
+        header::{
+            ChecksumHeader, EncryptionHeader, Header, MetadataHeader, PassphraseHeader,
+            RecipientHeader, RecordHeader, SignatureHeader, CHECKSUM_FIELD, CHUNK_MODE,
+            CRC32_CHECKSUM, ED25519_SIGNATURE, ENCRYPTION_FIELD, END_FIELD, FILE_METADATA,
+            FORMAT_VERSION, HEADER_CHECKSUM_LENGTH, MAGIC, METADATA_FIELD, MODE_FIELD,
+            NONCE_LENGTH, NO_ENCRYPTION, NO_SIGNATURE, PASSPHRASE_ENCRYPTION, PLAIN_RECORD,
+            RECIPIENT_ENCRYPTION, RGB_MODE, SALT_LENGTH, SEALED_RECORD, SHA256_CHECKSUM,
+            SIGNATURE_FIELD,
+        },
+        sample::Sample,
+        util::BITS_IN_BYTE,
     };
 
+    /// Bytes held in the least significant bit of the samples, checking that other bits keep
+    /// `fill`.
+    fn header_bytes<T: Sample + PartialEq>(buffer: &[T], fill: T) -> Vec<u8> {
+        assert!(buffer
+            .iter()
+            .all(|&sample| sample.with_low_bits(1, 0) == fill.with_low_bits(1, 0)));
+        buffer
+            .chunks_exact(BITS_IN_BYTE as usize)
+            .map(|bits| bits.iter().fold(0, |byte, s| byte << 1 | s.low_bits(1)))
+            .collect()
+    }
+
+    /// Splits CRC32 off the header bytes and checks it, then checks and drops magic and version.
+    fn fields(bytes: &[u8]) -> Vec<u8> {
+        let (bytes, checksum) = bytes.split_at(bytes.len() - HEADER_CHECKSUM_LENGTH);
+        assert_eq!(checksum, crc32fast::hash(bytes).to_be_bytes());
+        assert_eq!(bytes[..MAGIC.len()], MAGIC);
+        assert_eq!(bytes[MAGIC.len()], FORMAT_VERSION);
+        bytes[MAGIC.len() + 1..].to_vec()
+    }
+
+    const BITS: usize = BITS_IN_BYTE as usize;
+
     #[test]
     fn encode_alpha() {
-        let header = Header::new_alpha();
-        let mut buffer = vec![0; 10];
-        assert!(super::encode(header, &mut buffer).is_ok());
-        assert_eq!(buffer, vec![0, 0, 0, ALPHA_MODE, 0, 0, 0, 0, 0, 0]);
+        let header = Header::new_alpha(8);
+        let mut buffer: Vec<u8> = vec![0xFF; header.size() * BITS + 2];
+        assert!(super::encode(header.clone(), &mut buffer).is_ok());
+
+        let bytes = header_bytes(&buffer[..header.size() * BITS], 0xFF);
+        assert_eq!(bytes, header.to_bytes());
+        assert_eq!(buffer[header.size() * BITS..], [0xFF, 0xFF]);
     }
 
     #[test]
     fn encode_rgb() {
         let bits_per_channel = 4;
-        let header = Header::new_rgb(bits_per_channel);
-        let mut buffer = vec![0; 10];
+        let header = Header::new_rgb(8, bits_per_channel);
+        let mut buffer: Vec<u8> = vec![0; header.size() * BITS];
         assert!(super::encode(header, &mut buffer).is_ok());
         assert_eq!(
-            buffer,
-            vec![0, 0, 0, RGB_MODE, 0, 0, 0, bits_per_channel, 0, 0]
+            fields(&header_bytes(&buffer, 0)),
+            [
+                MODE_FIELD,
+                3,
+                RGB_MODE,
+                8,
+                bits_per_channel,
+                CHECKSUM_FIELD,
+                1,
+                SHA256_CHECKSUM,
+                END_FIELD
+            ]
         );
     }
 
     #[test]
-    fn encode_error_not_enough_data_for_mode() {
-        let header = Header::new_alpha();
-        let mut buffer = vec![0; 1];
-        let encoded = super::encode(header, &mut buffer);
+    fn encode_rgb_16bit() {
+        let header = Header::new_rgb(16, 2);
+        let mut buffer: Vec<u16> = vec![0xFFFF; header.size() * BITS];
+        assert!(super::encode(header.clone(), &mut buffer).is_ok());
+        assert_eq!(header_bytes(&buffer, 0xFFFF), header.to_bytes());
+    }
 
+    #[test]
+    fn encode_bit_order() {
+        let header = Header::new_chunk();
+        let mut buffer: Vec<u8> = vec![0xAA; header.size() * BITS];
+        assert!(super::encode(header, &mut buffer).is_ok());
+        // 'M', most significant bit first
         assert_eq!(
-            encoded
-                .unwrap_err()
-                .downcast::<HeaderEncodeError>()
-                .unwrap(),
-            HeaderEncodeError("Not enough to encode header mode.".to_string())
+            buffer[..BITS],
+            [0xAA, 0xAB, 0xAA, 0xAA, 0xAB, 0xAB, 0xAA, 0xAB]
+        );
+    }
+
+    #[test]
+    fn encode_chunk() {
+        let header = Header::new_chunk();
+        let mut buffer: Vec<u8> = vec![0; header.size() * BITS];
+        assert!(super::encode(header, &mut buffer).is_ok());
+        let buffer = header_bytes(&buffer, 0);
+        assert_eq!(
+            fields(&buffer),
+            [
+                MODE_FIELD,
+                2,
+                CHUNK_MODE,
+                8,
+                CHECKSUM_FIELD,
+                1,
+                SHA256_CHECKSUM,
+                END_FIELD
+            ]
+        );
+    }
+
+    #[test]
+    fn encode_signature() {
+        let header = Header::new_chunk().with_signature(SignatureHeader::Ed25519);
+        let mut buffer: Vec<u8> = vec![0; header.size() * BITS];
+        assert!(super::encode(header, &mut buffer).is_ok());
+        let buffer = header_bytes(&buffer, 0);
+        assert_eq!(
+            fields(&buffer)[4..7],
+            [SIGNATURE_FIELD, 1, ED25519_SIGNATURE]
+        );
+    }
+
+    #[test]
+    fn encode_metadata() {
+        let header = Header::new_chunk().with_metadata(MetadataHeader::Attributes);
+        let mut buffer: Vec<u8> = vec![0; header.size() * BITS];
+        assert!(super::encode(header.clone(), &mut buffer).is_ok());
+        let buffer = header_bytes(&buffer, 0);
+        assert_eq!(fields(&buffer)[4..7], [METADATA_FIELD, 1, FILE_METADATA]);
+
+        let header = header.with_version(1);
+        let mut buffer: Vec<u8> = vec![0; header.size() * BITS];
+        assert!(super::encode(header, &mut buffer).is_ok());
+        let buffer = header_bytes(&buffer, 0);
+        assert_eq!(
+            buffer[MAGIC.len()..buffer.len() - HEADER_CHECKSUM_LENGTH],
+            [
+                1,
+                CHUNK_MODE,
+                8,
+                NO_ENCRYPTION,
+                NO_SIGNATURE,
+                SHA256_CHECKSUM
+            ]
+        );
+    }
+
+    #[test]
+    fn encode_crc32_checksum() {
+        let header = Header::new_chunk().with_checksum(ChecksumHeader::Crc32);
+        let mut buffer: Vec<u8> = vec![0; header.size() * BITS];
+        assert!(super::encode(header, &mut buffer).is_ok());
+        let buffer = header_bytes(&buffer, 0);
+        assert_eq!(fields(&buffer)[4..7], [CHECKSUM_FIELD, 1, CRC32_CHECKSUM]);
+    }
+
+    #[test]
+    fn encode_passphrase_encryption() {
+        let encryption = PassphraseHeader {
+            salt: [0xAA; SALT_LENGTH],
+            nonce: [0xBB; NONCE_LENGTH],
+            memory_cost: 0x0102,
+            time_cost: 3,
+            parallelism: 4,
+        };
+        let header = Header::new_chunk().with_encryption(EncryptionHeader::Passphrase(encryption));
+        let mut buffer: Vec<u8> = vec![0; header.size() * BITS];
+        assert!(super::encode(header, &mut buffer).is_ok());
+        let buffer = header_bytes(&buffer, 0);
+
+        let fields = fields(&buffer);
+        assert_eq!(fields[4..7], [ENCRYPTION_FIELD, 42, PASSPHRASE_ENCRYPTION]);
+        assert_eq!(fields[7..23], [0xAA; SALT_LENGTH]);
+        assert_eq!(fields[23..35], [0xBB; NONCE_LENGTH]);
+        assert_eq!(fields[35..47], [0, 0, 1, 2, 0, 0, 0, 3, 0, 0, 0, 4]);
+        assert_eq!(fields[47], PLAIN_RECORD);
+        assert_eq!(fields[48], CHECKSUM_FIELD);
+    }
+
+    #[test]
+    fn encode_sealed_record() {
+        let header = Header::new_chunk()
+            .with_encryption(EncryptionHeader::Recipient(RecipientHeader {}))
+            .with_record(RecordHeader::Sealed);
+        let mut buffer: Vec<u8> = vec![0; header.size() * BITS];
+        assert!(super::encode(header, &mut buffer).is_ok());
+        let buffer = header_bytes(&buffer, 0);
+        assert_eq!(
+            fields(&buffer)[4..8],
+            [ENCRYPTION_FIELD, 2, RECIPIENT_ENCRYPTION, SEALED_RECORD]
         );
     }
 
     #[test]
-    fn encode_error_not_enough_data_for_bits_per_channel() {
-        let bits_per_channel = 1;
-        let header = Header::new_rgb(bits_per_channel);
-        let mut buffer = vec![0; 4];
+    fn encode_error_not_enough_data() {
+        let header = Header::new_alpha(8);
+        let mut buffer: Vec<u8> = vec![0; header.size() * BITS - 1];
         let encoded = super::encode(header, &mut buffer);
 
         assert_eq!(
@@ -92,7 +236,7 @@ mod tests {
                 .unwrap_err()
                 .downcast::<HeaderEncodeError>()
                 .unwrap(),
-            HeaderEncodeError("Not enough to encode header bits per channel.".to_string())
+            HeaderEncodeError("Not enough to encode header.".to_string())
         );
     }
 }
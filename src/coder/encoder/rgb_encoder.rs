@@ -1,23 +1,29 @@
-use std::slice::IterMut;
+use std::{mem, slice::IterMut};
 
-use crate::coder::util::{create_mask, BITS_IN_BYTE};
+use zeroize::Zeroizing;
+
+use crate::coder::{
+    header::LENGTH_SIZE,
+    sample::Sample,
+    util::{create_mask, BITS_IN_BYTE},
+};
 
 use super::Encode;
 
-pub struct RgbEncoder<'a> {
-    buffer: IterMut<'a, u8>,
-    data: Vec<u8>,
+pub struct RgbEncoder<'a, T: Sample> {
+    buffer: IterMut<'a, T>,
+    data: Zeroizing<Vec<u8>>,
     bits_per_channel: u8,
-    file_name: String,
+    file_name: Zeroizing<String>,
     mask: u8,
 }
 
-impl<'a> RgbEncoder<'a> {
+impl<'a, T: Sample> RgbEncoder<'a, T> {
     pub fn new(
-        buffer: &'a mut [u8],
-        data: Vec<u8>,
+        buffer: &'a mut [T],
+        data: Zeroizing<Vec<u8>>,
         bits_per_channel: u8,
-        file_name: String,
+        file_name: Zeroizing<String>,
     ) -> Self {
         RgbEncoder {
             buffer: buffer.iter_mut(),
@@ -29,7 +35,7 @@ impl<'a> RgbEncoder<'a> {
     }
 }
 
-impl<'a> Encode for RgbEncoder<'a> {
+impl<'a, T: Sample> Encode for RgbEncoder<'a, T> {
     fn encode_byte(&mut self, byte: u8) {
         let mask = self.mask;
         let mut shift = (BITS_IN_BYTE - self.bits_per_channel) as i32;
@@ -37,7 +43,7 @@ impl<'a> Encode for RgbEncoder<'a> {
         while shift >= 0 {
             let bits = (byte >> shift) & mask;
             let channel = self.buffer.next().unwrap();
-            *channel = (*channel & !mask) | bits;
+            *channel = channel.with_low_bits(self.bits_per_channel, bits);
             shift -= self.bits_per_channel as i32;
         }
     }
@@ -47,15 +53,15 @@ impl<'a> Encode for RgbEncoder<'a> {
     }
 
     fn bytes_to_encode(&self) -> usize {
-        self.data.len() + self.file_name.len() + 4 + 4
+        self.data.len() + self.file_name.len() + LENGTH_SIZE + LENGTH_SIZE
     }
 
-    fn file_name_bytes(&self) -> Vec<u8> {
-        self.file_name.as_bytes().to_vec()
+    fn take_file_name(&mut self) -> Zeroizing<Vec<u8>> {
+        Zeroizing::new(mem::take(&mut *self.file_name).into_bytes())
     }
 
-    fn data_bytes(&self) -> Vec<u8> {
-        self.data.clone()
+    fn take_data(&mut self) -> Zeroizing<Vec<u8>> {
+        Zeroizing::new(mem::take(&mut *self.data))
     }
 }
 
@@ -63,7 +69,9 @@ impl<'a> Encode for RgbEncoder<'a> {
 mod tests {
     use std::slice::Iter;
 
-    use crate::coder::{encoder::Encode, error::EncodeError};
+    use crate::coder::{encoder::Encode, error::EncodeError, header::LENGTH_SIZE};
+
+    use zeroize::Zeroizing;
 
     use super::RgbEncoder;
 
@@ -109,15 +117,15 @@ mod tests {
         let mut encoded_it = buffer.iter();
 
         // Encoded layout:
-        // Filename length = 4 bytes (encoded on 32 bits) = 0b00000000'00000000'00000000'00000101
+        // Filename length = 8 bytes (encoded on 64 bits) = 0b00000000'...'00000000'00000101
         // Filename = 01111000 00101110 01110000 01101110 01100111
-        // Message length = 4 bytes (encoded on 32 bits) = 0b00000000'00000000'00000000'00000011
+        // Message length = 8 bytes (encoded on 64 bits) = 0b00000000'...'00000000'00000011
         // Message bytes [...]
 
         // Using 2 bits per channel.
-        // Thus first 14 channels are = 0
+        // Thus first 30 channels are = 0
         // Filename length
-        verify_encoded(&mut encoded_it, &[0; 14]);
+        verify_encoded(&mut encoded_it, &[0; 30]);
         verify_encoded(&mut encoded_it, &[0b0000_0001, 0b0000_0001]);
 
         // x = 0111 1000
@@ -147,7 +155,7 @@ mod tests {
         );
 
         // Message Length
-        verify_encoded(&mut encoded_it, &[0; 15]);
+        verify_encoded(&mut encoded_it, &[0; 31]);
         verify_encoded(&mut encoded_it, &[0b0000_0011]);
 
         // Data
@@ -186,15 +194,15 @@ mod tests {
         let mut encoded_it = buffer.iter();
 
         // Encoded layout:
-        // Filename length = 4 bytes (encoded on 32 bits) = 0b00000000'00000000'00000000'00000101
+        // Filename length = 8 bytes (encoded on 64 bits) = 0b00000000'...'00000000'00000101
         // Filename = 01111000 00101110 01110000 01101110 01100111
-        // Message length = 4 bytes (encoded on 32 bits) = 0b00000000'00000000'00000000'00000100
+        // Message length = 8 bytes (encoded on 64 bits) = 0b00000000'...'00000000'00000100
         // Message bytes [...]
 
         // Using 4 bits per channel.
-        // Thus first 7 channels are = 0
+        // Thus first 15 channels are = 0
         // Filename length
-        verify_encoded(&mut encoded_it, &[0; 7]);
+        verify_encoded(&mut encoded_it, &[0; 15]);
         verify_encoded(&mut encoded_it, &[0b0000_0101]);
 
         // x = 0111 1000
@@ -209,7 +217,7 @@ mod tests {
         verify_encoded(&mut encoded_it, &[0b0000_0110, 0b0000_0111]);
 
         // Message Length
-        verify_encoded(&mut encoded_it, &[0; 7]);
+        verify_encoded(&mut encoded_it, &[0; 15]);
         verify_encoded(&mut encoded_it, &[0b0000_0100]);
 
         // Data
@@ -224,12 +232,17 @@ mod tests {
     }
 
     fn create_encoder<'a>(
-        buffer: &'a mut Vec<u8>,
+        buffer: &'a mut [u8],
         data: Vec<u8>,
         bits_per_channel: u8,
         file_name: String,
     ) -> Box<dyn Encode + 'a> {
-        Box::new(RgbEncoder::new(buffer, data, bits_per_channel, file_name))
+        Box::new(RgbEncoder::new(
+            buffer,
+            Zeroizing::new(data),
+            bits_per_channel,
+            Zeroizing::new(file_name),
+        ))
     }
 
     fn verify_encoded(iter: &mut Iter<u8>, bytes: &[u8]) {
@@ -243,7 +256,7 @@ mod tests {
         data_length: usize,
         bits_per_channel: u8,
     ) -> usize {
-        (4 + filename_length + 4 + data_length) * super::BITS_IN_BYTE as usize
+        (LENGTH_SIZE + filename_length + LENGTH_SIZE + data_length) * super::BITS_IN_BYTE as usize
             / bits_per_channel as usize
     }
 }
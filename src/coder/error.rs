@@ -13,6 +13,9 @@ pub struct HeaderEncodeError(pub String);
 #[derive(Debug, Clone, PartialEq)]
 pub struct HeaderDecodeError(pub String);
 
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecryptError(pub String);
+
 impl fmt::Display for EncodeError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "Encode error: {}", self.0)
@@ -37,10 +40,17 @@ impl fmt::Display for HeaderDecodeError {
     }
 }
 
+impl fmt::Display for DecryptError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Decrypt error: {}", self.0)
+    }
+}
+
 impl std::error::Error for EncodeError {}
 impl std::error::Error for DecodeError {}
 impl std::error::Error for HeaderEncodeError {}
 impl std::error::Error for HeaderDecodeError {}
+impl std::error::Error for DecryptError {}
 
 impl From<FromUtf8Error> for DecodeError {
     fn from(value: FromUtf8Error) -> Self {
@@ -52,6 +62,7 @@ impl From<FromUtf8Error> for DecodeError {
 mod tests {
 
     use super::DecodeError;
+    use super::DecryptError;
     use super::EncodeError;
     use super::HeaderDecodeError;
     use super::HeaderEncodeError;
@@ -79,4 +90,10 @@ mod tests {
         let error = HeaderDecodeError("some failure".to_string());
         assert_eq!(error.to_string(), "Header decode error: some failure");
     }
+
+    #[test]
+    fn display_decrypt_error() {
+        let error = DecryptError("some failure".to_string());
+        assert_eq!(error.to_string(), "Decrypt error: some failure");
+    }
 }
@@ -1,7 +1,59 @@
-use std::mem::size_of_val;
+use anyhow::{anyhow, Result};
+
+use super::{error::HeaderEncodeError, layout::Layout, util::BITS_IN_BYTE};
+
+/// Signature written ahead of every header, so that foreign images are not mistaken for carriers.
+pub const MAGIC: [u8; 4] = *b"MTlg";
+/// Version of the header and payload layout written by this build.
+pub const FORMAT_VERSION: u8 = 4;
+/// From format version 4, the header is stored in image samples one bit per sample, instead of
+/// whole bytes in the alpha mode channel. Byte streams store it the same way in every version.
+pub const LOW_BIT_HEADER_VERSION: u8 = 4;
+/// Size of the file name and data lengths stored ahead of them.
+pub const LENGTH_SIZE: usize = 8;
+/// Format version 1 stored lengths on 32 bits, truncating payloads over 4 GiB.
+pub const V1_LENGTH_SIZE: usize = 4;
+
+/// From format version 3, header fields are stored as type, length and value, in ascending type
+/// order and ended by [`END_FIELD`]. Fields holding default values are left out.
+pub const END_FIELD: u8 = 0;
+pub const MODE_FIELD: u8 = 1;
+pub const ENCRYPTION_FIELD: u8 = 2;
+pub const SIGNATURE_FIELD: u8 = 3;
+pub const METADATA_FIELD: u8 = 4;
+pub const CHECKSUM_FIELD: u8 = 5;
+/// Fields with this bit set may be skipped by decoders not knowing them, other unknown fields are
+/// rejected, as the payload cannot be read without them.
+pub const OPTIONAL_FIELD: u8 = 0x80;
 
 pub const ALPHA_MODE: u8 = 1;
 pub const RGB_MODE: u8 = 2;
+pub const CHUNK_MODE: u8 = 3;
+pub const FILTER_MODE: u8 = 4;
+pub const PALETTE_MODE: u8 = 5;
+pub const TEXT_MODE: u8 = 6;
+pub const SVG_MODE: u8 = 7;
+
+pub const NO_ENCRYPTION: u8 = 0;
+pub const PASSPHRASE_ENCRYPTION: u8 = 1;
+pub const RECIPIENT_ENCRYPTION: u8 = 2;
+
+pub const PLAIN_RECORD: u8 = 0;
+pub const SEALED_RECORD: u8 = 1;
+
+pub const NO_SIGNATURE: u8 = 0;
+pub const ED25519_SIGNATURE: u8 = 1;
+
+pub const NO_METADATA: u8 = 0;
+pub const FILE_METADATA: u8 = 1;
+
+pub const CRC32_CHECKSUM: u8 = 1;
+pub const SHA256_CHECKSUM: u8 = 2;
+
+/// CRC32 of all preceding header bytes, stored at the end of the header.
+pub const HEADER_CHECKSUM_LENGTH: usize = 4;
+pub const SALT_LENGTH: usize = 16;
+pub const NONCE_LENGTH: usize = 12;
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct AlphaHeader {}
@@ -11,72 +63,406 @@ pub struct RgbHeader {
     pub bits_per_channel: u8,
 }
 
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChunkHeader {}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FilterHeader {}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PaletteHeader {}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextHeader {}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SvgHeader {}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum AlgHeader {
     Alpha(AlphaHeader),
     Rgb(RgbHeader),
+    Chunk(ChunkHeader),
+    Filter(FilterHeader),
+    Palette(PaletteHeader),
+    Text(TextHeader),
+    Svg(SvgHeader),
+}
+
+impl AlgHeader {
+    /// Mode specific parameters, stored after the mode and sample width.
+    pub fn params(&self) -> Vec<u8> {
+        match self {
+            AlgHeader::Rgb(alg_header) => vec![alg_header.bits_per_channel],
+            AlgHeader::Alpha(_)
+            | AlgHeader::Chunk(_)
+            | AlgHeader::Filter(_)
+            | AlgHeader::Palette(_)
+            | AlgHeader::Text(_)
+            | AlgHeader::Svg(_) => vec![],
+        }
+    }
+}
+
+/// Argon2id key derivation parameters and ChaCha20-Poly1305 nonce.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PassphraseHeader {
+    pub salt: [u8; SALT_LENGTH],
+    pub nonce: [u8; NONCE_LENGTH],
+    pub memory_cost: u32,
+    pub time_cost: u32,
+    pub parallelism: u32,
+}
+
+/// File key wrapped to X25519 recipients. Wrapped keys are stored in the payload, ahead of the data.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecipientHeader {}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum EncryptionHeader {
+    None,
+    Passphrase(PassphraseHeader),
+    Recipient(RecipientHeader),
+}
+
+impl EncryptionHeader {
+    pub fn method(&self) -> u8 {
+        match self {
+            EncryptionHeader::None => NO_ENCRYPTION,
+            EncryptionHeader::Passphrase(_) => PASSPHRASE_ENCRYPTION,
+            EncryptionHeader::Recipient(_) => RECIPIENT_ENCRYPTION,
+        }
+    }
+
+    /// Method specific parameters, stored after the method.
+    pub fn params(&self) -> Vec<u8> {
+        match self {
+            EncryptionHeader::None => vec![],
+            EncryptionHeader::Passphrase(encryption) => [
+                &encryption.salt[..],
+                &encryption.nonce,
+                &encryption.memory_cost.to_be_bytes(),
+                &encryption.time_cost.to_be_bytes(),
+                &encryption.parallelism.to_be_bytes(),
+            ]
+            .concat(),
+            EncryptionHeader::Recipient(_) => vec![],
+        }
+    }
+}
+
+/// Whether encryption covers only the data, or the whole record along with file name and lengths.
+/// Stored right after the encryption parameters, only when data is encrypted.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RecordHeader {
+    Plain,
+    Sealed,
+}
+
+impl RecordHeader {
+    pub fn method(&self) -> u8 {
+        match self {
+            RecordHeader::Plain => PLAIN_RECORD,
+            RecordHeader::Sealed => SEALED_RECORD,
+        }
+    }
+}
+
+/// Ed25519 signature is appended to the payload, thus only the method is stored in the header.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SignatureHeader {
+    None,
+    Ed25519,
+}
+
+impl SignatureHeader {
+    pub fn method(&self) -> u8 {
+        match self {
+            SignatureHeader::None => NO_SIGNATURE,
+            SignatureHeader::Ed25519 => ED25519_SIGNATURE,
+        }
+    }
+}
+
+/// Whether file attributes are stored ahead of the data. Format version 1 has no such field.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MetadataHeader {
+    None,
+    Attributes,
+}
+
+impl MetadataHeader {
+    pub fn method(&self) -> u8 {
+        match self {
+            MetadataHeader::None => NO_METADATA,
+            MetadataHeader::Attributes => FILE_METADATA,
+        }
+    }
+}
+
+/// Checksum of the payload record, appended after the data (and its signature).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ChecksumHeader {
+    Crc32,
+    Sha256,
+}
+
+impl ChecksumHeader {
+    pub fn method(&self) -> u8 {
+        match self {
+            ChecksumHeader::Crc32 => CRC32_CHECKSUM,
+            ChecksumHeader::Sha256 => SHA256_CHECKSUM,
+        }
+    }
+}
+
+/// Header field stored as type, length and value.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HeaderField {
+    pub field_type: u8,
+    pub value: Vec<u8>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Header {
+    pub version: u8,
     pub mode: u8,
+    pub sample_bits: u8,
     pub alg_header: AlgHeader,
+    pub encryption: EncryptionHeader,
+    pub record: RecordHeader,
+    pub signature: SignatureHeader,
+    pub metadata: MetadataHeader,
+    pub checksum: ChecksumHeader,
+    /// Optional fields not known to this build, kept so that the header serializes back unchanged.
+    /// Set only through [`Header::with_unknown_fields`], which checks that their length fits a byte.
+    unknown_fields: Vec<HeaderField>,
 }
 
 impl Header {
-    pub fn new(mode: u8, alg_header: AlgHeader) -> Self {
-        Self { mode, alg_header }
+    pub fn new(mode: u8, sample_bits: u8, alg_header: AlgHeader) -> Self {
+        Self {
+            version: FORMAT_VERSION,
+            mode,
+            sample_bits,
+            alg_header,
+            encryption: EncryptionHeader::None,
+            record: RecordHeader::Plain,
+            signature: SignatureHeader::None,
+            metadata: MetadataHeader::None,
+            checksum: ChecksumHeader::Sha256,
+            unknown_fields: Vec::new(),
+        }
+    }
+
+    pub fn with_version(self, version: u8) -> Self {
+        Self { version, ..self }
     }
 
-    pub fn new_alpha() -> Self {
-        Header {
-            mode: ALPHA_MODE,
-            alg_header: AlgHeader::Alpha(AlphaHeader {}),
+    pub fn with_encryption(self, encryption: EncryptionHeader) -> Self {
+        Self { encryption, ..self }
+    }
+
+    pub fn with_record(self, record: RecordHeader) -> Self {
+        Self { record, ..self }
+    }
+
+    pub fn with_signature(self, signature: SignatureHeader) -> Self {
+        Self { signature, ..self }
+    }
+
+    pub fn with_metadata(self, metadata: MetadataHeader) -> Self {
+        Self { metadata, ..self }
+    }
+
+    pub fn with_checksum(self, checksum: ChecksumHeader) -> Self {
+        Self { checksum, ..self }
+    }
+
+    pub fn with_unknown_fields(self, unknown_fields: Vec<HeaderField>) -> Result<Self> {
+        if let Some(field) = unknown_fields
+            .iter()
+            .find(|field| u8::try_from(field.value.len()).is_err())
+        {
+            return Err(anyhow!(HeaderEncodeError(format!(
+                "Header field {} is longer than 255 bytes.",
+                field.field_type
+            ))));
         }
+        Ok(Self {
+            unknown_fields,
+            ..self
+        })
+    }
+
+    pub fn new_alpha(sample_bits: u8) -> Self {
+        Self::new(ALPHA_MODE, sample_bits, AlgHeader::Alpha(AlphaHeader {}))
+    }
+
+    pub fn new_rgb(sample_bits: u8, bits_per_channel: u8) -> Self {
+        Self::new(
+            RGB_MODE,
+            sample_bits,
+            AlgHeader::Rgb(RgbHeader { bits_per_channel }),
+        )
+    }
+
+    pub fn new_chunk() -> Self {
+        Self::new(CHUNK_MODE, u8::BITS as u8, AlgHeader::Chunk(ChunkHeader {}))
+    }
+
+    pub fn new_filter() -> Self {
+        Self::new(
+            FILTER_MODE,
+            u8::BITS as u8,
+            AlgHeader::Filter(FilterHeader {}),
+        )
+    }
+
+    pub fn new_palette() -> Self {
+        Self::new(
+            PALETTE_MODE,
+            u8::BITS as u8,
+            AlgHeader::Palette(PaletteHeader {}),
+        )
+    }
+
+    pub fn new_text() -> Self {
+        Self::new(TEXT_MODE, u8::BITS as u8, AlgHeader::Text(TextHeader {}))
+    }
+
+    pub fn new_svg() -> Self {
+        Self::new(SVG_MODE, u8::BITS as u8, AlgHeader::Svg(SvgHeader {}))
     }
 
-    pub fn new_rgb(bits_per_channel: u8) -> Self {
-        Header {
-            mode: RGB_MODE,
-            alg_header: AlgHeader::Rgb(RgbHeader { bits_per_channel }),
+    /// Size of the stored lengths, which depends on the format version.
+    pub fn length_size(&self) -> usize {
+        match self.version {
+            1 => V1_LENGTH_SIZE,
+            _ => LENGTH_SIZE,
         }
     }
 
+    /// Whether the header can store the metadata method, added in format version 2.
+    pub fn has_metadata_field(&self) -> bool {
+        self.version != 1
+    }
+
+    /// Whether data is stored as plain byte stream instead of image pixels.
+    pub fn is_byte_stream(&self) -> bool {
+        matches!(
+            self.alg_header,
+            AlgHeader::Chunk(_)
+                | AlgHeader::Filter(_)
+                | AlgHeader::Palette(_)
+                | AlgHeader::Text(_)
+                | AlgHeader::Svg(_)
+        )
+    }
+
     pub fn size(&self) -> usize {
-        let mut size: usize = 0;
-        size += size_of_val(&self.mode);
+        self.to_bytes().len()
+    }
 
-        match &self.alg_header {
-            AlgHeader::Alpha(_alg_header) => {}
-            AlgHeader::Rgb(alg_header) => {
-                size += size_of_val(&alg_header.bits_per_channel);
+    /// Number of image samples holding the header, one bit per sample, rounded up to whole pixels
+    /// so that data starts at a pixel. Earlier versions used a whole pixel per header byte.
+    pub fn sample_count(&self, layout: Layout) -> usize {
+        if self.version < LOW_BIT_HEADER_VERSION {
+            return self.size() * layout.channels();
+        }
+        (self.size() * BITS_IN_BYTE as usize).div_ceil(layout.channels()) * layout.channels()
+    }
+
+    /// Serializes the header: magic, version, fields and CRC32 of all preceding bytes. Versions 1
+    /// and 2 store field values one after another in a fixed order, without types and lengths.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.push(self.version);
+        match self.version {
+            1 | 2 => {
+                for field in self.fields() {
+                    bytes.extend(field.value);
+                }
+            }
+            _ => {
+                for field in self.fields().into_iter().chain(self.unknown_fields.clone()) {
+                    bytes.push(field.field_type);
+                    // Known fields are short and unknown ones are checked when set
+                    bytes.push(field.value.len() as u8);
+                    bytes.extend(field.value);
+                }
+                bytes.push(END_FIELD);
             }
         }
+        bytes.extend(crc32fast::hash(&bytes).to_be_bytes());
+        bytes
+    }
 
-        size
+    /// Known fields stored for the header version, in ascending type order.
+    fn fields(&self) -> Vec<HeaderField> {
+        let fixed = self.version < 3;
+        let mut fields = vec![HeaderField {
+            field_type: MODE_FIELD,
+            value: [vec![self.mode, self.sample_bits], self.alg_header.params()].concat(),
+        }];
+        if fixed || self.encryption != EncryptionHeader::None {
+            let mut value = vec![self.encryption.method()];
+            value.extend(self.encryption.params());
+            if self.encryption != EncryptionHeader::None {
+                value.push(self.record.method());
+            }
+            fields.push(HeaderField {
+                field_type: ENCRYPTION_FIELD,
+                value,
+            });
+        }
+        if fixed || self.signature != SignatureHeader::None {
+            fields.push(HeaderField {
+                field_type: SIGNATURE_FIELD,
+                value: vec![self.signature.method()],
+            });
+        }
+        if self.has_metadata_field() && (fixed || self.metadata != MetadataHeader::None) {
+            fields.push(HeaderField {
+                field_type: METADATA_FIELD,
+                value: vec![self.metadata.method()],
+            });
+        }
+        fields.push(HeaderField {
+            field_type: CHECKSUM_FIELD,
+            value: vec![self.checksum.method()],
+        });
+        fields
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::coder::header::RGB_MODE;
+    use crate::coder::header::{
+        CHUNK_MODE, FILTER_MODE, PALETTE_MODE, RGB_MODE, SVG_MODE, TEXT_MODE,
+    };
 
     use super::ALPHA_MODE;
+    use crate::coder::error::HeaderEncodeError;
 
     #[test]
     fn new() {
         let mode = ALPHA_MODE;
+        let sample_bits = 8;
         let alg_header = super::AlgHeader::Alpha(super::AlphaHeader {});
-        let header = super::Header::new(mode, alg_header.clone());
+        let header = super::Header::new(mode, sample_bits, alg_header.clone());
 
+        assert_eq!(header.version, super::FORMAT_VERSION);
         assert_eq!(header.mode, mode);
+        assert_eq!(header.sample_bits, sample_bits);
         assert_eq!(header.alg_header, alg_header);
     }
 
     #[test]
     fn new_alpha() {
-        let header = super::Header::new_alpha();
+        let header = super::Header::new_alpha(16);
         assert_eq!(header.mode, ALPHA_MODE);
+        assert_eq!(header.sample_bits, 16);
         assert_eq!(
             header.alg_header,
             super::AlgHeader::Alpha(super::AlphaHeader {})
@@ -86,23 +472,250 @@ mod tests {
     #[test]
     fn new_rgb() {
         let bits_per_channel = 4;
-        let header = super::Header::new_rgb(bits_per_channel);
+        let header = super::Header::new_rgb(8, bits_per_channel);
         assert_eq!(header.mode, RGB_MODE);
+        assert_eq!(header.sample_bits, 8);
         assert_eq!(
             header.alg_header,
             super::AlgHeader::Rgb(super::RgbHeader { bits_per_channel })
         );
     }
 
+    #[test]
+    fn new_chunk() {
+        let header = super::Header::new_chunk();
+        assert_eq!(header.mode, CHUNK_MODE);
+        assert_eq!(header.sample_bits, 8);
+        assert_eq!(
+            header.alg_header,
+            super::AlgHeader::Chunk(super::ChunkHeader {})
+        );
+    }
+
+    #[test]
+    fn new_filter() {
+        let header = super::Header::new_filter();
+        assert_eq!(header.mode, FILTER_MODE);
+        assert_eq!(header.sample_bits, 8);
+        assert_eq!(
+            header.alg_header,
+            super::AlgHeader::Filter(super::FilterHeader {})
+        );
+    }
+
+    #[test]
+    fn new_palette() {
+        let header = super::Header::new_palette();
+        assert_eq!(header.mode, PALETTE_MODE);
+        assert_eq!(header.sample_bits, 8);
+        assert_eq!(
+            header.alg_header,
+            super::AlgHeader::Palette(super::PaletteHeader {})
+        );
+    }
+
+    #[test]
+    fn new_text() {
+        let header = super::Header::new_text();
+        assert_eq!(header.mode, TEXT_MODE);
+        assert_eq!(header.sample_bits, 8);
+        assert_eq!(
+            header.alg_header,
+            super::AlgHeader::Text(super::TextHeader {})
+        );
+    }
+
+    #[test]
+    fn new_svg() {
+        let header = super::Header::new_svg();
+        assert_eq!(header.mode, SVG_MODE);
+        assert_eq!(header.sample_bits, 8);
+        assert_eq!(
+            header.alg_header,
+            super::AlgHeader::Svg(super::SvgHeader {})
+        );
+    }
+
+    #[test]
+    fn is_byte_stream() {
+        assert!(!super::Header::new_alpha(8).is_byte_stream());
+        assert!(!super::Header::new_rgb(8, 1).is_byte_stream());
+        assert!(super::Header::new_chunk().is_byte_stream());
+        assert!(super::Header::new_filter().is_byte_stream());
+        assert!(super::Header::new_palette().is_byte_stream());
+        assert!(super::Header::new_text().is_byte_stream());
+        assert!(super::Header::new_svg().is_byte_stream());
+    }
+
     #[test]
     fn size_alpha() {
-        let header = super::Header::new_alpha();
-        assert_eq!(header.size(), 1);
+        let header = super::Header::new_alpha(8);
+        assert_eq!(header.size(), 17);
     }
+
     #[test]
     fn size_rgb() {
         let bits_per_channel = 4;
-        let header = super::Header::new_rgb(bits_per_channel);
-        assert_eq!(header.size(), 2);
+        let header = super::Header::new_rgb(8, bits_per_channel);
+        assert_eq!(header.size(), 18);
+    }
+
+    #[test]
+    fn size_chunk() {
+        let header = super::Header::new_chunk();
+        assert_eq!(header.size(), 17);
+    }
+
+    #[test]
+    fn size_passphrase_encryption() {
+        let header = super::Header::new_chunk().with_encryption(
+            super::EncryptionHeader::Passphrase(super::PassphraseHeader {
+                salt: [0; super::SALT_LENGTH],
+                nonce: [0; super::NONCE_LENGTH],
+                memory_cost: 0,
+                time_cost: 0,
+                parallelism: 0,
+            }),
+        );
+        assert_eq!(header.size(), 17 + 2 + 1 + 16 + 12 + 3 * 4 + 1);
+    }
+
+    #[test]
+    fn size_recipient_encryption() {
+        let header = super::Header::new_chunk().with_encryption(
+            super::EncryptionHeader::Recipient(super::RecipientHeader {}),
+        );
+        assert_eq!(header.size(), 21);
+        assert_eq!(header.encryption.method(), super::RECIPIENT_ENCRYPTION);
+    }
+
+    #[test]
+    fn with_record() {
+        let header = super::Header::new_chunk()
+            .with_encryption(super::EncryptionHeader::Recipient(
+                super::RecipientHeader {},
+            ))
+            .with_record(super::RecordHeader::Sealed);
+        assert_eq!(header.record.method(), super::SEALED_RECORD);
+        assert_eq!(header.size(), 21);
+        assert_eq!(
+            super::Header::new_chunk().record,
+            super::RecordHeader::Plain
+        );
+    }
+
+    #[test]
+    fn with_signature() {
+        let header = super::Header::new_chunk().with_signature(super::SignatureHeader::Ed25519);
+        assert_eq!(header.size(), 20);
+        assert_eq!(header.signature.method(), super::ED25519_SIGNATURE);
+        assert_eq!(header.encryption, super::EncryptionHeader::None);
+    }
+
+    #[test]
+    fn with_checksum() {
+        let header = super::Header::new_alpha(8);
+        assert_eq!(header.checksum, super::ChecksumHeader::Sha256);
+
+        let header = header.with_checksum(super::ChecksumHeader::Crc32);
+        assert_eq!(header.checksum.method(), super::CRC32_CHECKSUM);
+        assert_eq!(header.size(), 17);
+    }
+
+    #[test]
+    fn with_metadata() {
+        let header = super::Header::new_alpha(8);
+        assert_eq!(header.metadata, super::MetadataHeader::None);
+
+        let header = header.with_metadata(super::MetadataHeader::Attributes);
+        assert_eq!(header.metadata.method(), super::FILE_METADATA);
+        assert_eq!(header.size(), 20);
+        assert_eq!(header.clone().with_version(2).size(), 15);
+        assert_eq!(header.with_version(1).size(), 14);
+    }
+
+    #[test]
+    fn to_bytes() {
+        let header = super::Header::new_rgb(8, 2).with_signature(super::SignatureHeader::Ed25519);
+        let bytes = header.to_bytes();
+        let (bytes, checksum) = bytes.split_at(bytes.len() - super::HEADER_CHECKSUM_LENGTH);
+        assert_eq!(
+            bytes,
+            [
+                &super::MAGIC[..],
+                &[super::FORMAT_VERSION],
+                &[super::MODE_FIELD, 3, RGB_MODE, 8, 2],
+                &[super::SIGNATURE_FIELD, 1, super::ED25519_SIGNATURE],
+                &[super::CHECKSUM_FIELD, 1, super::SHA256_CHECKSUM],
+                &[super::END_FIELD],
+            ]
+            .concat()
+        );
+        assert_eq!(checksum, crc32fast::hash(bytes).to_be_bytes());
+    }
+
+    #[test]
+    fn to_bytes_fixed_layout() {
+        let header = super::Header::new_rgb(8, 2).with_version(2);
+        let bytes = header.to_bytes();
+        assert_eq!(
+            bytes[super::MAGIC.len()..bytes.len() - super::HEADER_CHECKSUM_LENGTH],
+            [
+                2,
+                RGB_MODE,
+                8,
+                2,
+                super::NO_ENCRYPTION,
+                super::NO_SIGNATURE,
+                super::NO_METADATA,
+                super::SHA256_CHECKSUM
+            ]
+        );
+    }
+
+    #[test]
+    fn to_bytes_unknown_fields() {
+        let field = super::HeaderField {
+            field_type: super::OPTIONAL_FIELD | 1,
+            value: vec![0xAB, 0xCD],
+        };
+        let header = super::Header::new_chunk()
+            .with_unknown_fields(vec![field])
+            .unwrap();
+        let bytes = header.to_bytes();
+        assert_eq!(header.size(), 17 + 4);
+        assert_eq!(
+            bytes[bytes.len() - super::HEADER_CHECKSUM_LENGTH - 5
+                ..bytes.len() - super::HEADER_CHECKSUM_LENGTH],
+            [super::OPTIONAL_FIELD | 1, 2, 0xAB, 0xCD, super::END_FIELD]
+        );
+    }
+
+    #[test]
+    fn with_unknown_fields_error_too_long() {
+        let field = super::HeaderField {
+            field_type: super::OPTIONAL_FIELD | 1,
+            value: vec![0; 256],
+        };
+        let header = super::Header::new_chunk().with_unknown_fields(vec![field]);
+        assert_eq!(
+            header.unwrap_err().downcast::<HeaderEncodeError>().unwrap(),
+            HeaderEncodeError("Header field 129 is longer than 255 bytes.".to_string())
+        );
+    }
+
+    #[test]
+    fn length_size() {
+        let header = super::Header::new_alpha(8);
+        assert_eq!(header.version, super::FORMAT_VERSION);
+        assert_eq!(header.length_size(), super::LENGTH_SIZE);
+        assert_eq!(header.with_version(1).length_size(), super::V1_LENGTH_SIZE);
+    }
+
+    #[test]
+    fn with_encryption() {
+        let header = super::Header::new_alpha(16).with_encryption(super::EncryptionHeader::None);
+        assert_eq!(header, super::Header::new_alpha(16));
+        assert_eq!(header.encryption.method(), super::NO_ENCRYPTION);
     }
 }
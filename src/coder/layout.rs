@@ -0,0 +1,67 @@
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Layout {
+    L,
+    La,
+    Rgb,
+    Rgba,
+}
+
+impl Layout {
+    pub fn from_channel_count(channels: u8) -> Option<Self> {
+        match channels {
+            1 => Some(Layout::L),
+            2 => Some(Layout::La),
+            3 => Some(Layout::Rgb),
+            4 => Some(Layout::Rgba),
+            _ => None,
+        }
+    }
+
+    pub fn channels(&self) -> usize {
+        match self {
+            Layout::L => 1,
+            Layout::La => 2,
+            Layout::Rgb => 3,
+            Layout::Rgba => 4,
+        }
+    }
+
+    pub fn has_alpha(&self) -> bool {
+        matches!(self, Layout::La | Layout::Rgba)
+    }
+
+    /// Channel holding alpha mode data: alpha if present, otherwise the last color channel.
+    pub fn alpha_mode_channel(&self) -> usize {
+        self.channels() - 1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Layout;
+
+    #[test]
+    fn from_channel_count() {
+        assert_eq!(Layout::from_channel_count(1), Some(Layout::L));
+        assert_eq!(Layout::from_channel_count(2), Some(Layout::La));
+        assert_eq!(Layout::from_channel_count(3), Some(Layout::Rgb));
+        assert_eq!(Layout::from_channel_count(4), Some(Layout::Rgba));
+        assert_eq!(Layout::from_channel_count(5), None);
+    }
+
+    #[test]
+    fn has_alpha() {
+        assert!(!Layout::L.has_alpha());
+        assert!(Layout::La.has_alpha());
+        assert!(!Layout::Rgb.has_alpha());
+        assert!(Layout::Rgba.has_alpha());
+    }
+
+    #[test]
+    fn alpha_mode_channel() {
+        assert_eq!(Layout::L.alpha_mode_channel(), 0);
+        assert_eq!(Layout::La.alpha_mode_channel(), 1);
+        assert_eq!(Layout::Rgb.alpha_mode_channel(), 2);
+        assert_eq!(Layout::Rgba.alpha_mode_channel(), 3);
+    }
+}
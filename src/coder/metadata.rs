@@ -0,0 +1,243 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::{anyhow, Result};
+use zeroize::Zeroizing;
+
+use super::error::{DecodeError, EncodeError};
+
+const MODIFIED_FLAG: u8 = 1;
+const PERMISSIONS_FLAG: u8 = 2;
+const CONTENT_TYPE_FLAG: u8 = 4;
+
+/// Attributes of the hidden file, optionally stored ahead of its data.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Metadata {
+    pub modified: Option<SystemTime>,
+    /// Unix permission bits, such as `0o755` for an executable script.
+    pub permissions: Option<u32>,
+    /// MIME type, such as `text/plain`.
+    pub content_type: Option<String>,
+}
+
+impl Metadata {
+    pub fn is_empty(&self) -> bool {
+        self.modified.is_none() && self.permissions.is_none() && self.content_type.is_none()
+    }
+
+    /// Serializes attributes as a flags byte followed by the present fields: modification time as
+    /// signed seconds and nanoseconds since Unix epoch, permission bits and length prefixed
+    /// content type.
+    fn to_bytes(&self) -> Result<Vec<u8>> {
+        let mut flags = 0;
+        let mut bytes = vec![0];
+        if let Some(modified) = self.modified {
+            flags |= MODIFIED_FLAG;
+            let (seconds, nanos) = match modified.duration_since(UNIX_EPOCH) {
+                Ok(since) => (since.as_secs() as i64, since.subsec_nanos()),
+                Err(error) => {
+                    let before = error.duration();
+                    match before.subsec_nanos() {
+                        0 => (-(before.as_secs() as i64), 0),
+                        nanos => (-(before.as_secs() as i64) - 1, 1_000_000_000 - nanos),
+                    }
+                }
+            };
+            bytes.extend_from_slice(&seconds.to_be_bytes());
+            bytes.extend_from_slice(&nanos.to_be_bytes());
+        }
+        if let Some(permissions) = self.permissions {
+            flags |= PERMISSIONS_FLAG;
+            bytes.extend_from_slice(&permissions.to_be_bytes());
+        }
+        if let Some(content_type) = &self.content_type {
+            flags |= CONTENT_TYPE_FLAG;
+            let length = u8::try_from(content_type.len())
+                .map_err(|_| EncodeError("Content type is longer than 255 bytes.".to_string()))?;
+            bytes.push(length);
+            bytes.extend_from_slice(content_type.as_bytes());
+        }
+        bytes[0] = flags;
+        Ok(bytes)
+    }
+
+    /// Parses attributes written by [`Metadata::to_bytes`], returning them with their size.
+    fn from_bytes(bytes: &[u8]) -> Result<(Self, usize)> {
+        let mut reader = Reader { bytes, position: 0 };
+        let flags = reader.read::<1>()?[0];
+        if flags & !(MODIFIED_FLAG | PERMISSIONS_FLAG | CONTENT_TYPE_FLAG) != 0 {
+            return Err(anyhow!(DecodeError(
+                "Unknown file metadata attributes.".to_string()
+            )));
+        }
+
+        let mut metadata = Metadata::default();
+        if flags & MODIFIED_FLAG != 0 {
+            let seconds = i64::from_be_bytes(reader.read()?);
+            let nanos = u32::from_be_bytes(reader.read()?);
+            let since = Duration::from_secs(seconds.unsigned_abs());
+            let modified = if seconds >= 0 {
+                UNIX_EPOCH.checked_add(since)
+            } else {
+                UNIX_EPOCH.checked_sub(since)
+            };
+            metadata.modified = modified
+                .and_then(|modified| modified.checked_add(Duration::from_nanos(nanos.into())));
+        }
+        if flags & PERMISSIONS_FLAG != 0 {
+            metadata.permissions = Some(u32::from_be_bytes(reader.read()?));
+        }
+        if flags & CONTENT_TYPE_FLAG != 0 {
+            let length = reader.read::<1>()?[0] as usize;
+            let content_type = reader.read_slice(length)?;
+            metadata.content_type =
+                Some(String::from_utf8(content_type.to_vec()).map_err(|_| {
+                    DecodeError("Content type in file metadata is not valid UTF-8.".to_string())
+                })?);
+        }
+        Ok((metadata, reader.position))
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn read<const N: usize>(&mut self) -> Result<[u8; N]> {
+        Ok(self.read_slice(N)?.try_into()?)
+    }
+
+    fn read_slice(&mut self, length: usize) -> Result<&'a [u8]> {
+        let slice = self
+            .bytes
+            .get(self.position..self.position + length)
+            .ok_or(DecodeError(
+                "Not enough data to decode file metadata.".to_string(),
+            ))?;
+        self.position += length;
+        Ok(slice)
+    }
+}
+
+/// Prepends serialized metadata to the data, so that it is encrypted and signed along with it.
+pub(crate) fn prepend(metadata: &Metadata, data: Zeroizing<Vec<u8>>) -> Result<Zeroizing<Vec<u8>>> {
+    let bytes = metadata.to_bytes()?;
+    // Copied into a buffer of final size, as growing the data in place would leave copies behind
+    let mut prefixed = Zeroizing::new(Vec::with_capacity(bytes.len() + data.len()));
+    prefixed.extend_from_slice(&bytes);
+    prefixed.extend_from_slice(&data);
+    Ok(prefixed)
+}
+
+/// Splits metadata prepended by [`prepend`] off the data.
+pub(crate) fn split(mut data: Zeroizing<Vec<u8>>) -> Result<(Metadata, Zeroizing<Vec<u8>>)> {
+    let (metadata, size) = Metadata::from_bytes(&data)?;
+    data.drain(..size);
+    Ok((metadata, data))
+}
+
+/// Guesses content type from the file name extension, for common file types only.
+pub fn guess_content_type(file_name: &str) -> Option<String> {
+    let (_, extension) = file_name.rsplit_once('.')?;
+    let content_type = match extension.to_ascii_lowercase().as_str() {
+        "txt" => "text/plain",
+        "md" => "text/markdown",
+        "html" | "htm" => "text/html",
+        "csv" => "text/csv",
+        "json" => "application/json",
+        "xml" => "application/xml",
+        "pdf" => "application/pdf",
+        "zip" => "application/zip",
+        "gz" => "application/gzip",
+        "tar" => "application/x-tar",
+        "sh" => "application/x-sh",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "svg" => "image/svg+xml",
+        "mp3" => "audio/mpeg",
+        "mp4" => "video/mp4",
+        _ => return None,
+    };
+    Some(content_type.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use zeroize::Zeroizing;
+
+    use crate::coder::error::DecodeError;
+
+    use super::Metadata;
+
+    #[test]
+    fn prepend_split() {
+        let metadata = Metadata {
+            modified: Some(UNIX_EPOCH + Duration::new(1_700_000_000, 123)),
+            permissions: Some(0o755),
+            content_type: Some("text/x-shellscript".to_string()),
+        };
+        let data = super::prepend(&metadata, Zeroizing::new(b"data".to_vec())).unwrap();
+        assert_eq!(data.len(), 1 + 12 + 4 + 1 + 18 + 4);
+
+        let (split, data) = super::split(data).unwrap();
+        assert_eq!(split, metadata);
+        assert_eq!(*data, b"data");
+    }
+
+    #[test]
+    fn prepend_split_partial() {
+        let metadata = Metadata {
+            permissions: Some(0o600),
+            ..Default::default()
+        };
+        let data = super::prepend(&metadata, Zeroizing::new(b"data".to_vec())).unwrap();
+        assert_eq!(data[..5], [2, 0, 0, 0x01, 0x80]);
+
+        let (split, data) = super::split(data).unwrap();
+        assert_eq!(split, metadata);
+        assert_eq!(*data, b"data");
+    }
+
+    #[test]
+    fn prepend_split_before_epoch() {
+        let metadata = Metadata {
+            modified: Some(UNIX_EPOCH - Duration::new(10, 250)),
+            ..Default::default()
+        };
+        let data = super::prepend(&metadata, Zeroizing::new(vec![])).unwrap();
+        let (split, _) = super::split(data).unwrap();
+        assert_eq!(split, metadata);
+    }
+
+    #[test]
+    fn split_error_not_enough_data() {
+        let split = super::split(Zeroizing::new(vec![1, 0, 0]));
+        assert_eq!(
+            split.unwrap_err().downcast::<DecodeError>().unwrap(),
+            DecodeError("Not enough data to decode file metadata.".to_string())
+        );
+    }
+
+    #[test]
+    fn split_error_unknown_attributes() {
+        let split = super::split(Zeroizing::new(vec![8, 0]));
+        assert_eq!(
+            split.unwrap_err().downcast::<DecodeError>().unwrap(),
+            DecodeError("Unknown file metadata attributes.".to_string())
+        );
+    }
+
+    #[test]
+    fn guess_content_type() {
+        assert_eq!(
+            super::guess_content_type("run.SH"),
+            Some("application/x-sh".to_string())
+        );
+        assert_eq!(super::guess_content_type("secret"), None);
+        assert_eq!(super::guess_content_type("secret.unknown"), None);
+    }
+}
@@ -0,0 +1,122 @@
+use half::f16;
+
+use super::util::create_mask;
+
+pub trait Sample: Copy {
+    const BITS: u8;
+
+    fn low_bits(self, bits: u8) -> u8;
+    fn with_low_bits(self, bits: u8, value: u8) -> Self;
+}
+
+impl Sample for u8 {
+    const BITS: u8 = 8;
+
+    fn low_bits(self, bits: u8) -> u8 {
+        self & create_mask(bits)
+    }
+
+    fn with_low_bits(self, bits: u8, value: u8) -> Self {
+        let mask = create_mask(bits);
+        (self & !mask) | (value & mask)
+    }
+}
+
+impl Sample for u16 {
+    const BITS: u8 = 16;
+
+    fn low_bits(self, bits: u8) -> u8 {
+        (self & create_mask(bits) as u16) as u8
+    }
+
+    fn with_low_bits(self, bits: u8, value: u8) -> Self {
+        let mask = create_mask(bits) as u16;
+        (self & !mask) | (value as u16 & mask)
+    }
+}
+
+/// Data is stored in the least significant mantissa bits.
+impl Sample for f16 {
+    const BITS: u8 = 16;
+
+    fn low_bits(self, bits: u8) -> u8 {
+        self.to_bits().low_bits(bits)
+    }
+
+    fn with_low_bits(self, bits: u8, value: u8) -> Self {
+        f16::from_bits(self.to_bits().with_low_bits(bits, value))
+    }
+}
+
+/// Data is stored in the least significant mantissa bits.
+impl Sample for f32 {
+    const BITS: u8 = 32;
+
+    fn low_bits(self, bits: u8) -> u8 {
+        (self.to_bits() & create_mask(bits) as u32) as u8
+    }
+
+    fn with_low_bits(self, bits: u8, value: u8) -> Self {
+        let mask = create_mask(bits) as u32;
+        f32::from_bits((self.to_bits() & !mask) | (value as u32 & mask))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use half::f16;
+
+    use super::Sample;
+
+    #[test]
+    fn u8_low_bits() {
+        assert_eq!(0b1010_1101u8.low_bits(1), 0b1);
+        assert_eq!(0b1010_1101u8.low_bits(4), 0b1101);
+        assert_eq!(0b1010_1101u8.low_bits(8), 0b1010_1101);
+    }
+
+    #[test]
+    fn u8_with_low_bits() {
+        assert_eq!(0b1111_1111u8.with_low_bits(2, 0b00), 0b1111_1100);
+        assert_eq!(0b0000_0000u8.with_low_bits(4, 0b1111_0101), 0b0000_0101);
+        assert_eq!(0b1111_0000u8.with_low_bits(8, 0b0101_0101), 0b0101_0101);
+    }
+
+    #[test]
+    fn u16_low_bits() {
+        assert_eq!(0xABCDu16.low_bits(4), 0x0D);
+        assert_eq!(0xABCDu16.low_bits(8), 0xCD);
+    }
+
+    #[test]
+    fn u16_with_low_bits() {
+        assert_eq!(0xFFFFu16.with_low_bits(2, 0b00), 0xFFFC);
+        assert_eq!(0xABCDu16.with_low_bits(8, 0x12), 0xAB12);
+    }
+
+    #[test]
+    fn f16_low_bits() {
+        assert_eq!(f16::from_bits(0x3C0D).low_bits(4), 0x0D);
+        assert_eq!(f16::from_bits(0x3CCD).low_bits(8), 0xCD);
+    }
+
+    #[test]
+    fn f16_with_low_bits() {
+        let sample = f16::from_f32(1.0).with_low_bits(2, 0b11);
+        assert_eq!(sample.to_bits(), 0x3C03);
+        assert!((sample.to_f32() - 1.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn f32_low_bits() {
+        assert_eq!(f32::from_bits(0x3F80_00AB).low_bits(4), 0x0B);
+        assert_eq!(f32::from_bits(0x3F80_00AB).low_bits(8), 0xAB);
+    }
+
+    #[test]
+    fn f32_with_low_bits() {
+        let sample = 1.0f32.with_low_bits(8, 0xFF);
+        assert_eq!(sample.to_bits(), 0x3F80_00FF);
+        assert!((sample - 1.0).abs() < 1e-4);
+    }
+}
@@ -0,0 +1,260 @@
+use std::{fmt, str::FromStr};
+
+use anyhow::{anyhow, Result};
+use ed25519_dalek::{Signature, Signer, SIGNATURE_LENGTH};
+use rand::rngs::OsRng;
+use zeroize::Zeroizing;
+
+use super::{
+    crypto::{decode_key, encode_key, key_line},
+    error::DecodeError,
+    header::{Header, SignatureHeader},
+};
+
+const SIGNING_KEY_PREFIX: &str = "mtg-signing-key-";
+const VERIFYING_KEY_PREFIX: &str = "mtgverify";
+
+/// Ed25519 secret key, written in Bech32 as `MTG-SIGNING-KEY-1...`.
+#[derive(Clone)]
+pub struct SigningKey(ed25519_dalek::SigningKey);
+
+/// Ed25519 public key, written in Bech32 as `mtgverify1...`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VerifyingKey(ed25519_dalek::VerifyingKey);
+
+/// Outcome of checking the signature of decoded data.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SignatureStatus {
+    Unsigned,
+    /// Data is signed, but no verifying key was given to check it.
+    Unverified,
+    Valid,
+    Invalid,
+}
+
+impl SigningKey {
+    pub fn generate() -> Self {
+        SigningKey(ed25519_dalek::SigningKey::generate(&mut OsRng))
+    }
+
+    pub fn verifying_key(&self) -> VerifyingKey {
+        VerifyingKey(self.0.verifying_key())
+    }
+}
+
+impl fmt::Debug for SigningKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "SigningKey({})", self.verifying_key())
+    }
+}
+
+impl fmt::Display for SigningKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let key = encode_key(SIGNING_KEY_PREFIX, self.0.as_bytes()).map_err(|_| fmt::Error)?;
+        write!(f, "{}", key.to_uppercase())
+    }
+}
+
+/// Parses signing key file contents, skipping empty lines and `#` comments.
+impl FromStr for SigningKey {
+    type Err = anyhow::Error;
+
+    fn from_str(contents: &str) -> Result<Self> {
+        let key = decode_key(key_line(contents)?, SIGNING_KEY_PREFIX)
+            .ok_or(anyhow!("Invalid signing key."))?;
+        Ok(SigningKey(ed25519_dalek::SigningKey::from_bytes(&key)))
+    }
+}
+
+impl fmt::Display for VerifyingKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let key = encode_key(VERIFYING_KEY_PREFIX, self.0.as_bytes()).map_err(|_| fmt::Error)?;
+        write!(f, "{}", key)
+    }
+}
+
+impl FromStr for VerifyingKey {
+    type Err = anyhow::Error;
+
+    fn from_str(key: &str) -> Result<Self> {
+        let bytes = decode_key(key.trim(), VERIFYING_KEY_PREFIX)
+            .ok_or(anyhow!("Invalid verifying key: {}", key))?;
+        let key = ed25519_dalek::VerifyingKey::from_bytes(&bytes)
+            .map_err(|_| anyhow!("Invalid verifying key: {}", key))?;
+        Ok(VerifyingKey(key))
+    }
+}
+
+impl fmt::Display for SignatureStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let status = match self {
+            SignatureStatus::Unsigned => "unsigned",
+            SignatureStatus::Unverified => "unverified",
+            SignatureStatus::Valid => "valid",
+            SignatureStatus::Invalid => "invalid",
+        };
+        write!(f, "{}", status)
+    }
+}
+
+/// Appends signature over the header, file name and data, marking the header as signed.
+pub(crate) fn sign(
+    header: Header,
+    signing_key: Option<&SigningKey>,
+    data: Zeroizing<Vec<u8>>,
+    file_name: &str,
+) -> Result<(Header, Zeroizing<Vec<u8>>)> {
+    let Some(SigningKey(signing_key)) = signing_key else {
+        return Ok((header, data));
+    };
+
+    let header = header.with_signature(SignatureHeader::Ed25519);
+    let signature = signing_key.sign(&message(&header, file_name, &data)?);
+    // Copied into a buffer of final size, as growing the data in place would leave copies behind
+    let mut signed = Zeroizing::new(Vec::with_capacity(data.len() + SIGNATURE_LENGTH));
+    signed.extend_from_slice(&data);
+    signed.extend_from_slice(&signature.to_bytes());
+    Ok((header, signed))
+}
+
+/// Splits signature appended by [`sign`] off the data and checks it against any of the keys.
+pub(crate) fn verify(
+    header: &Header,
+    verifying_keys: &[VerifyingKey],
+    mut data: Zeroizing<Vec<u8>>,
+    file_name: &str,
+) -> Result<(SignatureStatus, Zeroizing<Vec<u8>>)> {
+    if header.signature == SignatureHeader::None {
+        return Ok((SignatureStatus::Unsigned, data));
+    }
+
+    let signature_start = data.len().checked_sub(SIGNATURE_LENGTH).ok_or(DecodeError(
+        "Not enough data to read signature.".to_string(),
+    ))?;
+    let signature = Signature::from_slice(&data.split_off(signature_start))?;
+    if verifying_keys.is_empty() {
+        return Ok((SignatureStatus::Unverified, data));
+    }
+
+    let message = message(header, file_name, &data)?;
+    let valid = verifying_keys
+        .iter()
+        .any(|VerifyingKey(key)| key.verify_strict(&message, &signature).is_ok());
+    let status = if valid {
+        SignatureStatus::Valid
+    } else {
+        SignatureStatus::Invalid
+    };
+    Ok((status, data))
+}
+
+fn message(header: &Header, file_name: &str, data: &[u8]) -> Result<Zeroizing<Vec<u8>>> {
+    let header = header.to_bytes();
+    let mut message = Zeroizing::new(Vec::with_capacity(
+        header.len() + 4 + file_name.len() + data.len(),
+    ));
+    message.extend_from_slice(&header);
+    message.extend_from_slice(&(file_name.len() as u32).to_be_bytes());
+    message.extend_from_slice(file_name.as_bytes());
+    message.extend_from_slice(data);
+    Ok(message)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::coder::header::{Header, SignatureHeader};
+
+    use zeroize::Zeroizing;
+
+    use super::{SignatureStatus, SigningKey, VerifyingKey};
+
+    #[test]
+    fn sign_verify() {
+        let signing_key = SigningKey::generate();
+        let (header, signed) = sign(Some(&signing_key), b"data");
+        assert_eq!(header.signature, SignatureHeader::Ed25519);
+        assert_eq!(signed.len(), 4 + 64);
+
+        let keys = [
+            SigningKey::generate().verifying_key(),
+            signing_key.verifying_key(),
+        ];
+        let (status, data) = super::verify(&header, &keys, signed, "secret.txt").unwrap();
+        assert_eq!(status, SignatureStatus::Valid);
+        assert_eq!(*data, b"data");
+    }
+
+    #[test]
+    fn sign_without_key() {
+        let (header, data) = sign(None, b"data");
+        assert_eq!(header, Header::new_chunk());
+
+        let keys = [SigningKey::generate().verifying_key()];
+        let (status, data) = super::verify(&header, &keys, data, "secret.txt").unwrap();
+        assert_eq!(status, SignatureStatus::Unsigned);
+        assert_eq!(*data, b"data");
+    }
+
+    #[test]
+    fn verify_without_keys() {
+        let (header, signed) = sign(Some(&SigningKey::generate()), b"data");
+        let (status, data) = super::verify(&header, &[], signed, "secret.txt").unwrap();
+        assert_eq!(status, SignatureStatus::Unverified);
+        assert_eq!(*data, b"data");
+    }
+
+    #[test]
+    fn verify_invalid() {
+        let signing_key = SigningKey::generate();
+        let keys = [signing_key.verifying_key()];
+        let (header, signed) = sign(Some(&signing_key), b"data");
+
+        let other_keys = [SigningKey::generate().verifying_key()];
+        let verified = super::verify(&header, &other_keys, signed.clone(), "secret.txt");
+        assert_eq!(verified.unwrap().0, SignatureStatus::Invalid);
+
+        let verified = super::verify(&header, &keys, signed.clone(), "other.txt");
+        assert_eq!(verified.unwrap().0, SignatureStatus::Invalid);
+
+        let mut tampered = signed;
+        tampered[0] ^= 1;
+        let verified = super::verify(&header, &keys, tampered, "secret.txt");
+        assert_eq!(verified.unwrap().0, SignatureStatus::Invalid);
+    }
+
+    #[test]
+    fn verify_missing_signature() {
+        let header = Header::new_chunk().with_signature(SignatureHeader::Ed25519);
+        assert!(super::verify(&header, &[], Zeroizing::new(vec![0; 63]), "secret.txt").is_err());
+    }
+
+    #[test]
+    fn keys_from_str() {
+        let signing_key = SigningKey::generate();
+        let encoded = signing_key.to_string();
+        assert!(encoded.starts_with("MTG-SIGNING-KEY-1"));
+        let parsed: SigningKey = format!("# comment\n{}\n", encoded).parse().unwrap();
+        assert_eq!(parsed.verifying_key(), signing_key.verifying_key());
+
+        let verifying_key = signing_key.verifying_key();
+        let encoded = verifying_key.to_string();
+        assert!(encoded.starts_with("mtgverify1"));
+        assert_eq!(encoded.parse::<VerifyingKey>().unwrap(), verifying_key);
+
+        assert!(encoded.parse::<SigningKey>().is_err());
+        assert!(signing_key.to_string().parse::<VerifyingKey>().is_err());
+    }
+
+    #[test]
+    fn status_display() {
+        assert_eq!(SignatureStatus::Unsigned.to_string(), "unsigned");
+        assert_eq!(SignatureStatus::Unverified.to_string(), "unverified");
+        assert_eq!(SignatureStatus::Valid.to_string(), "valid");
+        assert_eq!(SignatureStatus::Invalid.to_string(), "invalid");
+    }
+
+    fn sign(signing_key: Option<&SigningKey>, data: &[u8]) -> (Header, Zeroizing<Vec<u8>>) {
+        let data = Zeroizing::new(data.to_vec());
+        super::sign(Header::new_chunk(), signing_key, data, "secret.txt").unwrap()
+    }
+}
@@ -0,0 +1,156 @@
+use anyhow::{anyhow, Result};
+use argon2::Params;
+use chacha20poly1305::{
+    aead::{Aead, KeyInit, Payload},
+    ChaCha20Poly1305, Key, Nonce,
+};
+use hkdf::Hkdf;
+use rand::{rngs::OsRng, RngCore};
+use sha2::Sha256;
+use zeroize::Zeroizing;
+
+use super::{
+    crypto::{self, Passphrase, KEY_LENGTH, MEMORY_COST, PARALLELISM, TAG_LENGTH, TIME_COST},
+    error::EncodeError,
+    header::NONCE_LENGTH,
+};
+
+/// Fixed salt, as there is no header to store a random one in.
+const STEALTH_SALT: &[u8] = b"mythic-telegram/stealth";
+const PARAMS_INFO: &[u8] = b"mythic-telegram/stealth-params";
+/// Low bits per channel the key chooses from.
+const BITS_PER_CHANNEL: [u8; 2] = [1, 2];
+const LENGTH_SIZE: usize = 4;
+/// Random nonce followed by the masked length of the ciphertext.
+pub(super) const PREFIX_LENGTH: usize = NONCE_LENGTH + LENGTH_SIZE;
+
+/// Parameters of headerless embedding, all derived from the passphrase. Data is stored in the
+/// low bits of all channels, starting at a key-derived sample and wrapping around to the first one.
+pub(super) struct Stealth {
+    pub bits_per_channel: u8,
+    start: u64,
+    length_mask: [u8; LENGTH_SIZE],
+    cipher: ChaCha20Poly1305,
+}
+
+impl Stealth {
+    pub fn derive(passphrase: &Passphrase) -> Result<Self> {
+        let params = Params::new(MEMORY_COST, TIME_COST, PARALLELISM, None)
+            .map_err(|error| anyhow!(error.to_string()))?;
+        let master_key = crypto::derive_key(passphrase, STEALTH_SALT, params)?;
+
+        let mut keys = Zeroizing::new([0; KEY_LENGTH + 8 + LENGTH_SIZE + 1]);
+        Hkdf::<Sha256>::new(None, &*master_key)
+            .expand(PARAMS_INFO, &mut *keys)
+            .map_err(|error| anyhow!(error.to_string()))?;
+        let (key, params) = keys.split_at(KEY_LENGTH);
+        let (start, params) = params.split_at(8);
+        let (length_mask, params) = params.split_at(LENGTH_SIZE);
+
+        Ok(Stealth {
+            bits_per_channel: BITS_PER_CHANNEL[(params[0] & 1) as usize],
+            start: u64::from_be_bytes(start.try_into()?),
+            length_mask: length_mask.try_into()?,
+            cipher: ChaCha20Poly1305::new(Key::from_slice(key)),
+        })
+    }
+
+    /// Index of the sample data starts at.
+    pub fn start(&self, samples: usize) -> usize {
+        match samples {
+            0 => 0,
+            _ => (self.start % samples as u64) as usize,
+        }
+    }
+
+    /// Encrypts the record, prefixed with a random nonce and the masked ciphertext length.
+    pub fn seal(&self, record: &[u8]) -> Result<Vec<u8>> {
+        let length = u32::try_from(record.len() + TAG_LENGTH)
+            .map_err(|_| EncodeError("Too much data to encode in the image.".to_string()))?;
+        let mut nonce = [0; NONCE_LENGTH];
+        OsRng.fill_bytes(&mut nonce);
+        let masked_length = self.mask(length.to_be_bytes());
+
+        let ciphertext = self
+            .cipher
+            .encrypt(
+                Nonce::from_slice(&nonce),
+                Payload {
+                    msg: record,
+                    aad: &masked_length,
+                },
+            )
+            .map_err(|_| anyhow!(EncodeError("Could not encrypt data.".to_string())))?;
+
+        let mut payload = Vec::with_capacity(PREFIX_LENGTH + ciphertext.len());
+        payload.extend_from_slice(&nonce);
+        payload.extend_from_slice(&masked_length);
+        payload.extend(ciphertext);
+        Ok(payload)
+    }
+
+    /// Length of the ciphertext following the prefix.
+    pub fn ciphertext_length(&self, prefix: &[u8; PREFIX_LENGTH]) -> usize {
+        let mut masked_length = [0; LENGTH_SIZE];
+        masked_length.copy_from_slice(&prefix[NONCE_LENGTH..]);
+        u32::from_be_bytes(self.mask(masked_length)) as usize
+    }
+
+    /// Decrypts the record, or returns `None` if the tag does not match,
+    /// meaning there is no stealth data for this passphrase.
+    pub fn open(
+        &self,
+        prefix: &[u8; PREFIX_LENGTH],
+        ciphertext: &[u8],
+    ) -> Option<Zeroizing<Vec<u8>>> {
+        self.cipher
+            .decrypt(
+                Nonce::from_slice(&prefix[..NONCE_LENGTH]),
+                Payload {
+                    msg: ciphertext,
+                    aad: &prefix[NONCE_LENGTH..],
+                },
+            )
+            .ok()
+            .map(Zeroizing::new)
+    }
+
+    fn mask(&self, length: [u8; LENGTH_SIZE]) -> [u8; LENGTH_SIZE] {
+        std::array::from_fn(|i| length[i] ^ self.length_mask[i])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::coder::crypto::Passphrase;
+
+    use super::{Stealth, PREFIX_LENGTH};
+
+    #[test]
+    fn derive_deterministic() {
+        let first = Stealth::derive(&Passphrase::from("correct horse")).unwrap();
+        let second = Stealth::derive(&Passphrase::from("correct horse")).unwrap();
+        assert_eq!(first.bits_per_channel, second.bits_per_channel);
+        assert_eq!(first.start(1_000_003), second.start(1_000_003));
+        assert_eq!(first.length_mask, second.length_mask);
+        assert!([1, 2].contains(&first.bits_per_channel));
+        assert_eq!(first.start(0), 0);
+
+        let other = Stealth::derive(&Passphrase::from("wrong horse")).unwrap();
+        assert_ne!(first.length_mask, other.length_mask);
+    }
+
+    #[test]
+    fn seal_open() {
+        let stealth = Stealth::derive(&Passphrase::from("correct horse")).unwrap();
+        let payload = stealth.seal(b"record").unwrap();
+        let (prefix, ciphertext) = payload.split_at(PREFIX_LENGTH);
+        let prefix: &[u8; PREFIX_LENGTH] = prefix.try_into().unwrap();
+
+        assert_eq!(stealth.ciphertext_length(prefix), ciphertext.len());
+        assert_eq!(*stealth.open(prefix, ciphertext).unwrap(), b"record");
+
+        let other = Stealth::derive(&Passphrase::from("wrong horse")).unwrap();
+        assert!(other.open(prefix, ciphertext).is_none());
+    }
+}
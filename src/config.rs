@@ -1,5 +1,9 @@
+use anyhow::{anyhow, Result};
 use clap::builder::TypedValueParser;
-use clap::{Args, Parser, Subcommand};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use zeroize::Zeroizing;
+
+use crate::{coder::crypto::Passphrase, file};
 
 #[derive(Debug, Parser)]
 pub struct Config {
@@ -12,6 +16,8 @@ pub struct Config {
 pub enum Mode {
     Encode(EncodeConfig),
     Decode(DecodeConfig),
+    /// Generate X25519 identity or Ed25519 signing key file and print its public key.
+    Keygen(KeygenConfig),
 }
 
 #[derive(Debug, Args)]
@@ -22,6 +28,52 @@ pub struct EncodeConfig {
     #[arg(short, long, help = "Path to data file to be hidden.")]
     pub secret_file: std::path::PathBuf,
 
+    #[command(flatten)]
+    pub secret: SecretConfig,
+
+    #[arg(
+        short,
+        long,
+        conflicts_with = "SecretConfig",
+        help = "Public key of recipient to encrypt hidden data to. Can be repeated."
+    )]
+    pub recipient: Vec<String>,
+
+    #[arg(long, help = "Path to signing key file used to sign hidden data.")]
+    pub sign_key: Option<std::path::PathBuf>,
+
+    #[arg(
+        long,
+        help = "Encrypt file name and lengths along with hidden data. Requires passphrase or recipient."
+    )]
+    pub seal_record: bool,
+
+    #[arg(
+        long,
+        help = "Fill unused capacity with random padding, hiding where the data ends. Not supported in alpha, chunk and text modes."
+    )]
+    pub pad: bool,
+
+    #[arg(
+        long,
+        value_enum,
+        default_value_t,
+        help = "Checksum guarding hidden data against corruption."
+    )]
+    pub checksum: Checksum,
+
+    #[arg(
+        long,
+        help = "Store modification time, permissions and content type of the hidden file, restored on decode."
+    )]
+    pub preserve_metadata: bool,
+
+    #[arg(
+        long,
+        help = "Content type of the hidden file, guessed from its extension with --preserve-metadata."
+    )]
+    pub content_type: Option<String>,
+
     #[command(subcommand)]
     pub algorithm: Algorithm,
 }
@@ -30,12 +82,156 @@ pub struct EncodeConfig {
 pub struct DecodeConfig {
     #[arg(short, long, help = "Path to image file holding hidden data.")]
     pub image_file: std::path::PathBuf,
+
+    #[command(flatten)]
+    pub secret: SecretConfig,
+
+    #[arg(
+        long,
+        conflicts_with = "SecretConfig",
+        help = "Path to identity file used to decrypt hidden data."
+    )]
+    pub identity: Option<std::path::PathBuf>,
+
+    #[arg(
+        long,
+        help = "Public key trusted to sign hidden data. Can be repeated, decoding fails unless the signature is valid."
+    )]
+    pub verify_key: Vec<String>,
+}
+
+/// Sources of the passphrase and keyfile used to encrypt or decrypt hidden data.
+#[derive(Debug, Args)]
+pub struct SecretConfig {
+    #[arg(
+        long,
+        group = "passphrase_source",
+        help = "Prompt for passphrase without echoing it."
+    )]
+    pub ask_passphrase: bool,
+
+    #[arg(
+        long,
+        value_name = "VAR",
+        group = "passphrase_source",
+        help = "Name of environment variable holding the passphrase."
+    )]
+    pub passphrase_env: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "FD",
+        group = "passphrase_source",
+        help = "Inherited file descriptor to read the passphrase from, e.g. 0 for standard input."
+    )]
+    pub passphrase_fd: Option<u32>,
+
+    #[arg(
+        long,
+        help = "Path to keyfile used as a second factor along with passphrase, or on its own."
+    )]
+    pub keyfile: Option<std::path::PathBuf>,
+}
+
+impl SecretConfig {
+    /// Reads the passphrase from the selected source and combines it with the keyfile, if any.
+    /// Prompting for a passphrase to encrypt with asks for it twice.
+    pub fn passphrase(&self, confirm: bool) -> Result<Option<Passphrase>> {
+        let passphrase = if self.ask_passphrase {
+            Some(prompt_passphrase(confirm)?)
+        } else if let Some(name) = &self.passphrase_env {
+            let passphrase = std::env::var(name)
+                .map_err(|_| anyhow!("Environment variable {} is not set.", name))?;
+            Some(passphrase)
+        } else if let Some(fd) = self.passphrase_fd {
+            Some(read_passphrase_fd(fd)?)
+        } else {
+            None
+        };
+
+        if passphrase.as_ref().is_some_and(String::is_empty) {
+            return Err(anyhow!("Passphrase must not be empty."));
+        }
+        let Some(keyfile) = &self.keyfile else {
+            return Ok(passphrase.map(Passphrase::new));
+        };
+        let passphrase = Passphrase::new(passphrase.unwrap_or_default());
+        let keyfile = file::read_secret(keyfile)?;
+        Ok(Some(passphrase.with_keyfile(&keyfile)))
+    }
+}
+
+fn prompt_passphrase(confirm: bool) -> Result<String> {
+    let mut passphrase = Zeroizing::new(rpassword::prompt_password("Passphrase: ")?);
+    if confirm && Zeroizing::new(rpassword::prompt_password("Confirm passphrase: ")?) != passphrase
+    {
+        return Err(anyhow!("Passphrases do not match."));
+    }
+    // Moved out without copying, so the only buffer is the one zeroized by the caller
+    Ok(std::mem::take(&mut *passphrase))
+}
+
+/// Reads the first line of an inherited file descriptor.
+#[cfg(unix)]
+fn read_passphrase_fd(fd: u32) -> Result<String> {
+    let contents = std::fs::read_to_string(format!("/dev/fd/{}", fd))
+        .map(Zeroizing::new)
+        .map_err(|error| {
+            anyhow!(
+                "Could not read passphrase from descriptor {}: {}",
+                fd,
+                error
+            )
+        })?;
+    let line = contents.lines().next().unwrap_or_default();
+    Ok(line.to_string())
+}
+
+#[cfg(not(unix))]
+fn read_passphrase_fd(_fd: u32) -> Result<String> {
+    Err(anyhow!(
+        "Reading passphrase from a file descriptor is not supported."
+    ))
+}
+
+#[derive(Debug, Args)]
+pub struct KeygenConfig {
+    #[arg(short, long, help = "Path to key file to be created.")]
+    pub output: std::path::PathBuf,
+
+    #[arg(
+        long,
+        help = "Generate Ed25519 signing key instead of X25519 identity."
+    )]
+    pub signing: bool,
 }
 
 #[derive(Debug, Subcommand)]
 pub enum Algorithm {
     Rgb(RgbAlgorithmConfig),
     Alpha,
+    /// Store data in a private PNG chunk, leaving pixels untouched.
+    Chunk,
+    /// Store data in PNG row filter types, leaving pixels untouched.
+    Filter,
+    /// Store data in the order of palette entries of indexed PNG image, leaving pixels untouched.
+    Palette,
+    /// Hide data in zero-width characters of a cover text file.
+    Text,
+    /// Store data in low-order decimal digits of SVG path and shape coordinates.
+    Svg,
+    /// Hide encrypted data in pixels without any header, with parameters derived from the passphrase.
+    Stealth,
+}
+
+/// Checksum appended to hidden data, verified before decrypting it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, ValueEnum)]
+pub enum Checksum {
+    /// Fast, detects most accidental corruption.
+    Crc32,
+    /// Slower, practically never misses corruption.
+    #[default]
+    Sha256,
 }
 
 #[derive(Debug, Args)]
@@ -1,21 +1,93 @@
 use std::{
-    fs::File,
+    fs::{self, File, OpenOptions},
     io::{Read, Write},
     path::{Path, PathBuf},
 };
 
 use anyhow::{anyhow, Ok, Result};
+use zeroize::Zeroizing;
+
+use crate::coder::metadata::Metadata;
+
+/// Only permission bits are kept, as setuid, setgid and sticky bits are not to be trusted from
+/// hidden data.
+const PERMISSION_BITS: u32 = 0o777;
+/// Leading bytes enough to tell carrier formats apart by their signatures.
+pub const SIGNATURE_LENGTH: usize = 16;
 
 pub fn read_bytes(path: &Path) -> Result<Vec<u8>> {
     let mut file = File::open(path)?;
-    let mut data = Vec::new();
+    let mut data = Vec::with_capacity(file_length(&file)?);
+    file.read_to_end(&mut data)?;
+    Ok(data)
+}
+
+/// Reads a secret into a buffer sized upfront, as growing it would leave copies in reallocated
+/// buffers, and zeroized when dropped.
+pub fn read_secret(path: &Path) -> Result<Zeroizing<Vec<u8>>> {
+    let mut file = File::open(path)?;
+    let mut data = Zeroizing::new(Vec::with_capacity(file_length(&file)?));
     file.read_to_end(&mut data)?;
     Ok(data)
 }
 
-pub fn write_bytes(path: &PathBuf, data: &[u8]) -> Result<()> {
+/// Reads at most `length` leading bytes, to check the signature without reading the whole file.
+pub fn read_prefix(path: &Path, length: usize) -> Result<Vec<u8>> {
+    let mut data = Vec::with_capacity(length);
+    File::open(path)?
+        .take(length as u64)
+        .read_to_end(&mut data)?;
+    Ok(data)
+}
+
+fn file_length(file: &File) -> Result<usize> {
+    Ok(usize::try_from(file.metadata()?.len())?)
+}
+
+/// Writes data to the file, then restores modification time and permissions from `metadata`.
+pub fn write_bytes(path: &PathBuf, data: &[u8], metadata: Option<&Metadata>) -> Result<()> {
     let mut file = File::create(path)?;
     file.write_all(data)?;
+    let Some(metadata) = metadata else {
+        return Ok(());
+    };
+    if let Some(modified) = metadata.modified {
+        file.set_modified(modified)?;
+    }
+    #[cfg(unix)]
+    if let Some(permissions) = metadata.permissions {
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(fs::Permissions::from_mode(permissions & PERMISSION_BITS))?;
+    }
+    Ok(())
+}
+
+/// Reads modification time and, on Unix, permission bits of the file.
+pub fn read_metadata(path: &Path) -> Result<Metadata> {
+    let attributes = fs::metadata(path)?;
+    #[cfg(unix)]
+    let permissions = {
+        use std::os::unix::fs::PermissionsExt;
+        Some(attributes.permissions().mode() & PERMISSION_BITS)
+    };
+    #[cfg(not(unix))]
+    let permissions = None;
+    Ok(Metadata {
+        modified: attributes.modified().ok(),
+        permissions,
+        content_type: None,
+    })
+}
+
+/// Writes a new file readable only by its owner, refusing to overwrite an existing one.
+pub fn write_private(path: &Path, data: &[u8]) -> Result<()> {
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+    let mut file = options.open(path)?;
+    file.write_all(data)?;
     Ok(())
 }
 
@@ -0,0 +1,296 @@
+use std::io::{Read, Write};
+
+use anyhow::{anyhow, Result};
+use flate2::{read::ZlibDecoder, write::ZlibEncoder, Compression};
+use png::Transformations;
+
+use crate::chunk::{self, Chunk, IDAT, IHDR, PNG_SIGNATURE};
+
+/// Number of data bits carried by the filter type of a single row.
+/// Filter types `None`, `Sub`, `Up` and `Average` are used as 2-bit symbols.
+const BITS_PER_ROW: usize = 2;
+const SYMBOL_MASK: u8 = 0b11;
+const FILTER_TYPES: [u8; 5] = [0, 1, 2, 3, 4];
+const ANIMATION_CONTROL: [u8; 4] = *b"acTL";
+
+pub(crate) struct ImageHeader {
+    pub width: usize,
+    pub height: usize,
+    pub bit_depth: u8,
+    pub color_type: u8,
+    pub bits_per_pixel: usize,
+    pub interlaced: bool,
+}
+
+impl ImageHeader {
+    fn parse(data: &[u8]) -> Result<Self> {
+        if data.len() != 13 {
+            return Err(anyhow!("Invalid IHDR chunk."));
+        }
+        let channels = match data[9] {
+            0 | 3 => 1,
+            2 => 3,
+            4 => 2,
+            6 => 4,
+            _ => return Err(anyhow!("Unknown PNG color type.")),
+        };
+        Ok(ImageHeader {
+            width: u32::from_be_bytes(data[0..4].try_into().unwrap()) as usize,
+            height: u32::from_be_bytes(data[4..8].try_into().unwrap()) as usize,
+            bit_depth: data[8],
+            color_type: data[9],
+            bits_per_pixel: channels * data[8] as usize,
+            interlaced: data[12] != 0,
+        })
+    }
+
+    fn bytes_per_pixel(&self) -> usize {
+        self.bits_per_pixel.div_ceil(8)
+    }
+
+    pub fn row_length(&self) -> usize {
+        (self.width * self.bits_per_pixel).div_ceil(8)
+    }
+}
+
+/// Number of bytes which can be stored in filter types of the image rows.
+pub fn capacity(png: &[u8]) -> Result<usize> {
+    let chunks = chunk::chunks(png)?;
+    let header = image_header(png, &chunks)?;
+    Ok(header.height * BITS_PER_ROW / 8)
+}
+
+/// Re-encodes image data choosing row filter types by data bits. Pixels are left untouched.
+pub fn write(png: &[u8], data: &[u8]) -> Result<Vec<u8>> {
+    let chunks = chunk::chunks(png)?;
+    if chunks.iter().any(|chunk| chunk.kind == ANIMATION_CONTROL) {
+        return Err(anyhow!("Filter mode does not support animated images."));
+    }
+    let header = image_header(png, &chunks)?;
+    if data.len() > header.height * BITS_PER_ROW / 8 {
+        return Err(anyhow!("Too much data to encode in row filters."));
+    }
+
+    let symbols = data.iter().flat_map(|byte| {
+        (0..8 / BITS_PER_ROW)
+            .rev()
+            .map(move |index| (byte >> (index * BITS_PER_ROW)) & SYMBOL_MASK)
+    });
+    let image_data = image_data(&raw_rows(png)?, &header, symbols)?;
+
+    let mut output = PNG_SIGNATURE.to_vec();
+    let mut image_data_written = false;
+    for chunk in chunks {
+        match chunk.kind {
+            IHDR => {
+                let mut ihdr = png[chunk.data].to_vec();
+                ihdr[12] = 0; // Rows are written without interlacing
+                chunk::write(&mut output, IHDR, &ihdr);
+            }
+            IDAT if !image_data_written => {
+                chunk::write(&mut output, IDAT, &image_data);
+                image_data_written = true;
+            }
+            IDAT => {}
+            _ => output.extend_from_slice(&png[chunk.start..chunk.end]),
+        }
+    }
+    Ok(output)
+}
+
+/// Reads data bits stored in filter types of all image rows.
+pub fn read(png: &[u8]) -> Result<Vec<u8>> {
+    let chunks = chunk::chunks(png)?;
+    let header = image_header(png, &chunks)?;
+    if header.interlaced {
+        return Err(anyhow!("Filter mode requires a non-interlaced image."));
+    }
+
+    let compressed: Vec<u8> = chunks
+        .iter()
+        .filter(|chunk| chunk.kind == IDAT)
+        .flat_map(|chunk| png[chunk.data.clone()].iter().copied())
+        .collect();
+    let mut filtered = Vec::new();
+    ZlibDecoder::new(compressed.as_slice()).read_to_end(&mut filtered)?;
+
+    let symbols: Vec<u8> = filtered
+        .chunks(header.row_length() + 1)
+        .take(header.height)
+        .map(|row| row[0] & SYMBOL_MASK)
+        .collect();
+    Ok(symbols
+        .chunks_exact(8 / BITS_PER_ROW)
+        .map(|symbols| {
+            symbols
+                .iter()
+                .fold(0, |byte, symbol| (byte << BITS_PER_ROW) | symbol)
+        })
+        .collect())
+}
+
+/// Filters and compresses raw image rows into `IDAT` data. Filter types are taken from
+/// `filter_types` as long as available, the best filter type is used for the remaining rows.
+pub(crate) fn image_data(
+    rows: &[u8],
+    header: &ImageHeader,
+    mut filter_types: impl Iterator<Item = u8>,
+) -> Result<Vec<u8>> {
+    let row_length = header.row_length();
+    let bpp = header.bytes_per_pixel();
+    let mut filtered = Vec::with_capacity((row_length + 1) * header.height);
+    let mut previous = vec![0; row_length];
+    for row in rows.chunks_exact(row_length) {
+        let filter_type = filter_types
+            .next()
+            .unwrap_or_else(|| best_filter_type(row, &previous, bpp));
+        filtered.push(filter_type);
+        filtered.extend(apply_filter(filter_type, row, &previous, bpp));
+        previous.copy_from_slice(row);
+    }
+
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(&filtered)?;
+    Ok(encoder.finish()?)
+}
+
+pub(crate) fn image_header(png: &[u8], chunks: &[Chunk]) -> Result<ImageHeader> {
+    let ihdr = chunks
+        .first()
+        .filter(|chunk| chunk.kind == IHDR)
+        .ok_or(anyhow!("Missing IHDR chunk in PNG file."))?;
+    ImageHeader::parse(&png[ihdr.data.clone()])
+}
+
+/// Decodes image rows without any transformation, deinterlaced and in original bit depth.
+pub(crate) fn raw_rows(png: &[u8]) -> Result<Vec<u8>> {
+    let mut decoder = png::Decoder::new(png);
+    decoder.set_transformations(Transformations::IDENTITY);
+    let mut reader = decoder.read_info()?;
+
+    let mut rows = vec![0; reader.output_buffer_size()];
+    reader.next_frame(&mut rows)?;
+    Ok(rows)
+}
+
+/// Picks filter type with minimal sum of absolute differences, as common PNG encoders do.
+fn best_filter_type(row: &[u8], previous: &[u8], bpp: usize) -> u8 {
+    FILTER_TYPES
+        .into_iter()
+        .min_by_key(|&filter_type| {
+            apply_filter(filter_type, row, previous, bpp)
+                .into_iter()
+                .map(|byte| (byte as i8).unsigned_abs() as usize)
+                .sum::<usize>()
+        })
+        .unwrap()
+}
+
+fn apply_filter(filter_type: u8, row: &[u8], previous: &[u8], bpp: usize) -> Vec<u8> {
+    (0..row.len())
+        .map(|index| {
+            let left = if index >= bpp { row[index - bpp] } else { 0 };
+            let up = previous[index];
+            let up_left = if index >= bpp {
+                previous[index - bpp]
+            } else {
+                0
+            };
+
+            let predicted = match filter_type {
+                0 => 0,
+                1 => left,
+                2 => up,
+                3 => ((left as u16 + up as u16) / 2) as u8,
+                _ => paeth(left, up, up_left),
+            };
+            row[index].wrapping_sub(predicted)
+        })
+        .collect()
+}
+
+fn paeth(left: u8, up: u8, up_left: u8) -> u8 {
+    let estimate = left as i16 + up as i16 - up_left as i16;
+    let distance_left = (estimate - left as i16).abs();
+    let distance_up = (estimate - up as i16).abs();
+    let distance_up_left = (estimate - up_left as i16).abs();
+
+    if distance_left <= distance_up && distance_left <= distance_up_left {
+        left
+    } else if distance_up <= distance_up_left {
+        up
+    } else {
+        up_left
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use image::{DynamicImage, ImageFormat, RgbImage};
+
+    #[test]
+    fn apply_filter() {
+        let previous = [10, 20, 30, 40];
+        let row = [15, 25, 35, 45];
+
+        assert_eq!(
+            super::apply_filter(0, &row, &previous, 2),
+            vec![15, 25, 35, 45]
+        );
+        assert_eq!(
+            super::apply_filter(1, &row, &previous, 2),
+            vec![15, 25, 20, 20]
+        );
+        assert_eq!(super::apply_filter(2, &row, &previous, 2), vec![5, 5, 5, 5]);
+        assert_eq!(
+            super::apply_filter(3, &row, &previous, 2),
+            vec![10, 15, 13, 13]
+        );
+        assert_eq!(super::apply_filter(4, &row, &previous, 2), vec![5, 5, 5, 5]);
+    }
+
+    #[test]
+    fn paeth() {
+        assert_eq!(super::paeth(10, 20, 10), 20);
+        assert_eq!(super::paeth(20, 10, 10), 20);
+        assert_eq!(super::paeth(10, 10, 20), 10);
+    }
+
+    #[test]
+    fn capacity() {
+        let png = create_png(8, 40);
+        assert_eq!(super::capacity(&png).unwrap(), 10);
+    }
+
+    #[test]
+    fn write_read() {
+        let png = create_png(8, 40);
+        let data = b"filtered!!";
+
+        let written = super::write(&png, data).unwrap();
+        assert_eq!(&super::read(&written).unwrap()[..data.len()], data);
+
+        let original = image::load_from_memory(&png).unwrap();
+        let encoded = image::load_from_memory(&written).unwrap();
+        assert_eq!(original.as_bytes(), encoded.as_bytes());
+    }
+
+    #[test]
+    fn write_too_much_data() {
+        let png = create_png(8, 40);
+        assert!(super::write(&png, &[0; 11]).is_err());
+    }
+
+    fn create_png(width: u32, height: u32) -> Vec<u8> {
+        let image = RgbImage::from_fn(width, height, |x, y| {
+            image::Rgb([(x * 30) as u8, (y * 5) as u8, (x * y) as u8])
+        });
+        let mut png = Cursor::new(Vec::new());
+        DynamicImage::ImageRgb8(image)
+            .write_to(&mut png, ImageFormat::Png)
+            .unwrap();
+        png.into_inner()
+    }
+}
@@ -0,0 +1,288 @@
+use std::path::Path;
+
+use anyhow::{anyhow, Result};
+use exr::prelude::{
+    AnyChannel, AnyChannels, Compression, Encoding, FlatImage, FlatSamples, Image, ImageAttributes,
+    IntegerBounds, Layer, LayerAttributes, SmallVec, WritableImage,
+};
+use half::f16;
+
+use crate::{coder::layout::Layout, file};
+
+const EXR_MAGIC: [u8; 4] = [0x76, 0x2F, 0x31, 0x01];
+const HDR_SIGNATURES: [&[u8]; 2] = [b"#?RADIANCE", b"#?RGBE"];
+
+/// Floating point image, kept as OpenEXR layers with their original channels and attributes.
+pub struct FloatImage {
+    image: FlatImage,
+}
+
+impl FloatImage {
+    /// Channels of all layers are coded as a single grayscale buffer, one channel after another.
+    pub fn layout(&self) -> Layout {
+        Layout::L
+    }
+
+    /// Whether data is stored in `f16` channels, otherwise in `f32` ones.
+    /// Integer channels are never used to store data.
+    pub fn is_half(&self) -> Result<bool> {
+        let mut half = false;
+        let mut full = false;
+        for channel in self.channels() {
+            match channel.sample_data {
+                FlatSamples::F16(_) => half = true,
+                FlatSamples::F32(_) => full = true,
+                FlatSamples::U32(_) => {}
+            }
+        }
+
+        match (half, full) {
+            (true, false) => Ok(true),
+            (false, true) => Ok(false),
+            (true, true) => Err(anyhow!(
+                "Images mixing f16 and f32 channels are not supported."
+            )),
+            (false, false) => Err(anyhow!("Image does not contain any float channel.")),
+        }
+    }
+
+    /// Finite samples of all channels. Mantissa bits of infinities and NaNs tell them apart, so
+    /// they are skipped and stay untouched, while storing data keeps finite samples finite.
+    pub fn samples_f16(&self) -> Vec<f16> {
+        self.channels()
+            .filter_map(|channel| match &channel.sample_data {
+                FlatSamples::F16(samples) => Some(samples.iter().copied()),
+                _ => None,
+            })
+            .flatten()
+            .filter(|sample| sample.is_finite())
+            .collect()
+    }
+
+    /// Replaces finite samples, in the order of [`FloatImage::samples_f16`].
+    pub fn set_samples_f16(&mut self, samples: &[f16]) {
+        let mut samples = samples.iter();
+        for channel in self.channels_mut() {
+            if let FlatSamples::F16(data) = &mut channel.sample_data {
+                data.iter_mut()
+                    .filter(|sample| sample.is_finite())
+                    .zip(samples.by_ref())
+                    .for_each(|(sample, new)| *sample = *new);
+            }
+        }
+    }
+
+    /// Finite samples of all channels, see [`FloatImage::samples_f16`].
+    pub fn samples_f32(&self) -> Vec<f32> {
+        self.channels()
+            .filter_map(|channel| match &channel.sample_data {
+                FlatSamples::F32(samples) => Some(samples.iter().copied()),
+                _ => None,
+            })
+            .flatten()
+            .filter(|sample| sample.is_finite())
+            .collect()
+    }
+
+    /// Replaces finite samples, in the order of [`FloatImage::samples_f32`].
+    pub fn set_samples_f32(&mut self, samples: &[f32]) {
+        let mut samples = samples.iter();
+        for channel in self.channels_mut() {
+            if let FlatSamples::F32(data) = &mut channel.sample_data {
+                data.iter_mut()
+                    .filter(|sample| sample.is_finite())
+                    .zip(samples.by_ref())
+                    .for_each(|(sample, new)| *sample = *new);
+            }
+        }
+    }
+
+    fn channels(&self) -> impl Iterator<Item = &AnyChannel<FlatSamples>> {
+        self.image
+            .layer_data
+            .iter()
+            .flat_map(|layer| layer.channel_data.list.iter())
+    }
+
+    fn channels_mut(&mut self) -> impl Iterator<Item = &mut AnyChannel<FlatSamples>> {
+        self.image
+            .layer_data
+            .iter_mut()
+            .flat_map(|layer| layer.channel_data.list.iter_mut())
+    }
+}
+
+/// Checks whether the file is a carrier for [`read`]: an OpenEXR or a Radiance HDR image.
+pub fn is_float(file: &[u8]) -> bool {
+    is_exr(file) || is_hdr(file)
+}
+
+pub fn is_exr(file: &[u8]) -> bool {
+    file.starts_with(&EXR_MAGIC)
+}
+
+fn is_hdr(file: &[u8]) -> bool {
+    HDR_SIGNATURES
+        .iter()
+        .any(|signature| file.starts_with(signature))
+}
+
+pub fn read(path: &Path) -> Result<FloatImage> {
+    let image = if is_exr(&file::read_prefix(path, file::SIGNATURE_LENGTH)?) {
+        exr::prelude::read_all_flat_layers_from_file(path)?
+    } else {
+        read_hdr(path)?
+    };
+
+    let mut image = FloatImage { image };
+    for layer in image.image.layer_data.iter_mut() {
+        // Lossy compression would destroy data stored in the least significant bits.
+        if layer.encoding.compression.may_loose_data() {
+            layer.encoding.compression = Compression::ZIP16;
+        }
+    }
+    Ok(image)
+}
+
+/// Converts Radiance HDR image to OpenEXR one with `R`, `G` and `B` channels.
+fn read_hdr(path: &Path) -> Result<FlatImage> {
+    let image = image::open(path)?.into_rgb32f();
+    let size = (image.width() as usize, image.height() as usize);
+    let pixels = image.into_raw();
+
+    let channels = ["R", "G", "B"]
+        .into_iter()
+        .enumerate()
+        .map(|(index, name)| {
+            let samples = pixels.iter().skip(index).step_by(3).copied().collect();
+            AnyChannel::new(name, FlatSamples::F32(samples))
+        })
+        .collect();
+    let layer = Layer::new(
+        size,
+        LayerAttributes::default(),
+        Encoding::FAST_LOSSLESS,
+        AnyChannels::sort(channels),
+    );
+    Ok(Image::from_layers(
+        ImageAttributes::new(IntegerBounds::from_dimensions(size)),
+        SmallVec::from_vec(vec![layer]),
+    ))
+}
+
+/// Writes the image as OpenEXR, keeping its layers, channels and attributes.
+pub fn write(path: &Path, image: &FloatImage) -> Result<()> {
+    image.image.write().to_file(path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use exr::prelude::{
+        AnyChannel, AnyChannels, FlatImage, FlatSamples, Image, ImageAttributes, IntegerBounds,
+        Layer, LayerAttributes, SmallVec,
+    };
+    use half::f16;
+
+    use crate::coder::sample::Sample;
+
+    use super::FloatImage;
+
+    #[test]
+    fn is_half() {
+        let half = create_image(vec![FlatSamples::F16(vec![]), FlatSamples::U32(vec![])]);
+        assert!(half.is_half().unwrap());
+
+        let full = create_image(vec![FlatSamples::F32(vec![])]);
+        assert!(!full.is_half().unwrap());
+
+        let mixed = create_image(vec![FlatSamples::F16(vec![]), FlatSamples::F32(vec![])]);
+        assert!(mixed.is_half().is_err());
+
+        let integer = create_image(vec![FlatSamples::U32(vec![])]);
+        assert!(integer.is_half().is_err());
+    }
+
+    #[test]
+    fn samples_f32() {
+        let mut image = create_image(vec![
+            FlatSamples::F32(vec![1.0, 2.0]),
+            FlatSamples::U32(vec![7, 8]),
+            FlatSamples::F32(vec![3.0, 4.0]),
+        ]);
+        assert_eq!(image.samples_f32(), vec![1.0, 2.0, 3.0, 4.0]);
+
+        image.set_samples_f32(&[5.0, 6.0, 7.0, 8.0]);
+        assert_eq!(image.samples_f32(), vec![5.0, 6.0, 7.0, 8.0]);
+    }
+
+    #[test]
+    fn samples_f16() {
+        let mut image = create_image(vec![FlatSamples::F16(vec![f16::ONE, f16::ZERO])]);
+        assert_eq!(image.samples_f16(), vec![f16::ONE, f16::ZERO]);
+
+        image.set_samples_f16(&[f16::ZERO, f16::ONE]);
+        assert_eq!(image.samples_f16(), vec![f16::ZERO, f16::ONE]);
+    }
+
+    #[test]
+    fn samples_skip_non_finite() {
+        let mut image = create_image(vec![
+            FlatSamples::F16(vec![f16::INFINITY, f16::ONE]),
+            FlatSamples::F16(vec![f16::NAN, f16::NEG_INFINITY]),
+        ]);
+        assert_eq!(image.samples_f16(), vec![f16::ONE]);
+
+        image.set_samples_f16(&[f16::ONE.with_low_bits(2, 0b11)]);
+        assert_eq!(
+            image
+                .channels()
+                .flat_map(|channel| match &channel.sample_data {
+                    FlatSamples::F16(samples) => samples.iter().map(|s| s.to_bits()).collect(),
+                    _ => vec![],
+                })
+                .collect::<Vec<_>>(),
+            vec![
+                f16::INFINITY.to_bits(),
+                f16::ONE.to_bits() | 0b11,
+                f16::NAN.to_bits(),
+                f16::NEG_INFINITY.to_bits()
+            ]
+        );
+
+        let mut image = create_image(vec![FlatSamples::F32(vec![f32::INFINITY, 2.0])]);
+        assert_eq!(image.samples_f32(), vec![2.0]);
+        image.set_samples_f32(&[3.0]);
+        assert!(matches!(
+            &image.channels().next().unwrap().sample_data,
+            FlatSamples::F32(samples) if samples[0] == f32::INFINITY && samples[1] == 3.0
+        ));
+    }
+
+    #[test]
+    fn is_float() {
+        assert!(super::is_float(&[0x76, 0x2F, 0x31, 0x01, 0x02]));
+        assert!(super::is_float(b"#?RADIANCE\n"));
+        assert!(!super::is_float(b"\x89PNG"));
+    }
+
+    fn create_image(samples: Vec<FlatSamples>) -> FloatImage {
+        let size = (2, 1);
+        let channels = samples
+            .into_iter()
+            .enumerate()
+            .map(|(index, samples)| AnyChannel::new(format!("C{}", index).as_str(), samples))
+            .collect();
+        let layer = Layer::new(
+            size,
+            LayerAttributes::default(),
+            Default::default(),
+            AnyChannels::sort(channels),
+        );
+        let image: FlatImage = Image::from_layers(
+            ImageAttributes::new(IntegerBounds::from_dimensions(size)),
+            SmallVec::from_vec(vec![layer]),
+        );
+        FloatImage { image }
+    }
+}
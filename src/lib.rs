@@ -1,3 +1,11 @@
+pub mod animation;
+pub mod chunk;
 pub mod coder;
 pub mod config;
 pub mod file;
+pub mod filter;
+pub mod float;
+pub mod palette;
+pub mod svg;
+pub mod text;
+pub mod y4m;
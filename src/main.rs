@@ -1,50 +1,759 @@
-use anyhow::Result;
-use image::{io::Reader as ImageReader, GenericImageView, RgbaImage};
+use anyhow::{anyhow, Result};
+use image::{DynamicImage, ImageBuffer, ImageReader, Pixel};
 use mythic_telegram::{
-    coder::{decoder, encoder},
-    config::{self, Config, DecodeConfig, EncodeConfig, Mode},
-    file,
+    animation, chunk,
+    coder::{
+        crypto::{Identity, Protection},
+        decoder::{self, Decoded},
+        encoder::{self, Envelope},
+        layout::Layout,
+        metadata::{self, Metadata},
+        sample::Sample,
+        signature::{SignatureStatus, SigningKey, VerifyingKey},
+    },
+    config::{self, Algorithm, Config, DecodeConfig, EncodeConfig, KeygenConfig, Mode},
+    file, filter, float, palette, svg, text, y4m,
 };
 use std::path::Path;
+use zeroize::Zeroizing;
 
 fn encode(config: &EncodeConfig) -> Result<()> {
     let image_path = Path::new(&config.image_file);
     let secret_file_path = Path::new(&config.secret_file);
 
+    let secret_data = file::read_secret(&config.secret_file)?;
+    let secret_filename = file::extract_file_name(secret_file_path)?;
+
+    if matches!(config.algorithm, Algorithm::Chunk) {
+        return encode_chunk(config, secret_data, secret_filename);
+    }
+    if matches!(config.algorithm, Algorithm::Filter) {
+        return encode_filter(config, secret_data, secret_filename);
+    }
+    if matches!(config.algorithm, Algorithm::Palette) {
+        return encode_palette(config, secret_data, secret_filename);
+    }
+    if matches!(config.algorithm, Algorithm::Text) {
+        return encode_text(config, secret_data, secret_filename);
+    }
+    if matches!(config.algorithm, Algorithm::Svg) {
+        return encode_svg(config, secret_data, secret_filename);
+    }
+    if is_video(image_path)? {
+        return encode_video(config, secret_data, secret_filename);
+    }
+    if is_float_image(image_path)? {
+        return encode_float_image(config, secret_data, secret_filename);
+    }
+    if animation::is_animation(image_path)? {
+        return encode_animation(config, secret_data, secret_filename);
+    }
+
     let image = ImageReader::open(image_path)?.decode()?;
-    let (image_width, image_height) = image.dimensions();
+
+    let encoded_image: DynamicImage = match image {
+        DynamicImage::ImageLuma8(buffer) => {
+            encode_image(config, buffer, secret_data, secret_filename)?.into()
+        }
+        DynamicImage::ImageLumaA8(buffer) => {
+            encode_image(config, buffer, secret_data, secret_filename)?.into()
+        }
+        DynamicImage::ImageRgb8(buffer) => {
+            encode_image(config, buffer, secret_data, secret_filename)?.into()
+        }
+        DynamicImage::ImageRgba8(buffer) => {
+            encode_image(config, buffer, secret_data, secret_filename)?.into()
+        }
+        DynamicImage::ImageLuma16(buffer) => {
+            encode_image(config, buffer, secret_data, secret_filename)?.into()
+        }
+        DynamicImage::ImageLumaA16(buffer) => {
+            encode_image(config, buffer, secret_data, secret_filename)?.into()
+        }
+        DynamicImage::ImageRgb16(buffer) => {
+            encode_image(config, buffer, secret_data, secret_filename)?.into()
+        }
+        DynamicImage::ImageRgba16(buffer) => {
+            encode_image(config, buffer, secret_data, secret_filename)?.into()
+        }
+        image => encode_image(config, image.to_rgba8(), secret_data, secret_filename)?.into(),
+    };
+
+    let image_filename = file::extract_file_name(image_path)?;
+    encoded_image.save(image_path.with_file_name(format!("encoded_{}", image_filename)))?;
+    Ok(())
+}
+
+fn encode_chunk(
+    config: &EncodeConfig,
+    secret_data: Zeroizing<Vec<u8>>,
+    secret_filename: String,
+) -> Result<()> {
+    let image_path = Path::new(&config.image_file);
+    let png = file::read_bytes(image_path)?;
+    if !chunk::is_png(&png) {
+        return Err(anyhow!("Chunk mode requires a PNG image."));
+    }
+
+    let encoded_data = encoder::encode_bytes(
+        &config.algorithm,
+        chunk::MAX_CHUNK_LENGTH,
+        secret_data,
+        secret_filename,
+        &envelope(config)?,
+    )?;
+    let encoded_png = chunk::insert(&png, chunk::DATA_CHUNK, &encoded_data)?;
+
+    let image_filename = file::extract_file_name(image_path)?;
+    file::write_bytes(
+        &image_path.with_file_name(format!("encoded_{}", image_filename)),
+        &encoded_png,
+        None,
+    )
+}
+
+fn encode_filter(
+    config: &EncodeConfig,
+    secret_data: Zeroizing<Vec<u8>>,
+    secret_filename: String,
+) -> Result<()> {
+    let image_path = Path::new(&config.image_file);
+    let png = file::read_bytes(image_path)?;
+    if !chunk::is_png(&png) {
+        return Err(anyhow!("Filter mode requires a PNG image."));
+    }
+
+    let encoded_data = encoder::encode_bytes(
+        &config.algorithm,
+        filter::capacity(&png)?,
+        secret_data,
+        secret_filename,
+        &envelope(config)?,
+    )?;
+    let encoded_png = filter::write(&png, &encoded_data)?;
+
+    let image_filename = file::extract_file_name(image_path)?;
+    file::write_bytes(
+        &image_path.with_file_name(format!("encoded_{}", image_filename)),
+        &encoded_png,
+        None,
+    )
+}
+
+fn encode_palette(
+    config: &EncodeConfig,
+    secret_data: Zeroizing<Vec<u8>>,
+    secret_filename: String,
+) -> Result<()> {
+    let image_path = Path::new(&config.image_file);
+    let png = file::read_bytes(image_path)?;
+    if !chunk::is_png(&png) {
+        return Err(anyhow!(
+            "Palette mode requires a PNG image, GIF color tables are not supported."
+        ));
+    }
+
+    let encoded_data = encoder::encode_bytes(
+        &config.algorithm,
+        palette::capacity(&png)?,
+        secret_data,
+        secret_filename,
+        &envelope(config)?,
+    )?;
+    let encoded_png = palette::write(&png, &encoded_data)?;
+
+    let image_filename = file::extract_file_name(image_path)?;
+    file::write_bytes(
+        &image_path.with_file_name(format!("encoded_{}", image_filename)),
+        &encoded_png,
+        None,
+    )
+}
+
+fn encode_text(
+    config: &EncodeConfig,
+    secret_data: Zeroizing<Vec<u8>>,
+    secret_filename: String,
+) -> Result<()> {
+    let cover_path = Path::new(&config.image_file);
+    let cover = String::from_utf8(file::read_bytes(cover_path)?)
+        .map_err(|_| anyhow!("Text mode requires a UTF-8 cover text file."))?;
+
+    let encoded_data = encoder::encode_bytes(
+        &config.algorithm,
+        text::CAPACITY,
+        secret_data,
+        secret_filename,
+        &envelope(config)?,
+    )?;
+    let encoded_text = text::write(&cover, &encoded_data)?;
+
+    let cover_filename = file::extract_file_name(cover_path)?;
+    file::write_bytes(
+        &cover_path.with_file_name(format!("encoded_{}", cover_filename)),
+        encoded_text.as_bytes(),
+        None,
+    )
+}
+
+fn encode_svg(
+    config: &EncodeConfig,
+    secret_data: Zeroizing<Vec<u8>>,
+    secret_filename: String,
+) -> Result<()> {
+    let image_path = Path::new(&config.image_file);
+    let image = file::read_bytes(image_path)?;
+    if !svg::is_svg(&image) {
+        return Err(anyhow!("Svg mode requires an SVG image."));
+    }
+    let image = String::from_utf8(image)?;
+
+    let encoded_data = encoder::encode_bytes(
+        &config.algorithm,
+        svg::capacity(&image),
+        secret_data,
+        secret_filename,
+        &envelope(config)?,
+    )?;
+    let encoded_image = svg::write(&image, &encoded_data)?;
+
+    let image_filename = file::extract_file_name(image_path)?;
+    file::write_bytes(
+        &image_path.with_file_name(format!("encoded_{}", image_filename)),
+        encoded_image.as_bytes(),
+        None,
+    )
+}
+
+fn encode_video(
+    config: &EncodeConfig,
+    secret_data: Zeroizing<Vec<u8>>,
+    secret_filename: String,
+) -> Result<()> {
+    let image_path = Path::new(&config.image_file);
+    let mut video = y4m::parse(&file::read_bytes(image_path)?)?;
 
     let encoded_data = encoder::encode(
         &config.algorithm,
-        image.to_rgba8().into_vec(),
-        file::read_bytes(&config.secret_file)?,
-        file::extract_file_name(secret_file_path)?,
+        video.layout(),
+        video.samples(),
+        secret_data,
+        secret_filename,
+        &envelope(config)?,
     )?;
-    let encoded_image = RgbaImage::from_vec(image_width, image_height, encoded_data).unwrap();
+    video.set_samples(&encoded_data);
 
     let image_filename = file::extract_file_name(image_path)?;
-    encoded_image.save(image_path.with_file_name(format!("encoded_{}", image_filename)))?;
-    Ok(())
+    file::write_bytes(
+        &image_path.with_file_name(format!("encoded_{}", image_filename)),
+        &video.to_bytes(),
+        None,
+    )
+}
+
+fn encode_float_image(
+    config: &EncodeConfig,
+    secret_data: Zeroizing<Vec<u8>>,
+    secret_filename: String,
+) -> Result<()> {
+    let image_path = Path::new(&config.image_file);
+    let mut image = float::read(image_path)?;
+    let layout = image.layout();
+
+    if image.is_half()? {
+        let encoded_data = encoder::encode(
+            &config.algorithm,
+            layout,
+            image.samples_f16(),
+            secret_data,
+            secret_filename,
+            &envelope(config)?,
+        )?;
+        image.set_samples_f16(&encoded_data);
+    } else {
+        let encoded_data = encoder::encode(
+            &config.algorithm,
+            layout,
+            image.samples_f32(),
+            secret_data,
+            secret_filename,
+            &envelope(config)?,
+        )?;
+        image.set_samples_f32(&encoded_data);
+    }
+
+    // Float images are always written back as OpenEXR.
+    let image_filename = file::extract_file_name(&image_path.with_extension("exr"))?;
+    float::write(
+        &image_path.with_file_name(format!("encoded_{}", image_filename)),
+        &image,
+    )
+}
+
+fn encode_animation(
+    config: &EncodeConfig,
+    secret_data: Zeroizing<Vec<u8>>,
+    secret_filename: String,
+) -> Result<()> {
+    let image_path = Path::new(&config.image_file);
+    let mut animation = animation::read(image_path)?;
+    let layout = animation.layout();
+
+    if animation.is_16bit() {
+        let encoded_data = encoder::encode(
+            &config.algorithm,
+            layout,
+            animation.samples_u16(),
+            secret_data,
+            secret_filename,
+            &envelope(config)?,
+        )?;
+        animation.set_samples_u16(&encoded_data);
+    } else {
+        let encoded_data = encoder::encode(
+            &config.algorithm,
+            layout,
+            animation.samples(),
+            secret_data,
+            secret_filename,
+            &envelope(config)?,
+        )?;
+        animation.set_samples(&encoded_data);
+    }
+
+    // Animations are always written back as lossless APNG.
+    let image_filename = file::extract_file_name(&image_path.with_extension("png"))?;
+    animation::write(
+        &image_path.with_file_name(format!("encoded_{}", image_filename)),
+        &animation,
+    )
+}
+
+fn encode_image<P>(
+    config: &EncodeConfig,
+    image: ImageBuffer<P, Vec<P::Subpixel>>,
+    secret_data: Zeroizing<Vec<u8>>,
+    secret_filename: String,
+) -> Result<ImageBuffer<P, Vec<P::Subpixel>>>
+where
+    P: Pixel,
+    P::Subpixel: Sample,
+{
+    let (image_width, image_height) = image.dimensions();
+    let encoded_data = encoder::encode(
+        &config.algorithm,
+        layout::<P>(),
+        image.into_raw(),
+        secret_data,
+        secret_filename,
+        &envelope(config)?,
+    )?;
+    Ok(ImageBuffer::from_raw(image_width, image_height, encoded_data).unwrap())
 }
 
 fn decode(config: &DecodeConfig) -> Result<()> {
     let image_path = Path::new(&config.image_file);
+    let protection = &decode_protection(config)?;
+    let verifying_keys = &verifying_keys(config)?;
+
+    let contents = file::read_bytes(image_path)?;
+    let decoded = match decode_contents(&contents, protection, verifying_keys)? {
+        Some(decoded) => decoded,
+        None => {
+            // Image decoders read the file on their own
+            drop(contents);
+            if is_float_image(image_path)? {
+                decode_float_image(image_path, protection, verifying_keys)?
+            } else if animation::is_animation(image_path)? {
+                decode_animation(image_path, protection, verifying_keys)?
+            } else {
+                decode_still_image(image_path, protection, verifying_keys)?
+            }
+        }
+    };
+
+    println!("Signature: {}", decoded.signature);
+    // Data is not trusted when a verifying key was asked for, but the signature is not valid.
+    if !verifying_keys.is_empty() && decoded.signature != SignatureStatus::Valid {
+        return Err(anyhow!(
+            "Signature check failed: data is {}.",
+            decoded.signature
+        ));
+    }
+    if let Some(content_type) = &decoded.metadata.content_type {
+        println!("Content type: {}", content_type);
+    }
+    let secret_file_path = image_path.with_file_name(&*decoded.file_name);
+    file::write_bytes(&secret_file_path, &decoded.data, Some(&decoded.metadata))
+}
+
+/// Decodes carriers parsed from the file contents read once, returning `None` for images left to
+/// image decoders.
+fn decode_contents(
+    contents: &[u8],
+    protection: &Protection,
+    verifying_keys: &[VerifyingKey],
+) -> Result<Option<Decoded>> {
+    if let Some(encoded_data) = find_data_chunk(contents) {
+        return decoder::decode_bytes(&encoded_data, protection, verifying_keys).map(Some);
+    }
+    if let Some(decoded) = decode_svg(contents, protection, verifying_keys)? {
+        return Ok(Some(decoded));
+    }
+    if let Some(decoded) = decode_text(contents, protection, verifying_keys)? {
+        return Ok(Some(decoded));
+    }
+    if let Some(decoded) = decode_filter(contents, protection, verifying_keys)? {
+        return Ok(Some(decoded));
+    }
+    if let Some(decoded) = decode_palette(contents, protection, verifying_keys)? {
+        return Ok(Some(decoded));
+    }
+    if y4m::is_y4m(contents) {
+        return decode_video(contents, protection, verifying_keys).map(Some);
+    }
+    Ok(None)
+}
+
+/// A PNG that cannot be parsed chunk by chunk may still decode as an image, thus parsing errors
+/// mean there is no data chunk rather than failing the decoding.
+fn find_data_chunk(image: &[u8]) -> Option<Vec<u8>> {
+    if !chunk::is_png(image) {
+        return None;
+    }
+    chunk::find(image, chunk::DATA_CHUNK).unwrap_or(None)
+}
+
+/// Coordinates of any SVG can be read as data, thus a missing header signature means there is no data
+/// hidden in them.
+fn decode_svg(
+    image: &[u8],
+    protection: &Protection,
+    verifying_keys: &[VerifyingKey],
+) -> Result<Option<Decoded>> {
+    if !svg::is_svg(image) {
+        return Ok(None);
+    }
+    let Ok(svg) = std::str::from_utf8(image) else {
+        return Ok(None);
+    };
+    probe(decoder::decode_bytes(
+        &svg::read(svg),
+        protection,
+        verifying_keys,
+    ))
+}
+
+/// Any text file may be a cover text, thus a missing header signature means there is no data
+/// hidden in it.
+fn decode_text(
+    cover: &[u8],
+    protection: &Protection,
+    verifying_keys: &[VerifyingKey],
+) -> Result<Option<Decoded>> {
+    let Ok(cover) = std::str::from_utf8(cover) else {
+        return Ok(None);
+    };
+    let Ok(encoded_data) = text::read(cover) else {
+        return Ok(None);
+    };
+    probe(decoder::decode_bytes(
+        &encoded_data,
+        protection,
+        verifying_keys,
+    ))
+}
+
+/// Row filters of any PNG can be read as data, thus a missing header signature means there is no data
+/// hidden in them.
+fn decode_filter(
+    image: &[u8],
+    protection: &Protection,
+    verifying_keys: &[VerifyingKey],
+) -> Result<Option<Decoded>> {
+    if !chunk::is_png(image) {
+        return Ok(None);
+    }
+    let Ok(encoded_data) = filter::read(image) else {
+        return Ok(None);
+    };
+    probe(decoder::decode_bytes(
+        &encoded_data,
+        protection,
+        verifying_keys,
+    ))
+}
+
+/// Order of any palette can be read as data, thus a missing header signature means there is no data
+/// hidden in it.
+fn decode_palette(
+    image: &[u8],
+    protection: &Protection,
+    verifying_keys: &[VerifyingKey],
+) -> Result<Option<Decoded>> {
+    if !chunk::is_png(image) || !palette::is_indexed(image).unwrap_or(false) {
+        return Ok(None);
+    }
+    let Ok(encoded_data) = palette::read(image) else {
+        return Ok(None);
+    };
+    probe(decoder::decode_bytes(
+        &encoded_data,
+        protection,
+        verifying_keys,
+    ))
+}
+
+/// Turns a probe without header signature into no data. Once the signature matched, data is there,
+/// so corruption, decryption and other failures are reported.
+fn probe(decoded: Result<Decoded>) -> Result<Option<Decoded>> {
+    match decoded {
+        Ok(decoded) => Ok(Some(decoded)),
+        Err(error) if decoder::is_no_payload(&error) => Ok(None),
+        Err(error) => Err(error),
+    }
+}
+
+fn decode_video(
+    video: &[u8],
+    protection: &Protection,
+    verifying_keys: &[VerifyingKey],
+) -> Result<Decoded> {
+    let video = y4m::parse(video)?;
+    decoder::decode(video.layout(), video.samples(), protection, verifying_keys)
+}
+
+fn decode_float_image(
+    image_path: &Path,
+    protection: &Protection,
+    verifying_keys: &[VerifyingKey],
+) -> Result<Decoded> {
+    let image = float::read(image_path)?;
+    if image.is_half()? {
+        decoder::decode(
+            image.layout(),
+            image.samples_f16(),
+            protection,
+            verifying_keys,
+        )
+    } else {
+        decoder::decode(
+            image.layout(),
+            image.samples_f32(),
+            protection,
+            verifying_keys,
+        )
+    }
+}
+
+fn decode_animation(
+    image_path: &Path,
+    protection: &Protection,
+    verifying_keys: &[VerifyingKey],
+) -> Result<Decoded> {
+    let animation = animation::read(image_path)?;
+    if animation.is_16bit() {
+        decoder::decode(
+            animation.layout(),
+            animation.samples_u16(),
+            protection,
+            verifying_keys,
+        )
+    } else {
+        decoder::decode(
+            animation.layout(),
+            animation.samples(),
+            protection,
+            verifying_keys,
+        )
+    }
+}
+
+fn decode_still_image(
+    image_path: &Path,
+    protection: &Protection,
+    verifying_keys: &[VerifyingKey],
+) -> Result<Decoded> {
     let image = ImageReader::open(image_path)?.decode()?;
-    let image_data = image.to_rgba8().into_vec();
 
-    let (file_name, decoded_data) = decoder::decode(image_data)?;
-    let secret_file_path = image_path.with_file_name(file_name);
-    file::write_bytes(&secret_file_path, &decoded_data)
+    let decoded = match image {
+        DynamicImage::ImageLuma8(buffer) => decode_image(buffer, protection, verifying_keys)?,
+        DynamicImage::ImageLumaA8(buffer) => decode_image(buffer, protection, verifying_keys)?,
+        DynamicImage::ImageRgb8(buffer) => decode_image(buffer, protection, verifying_keys)?,
+        DynamicImage::ImageRgba8(buffer) => decode_image(buffer, protection, verifying_keys)?,
+        DynamicImage::ImageLuma16(buffer) => decode_image(buffer, protection, verifying_keys)?,
+        DynamicImage::ImageLumaA16(buffer) => decode_image(buffer, protection, verifying_keys)?,
+        DynamicImage::ImageRgb16(buffer) => decode_image(buffer, protection, verifying_keys)?,
+        DynamicImage::ImageRgba16(buffer) => decode_image(buffer, protection, verifying_keys)?,
+        image => decode_image(image.to_rgba8(), protection, verifying_keys)?,
+    };
+    Ok(decoded)
+}
+
+fn decode_image<P>(
+    image: ImageBuffer<P, Vec<P::Subpixel>>,
+    protection: &Protection,
+    verifying_keys: &[VerifyingKey],
+) -> Result<Decoded>
+where
+    P: Pixel,
+    P::Subpixel: Sample,
+{
+    decoder::decode(layout::<P>(), image.into_raw(), protection, verifying_keys)
+}
+
+fn envelope(config: &EncodeConfig) -> Result<Envelope> {
+    Ok(Envelope {
+        protection: encode_protection(config)?,
+        signing_key: signing_key(config)?,
+        seal_record: config.seal_record,
+        pad: config.pad,
+        checksum: config.checksum,
+        metadata: secret_metadata(config)?,
+    })
+}
+
+/// Attributes of the secret file requested to be stored. An explicit content type is stored even
+/// without other attributes.
+fn secret_metadata(config: &EncodeConfig) -> Result<Metadata> {
+    let mut metadata = if config.preserve_metadata {
+        file::read_metadata(&config.secret_file)?
+    } else {
+        Metadata::default()
+    };
+    metadata.content_type = config.content_type.clone().or_else(|| {
+        config
+            .preserve_metadata
+            .then(|| file::extract_file_name(&config.secret_file).ok())
+            .flatten()
+            .and_then(|file_name| metadata::guess_content_type(&file_name))
+    });
+    Ok(metadata)
+}
+
+fn encode_protection(config: &EncodeConfig) -> Result<Protection> {
+    if !config.recipient.is_empty() {
+        let recipients = config
+            .recipient
+            .iter()
+            .map(|recipient| recipient.parse())
+            .collect::<Result<_>>()?;
+        return Ok(Protection::Recipients(recipients));
+    }
+    Ok(config
+        .secret
+        .passphrase(true)?
+        .map_or(Protection::None, Protection::Passphrase))
+}
+
+fn decode_protection(config: &DecodeConfig) -> Result<Protection> {
+    if let Some(identity_path) = &config.identity {
+        let identity = String::from_utf8(file::read_bytes(identity_path)?)?;
+        return Ok(Protection::Identity(identity.parse()?));
+    }
+    Ok(config
+        .secret
+        .passphrase(false)?
+        .map_or(Protection::None, Protection::Passphrase))
+}
+
+fn signing_key(config: &EncodeConfig) -> Result<Option<SigningKey>> {
+    config
+        .sign_key
+        .as_ref()
+        .map(|path| String::from_utf8(file::read_bytes(path)?)?.parse())
+        .transpose()
+}
+
+fn verifying_keys(config: &DecodeConfig) -> Result<Vec<VerifyingKey>> {
+    config.verify_key.iter().map(|key| key.parse()).collect()
+}
+
+fn keygen(config: &KeygenConfig) -> Result<()> {
+    if config.signing {
+        let signing_key = SigningKey::generate();
+        let verifying_key = signing_key.verifying_key();
+        let contents = format!("# verifying key: {}\n{}\n", verifying_key, signing_key);
+        file::write_private(&config.output, contents.as_bytes())?;
+        println!("Verifying key: {}", verifying_key);
+        return Ok(());
+    }
+
+    let identity = Identity::generate();
+    let recipient = identity.recipient();
+    let contents = format!("# public key: {}\n{}\n", recipient, identity);
+    file::write_private(&config.output, contents.as_bytes())?;
+    println!("Public key: {}", recipient);
+    Ok(())
+}
+
+fn is_video(path: &Path) -> Result<bool> {
+    Ok(y4m::is_y4m(&file::read_prefix(
+        path,
+        file::SIGNATURE_LENGTH,
+    )?))
+}
+
+fn is_float_image(path: &Path) -> Result<bool> {
+    Ok(float::is_float(&file::read_prefix(
+        path,
+        file::SIGNATURE_LENGTH,
+    )?))
+}
+
+fn layout<P: Pixel>() -> Layout {
+    Layout::from_channel_count(P::CHANNEL_COUNT).unwrap()
 }
 
 fn run(config: Config) -> Result<()> {
     match config.mode {
         Mode::Encode(encode_cfg) => encode(&encode_cfg)?,
         Mode::Decode(decode_cfg) => decode(&decode_cfg)?,
+        Mode::Keygen(keygen_cfg) => keygen(&keygen_cfg)?,
     }
     Ok(())
 }
 
+/// Keeps secrets held in memory out of core dumps.
+#[cfg(unix)]
+fn disable_core_dumps() {
+    let limit = libc::rlimit {
+        rlim_cur: 0,
+        rlim_max: 0,
+    };
+    // Best effort: failing to set the limit leaves the default behavior.
+    unsafe {
+        libc::setrlimit(libc::RLIMIT_CORE, &limit);
+        #[cfg(target_os = "linux")]
+        libc::prctl(libc::PR_SET_DUMPABLE, 0);
+    }
+}
+
+#[cfg(not(unix))]
+fn disable_core_dumps() {}
+
+/// Keeps secrets held in memory from being paged out to swap, by locking all current and future
+/// pages. Only done when the locked memory limit can be lifted, as with a finite limit every
+/// allocation beyond it would fail, e.g. when reading a large carrier.
+#[cfg(unix)]
+fn lock_memory() {
+    let unlimited = libc::rlimit {
+        rlim_cur: libc::RLIM_INFINITY,
+        rlim_max: libc::RLIM_INFINITY,
+    };
+    // Best effort: failing to lock leaves pages swappable.
+    unsafe {
+        if libc::setrlimit(libc::RLIMIT_MEMLOCK, &unlimited) == 0 {
+            libc::mlockall(libc::MCL_CURRENT | libc::MCL_FUTURE);
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn lock_memory() {}
+
 fn main() -> Result<()> {
+    disable_core_dumps();
+    lock_memory();
     run(config::parse())
 }
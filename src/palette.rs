@@ -0,0 +1,289 @@
+use anyhow::{anyhow, Result};
+
+use crate::{
+    chunk::{self, Chunk, IDAT, IHDR, PNG_SIGNATURE},
+    filter::{self, ImageHeader},
+};
+
+const PLTE: [u8; 4] = *b"PLTE";
+const TRNS: [u8; 4] = *b"tRNS";
+const INDEXED_COLOR_TYPE: u8 = 3;
+
+/// Palette entry as RGBA, alpha taken from `tRNS` chunk (opaque if missing).
+type Entry = [u8; 4];
+
+struct Palette {
+    entries: Vec<Entry>,
+    has_transparency: bool,
+}
+
+impl Palette {
+    fn parse(png: &[u8], chunks: &[Chunk]) -> Result<Self> {
+        let plte = find(png, chunks, PLTE).ok_or(anyhow!("Missing PLTE chunk in PNG file."))?;
+        let trns = find(png, chunks, TRNS);
+
+        let entries: Vec<Entry> = plte
+            .chunks_exact(3)
+            .enumerate()
+            .map(|(index, rgb)| {
+                let alpha = trns.and_then(|trns| trns.get(index)).copied();
+                [rgb[0], rgb[1], rgb[2], alpha.unwrap_or(u8::MAX)]
+            })
+            .collect();
+
+        let mut sorted = entries.clone();
+        sorted.sort();
+        sorted.dedup();
+        if sorted.len() != entries.len() {
+            return Err(anyhow!("Palette contains duplicated colors."));
+        }
+
+        Ok(Palette {
+            entries,
+            has_transparency: trns.is_some(),
+        })
+    }
+
+    /// Canonical order of entries which every permutation is relative to.
+    fn canonical(&self) -> Vec<Entry> {
+        let mut canonical = self.entries.clone();
+        canonical.sort();
+        canonical
+    }
+
+    fn plte(&self) -> Vec<u8> {
+        self.entries
+            .iter()
+            .flat_map(|entry| entry[..3].iter().copied())
+            .collect()
+    }
+
+    fn trns(&self) -> Vec<u8> {
+        self.entries.iter().map(|entry| entry[3]).collect()
+    }
+}
+
+/// Number of bytes which can be stored in the palette order: `floor(log2(n!) / 8)`.
+pub fn capacity(png: &[u8]) -> Result<usize> {
+    let chunks = indexed_chunks(png)?;
+    let palette = Palette::parse(png, &chunks)?;
+    Ok(capacity_of(palette.entries.len()))
+}
+
+/// Permutes palette entries to represent data and remaps pixel indices accordingly.
+pub fn write(png: &[u8], data: &[u8]) -> Result<Vec<u8>> {
+    let chunks = indexed_chunks(png)?;
+    let header = filter::image_header(png, &chunks)?;
+    let palette = Palette::parse(png, &chunks)?;
+
+    let capacity = capacity_of(palette.entries.len());
+    if data.len() > capacity {
+        return Err(anyhow!("Too much data to encode in palette order."));
+    }
+    let mut number = data.to_vec();
+    number.resize(capacity, 0);
+
+    // Lehmer code: each position picks one of the remaining entries in canonical order.
+    let mut remaining = palette.canonical();
+    let mut permuted = Palette {
+        entries: Vec::with_capacity(remaining.len()),
+        has_transparency: palette.has_transparency,
+    };
+    while !remaining.is_empty() {
+        let digit = div_rem(&mut number, remaining.len() as u32);
+        permuted.entries.push(remaining.remove(digit as usize));
+    }
+
+    let remap: Vec<u8> = palette
+        .entries
+        .iter()
+        .map(|entry| permuted.entries.iter().position(|e| e == entry).unwrap() as u8)
+        .collect();
+    let mut rows = filter::raw_rows(png)?;
+    remap_indices(&mut rows, &header, &remap);
+    let image_data = filter::image_data(&rows, &header, std::iter::empty())?;
+
+    let mut output = PNG_SIGNATURE.to_vec();
+    let mut image_data_written = false;
+    for chunk in chunks {
+        match chunk.kind {
+            IHDR => {
+                let mut ihdr = png[chunk.data].to_vec();
+                ihdr[12] = 0; // Rows are written without interlacing
+                chunk::write(&mut output, IHDR, &ihdr);
+            }
+            PLTE => {
+                chunk::write(&mut output, PLTE, &permuted.plte());
+                if permuted.has_transparency {
+                    chunk::write(&mut output, TRNS, &permuted.trns());
+                }
+            }
+            TRNS => {}
+            IDAT if !image_data_written => {
+                chunk::write(&mut output, IDAT, &image_data);
+                image_data_written = true;
+            }
+            IDAT => {}
+            _ => output.extend_from_slice(&png[chunk.start..chunk.end]),
+        }
+    }
+    Ok(output)
+}
+
+/// Reads data from the order of palette entries relative to their canonical order.
+pub fn read(png: &[u8]) -> Result<Vec<u8>> {
+    let chunks = indexed_chunks(png)?;
+    let palette = Palette::parse(png, &chunks)?;
+
+    let mut remaining = palette.canonical();
+    let mut digits = Vec::with_capacity(remaining.len());
+    for entry in &palette.entries {
+        let digit = remaining.iter().position(|e| e == entry).unwrap();
+        digits.push((digit as u32, remaining.len() as u32));
+        remaining.remove(digit);
+    }
+
+    let mut number = vec![0; capacity_of(palette.entries.len())];
+    for (digit, radix) in digits.into_iter().rev() {
+        mul_add(&mut number, radix, digit);
+    }
+    Ok(number)
+}
+
+pub fn is_indexed(png: &[u8]) -> Result<bool> {
+    let chunks = chunk::chunks(png)?;
+    Ok(filter::image_header(png, &chunks)?.color_type == INDEXED_COLOR_TYPE)
+}
+
+fn indexed_chunks(png: &[u8]) -> Result<Vec<Chunk>> {
+    let chunks = chunk::chunks(png)?;
+    if filter::image_header(png, &chunks)?.color_type != INDEXED_COLOR_TYPE {
+        return Err(anyhow!("Palette mode requires an indexed color image."));
+    }
+    Ok(chunks)
+}
+
+fn find<'a>(png: &'a [u8], chunks: &[Chunk], kind: [u8; 4]) -> Option<&'a [u8]> {
+    chunks
+        .iter()
+        .find(|chunk| chunk.kind == kind)
+        .map(|chunk| &png[chunk.data.clone()])
+}
+
+fn capacity_of(entries: usize) -> usize {
+    let bits: f64 = (2..=entries).map(|k| (k as f64).log2()).sum();
+    // Stay below the exact value in case of floating point rounding up
+    ((bits - 1e-9).max(0.0) as usize) / 8
+}
+
+fn remap_indices(rows: &mut [u8], header: &ImageHeader, remap: &[u8]) {
+    let depth = header.bit_depth as usize;
+    let mask = ((1u16 << depth) - 1) as u8;
+
+    for row in rows.chunks_exact_mut(header.row_length()) {
+        for pixel in 0..header.width {
+            let byte = pixel * depth / 8;
+            let shift = 8 - depth - (pixel * depth) % 8;
+            let index = (row[byte] >> shift) & mask;
+            let remapped = remap.get(index as usize).copied().unwrap_or(index);
+            row[byte] = (row[byte] & !(mask << shift)) | (remapped << shift);
+        }
+    }
+}
+
+/// Divides big-endian number in place, returning the remainder.
+fn div_rem(number: &mut [u8], divisor: u32) -> u32 {
+    let mut remainder = 0;
+    for byte in number.iter_mut() {
+        let current = (remainder << 8) | *byte as u32;
+        *byte = (current / divisor) as u8;
+        remainder = current % divisor;
+    }
+    remainder
+}
+
+/// Computes `number * factor + addend` on big-endian number in place.
+fn mul_add(number: &mut [u8], factor: u32, addend: u32) {
+    let mut carry = addend;
+    for byte in number.iter_mut().rev() {
+        let current = *byte as u32 * factor + carry;
+        *byte = current as u8;
+        carry = current >> 8;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use image::{codecs::png::PngEncoder, ExtendedColorType, ImageEncoder};
+
+    #[test]
+    fn capacity_of() {
+        assert_eq!(super::capacity_of(1), 0);
+        assert_eq!(super::capacity_of(8), 1); // log2(8!) = 15.3
+        assert_eq!(super::capacity_of(16), 5); // log2(16!) = 44.3
+        assert_eq!(super::capacity_of(256), 210); // log2(256!) = 1683.9
+    }
+
+    #[test]
+    fn div_rem_mul_add() {
+        let mut number = vec![0x01, 0x00];
+        assert_eq!(super::div_rem(&mut number, 10), 6);
+        assert_eq!(number, vec![0x00, 25]);
+
+        super::mul_add(&mut number, 10, 6);
+        assert_eq!(number, vec![0x01, 0x00]);
+    }
+
+    #[test]
+    fn write_read() {
+        let png = create_indexed_png(16);
+        let data = b"perm!";
+
+        let written = super::write(&png, data).unwrap();
+        assert_eq!(&super::read(&written).unwrap()[..data.len()], data);
+
+        let original = image::load_from_memory(&png).unwrap().to_rgba8();
+        let encoded = image::load_from_memory(&written).unwrap().to_rgba8();
+        assert_eq!(original, encoded);
+    }
+
+    #[test]
+    fn write_too_much_data() {
+        let png = create_indexed_png(16);
+        assert!(super::write(&png, &[0; 6]).is_err());
+    }
+
+    #[test]
+    fn not_indexed() {
+        let mut png = Cursor::new(Vec::new());
+        PngEncoder::new(&mut png)
+            .write_image(&[0; 12], 2, 2, ExtendedColorType::Rgb8)
+            .unwrap();
+        assert!(!super::is_indexed(png.get_ref()).unwrap());
+        assert!(super::capacity(png.get_ref()).is_err());
+    }
+
+    fn create_indexed_png(colors: u8) -> Vec<u8> {
+        let (width, height) = (8, 4);
+        let mut output = Vec::new();
+        let mut encoder = png::Encoder::new(&mut output, width, height);
+        encoder.set_color(png::ColorType::Indexed);
+        encoder.set_depth(png::BitDepth::Four);
+        encoder.set_palette(
+            (0..colors)
+                .flat_map(|color| [color * 16, 255 - color * 16, color])
+                .collect::<Vec<u8>>(),
+        );
+        encoder.set_trns((0..colors).map(|color| 255 - color).collect::<Vec<u8>>());
+
+        let mut writer = encoder.write_header().unwrap();
+        let indices: Vec<u8> = (0..width * height / 2)
+            .map(|pixel| (((pixel * 2) % 16) << 4 | ((pixel * 2 + 1) % 16)) as u8)
+            .collect();
+        writer.write_image_data(&indices).unwrap();
+        writer.finish().unwrap();
+        output
+    }
+}
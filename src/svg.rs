@@ -0,0 +1,221 @@
+use anyhow::{anyhow, Result};
+
+/// Attributes holding path and shape coordinates.
+const COORDINATE_ATTRIBUTES: [&str; 15] = [
+    "d", "points", "x", "y", "x1", "y1", "x2", "y2", "cx", "cy", "r", "rx", "ry", "width", "height",
+];
+
+/// Minimal number of fractional digits for a coordinate to carry data. Changing the last
+/// of them moves the point by at most `0.001` of a user unit, which is below visual precision.
+const MIN_FRACTION_DIGITS: usize = 3;
+
+pub fn is_svg(file: &[u8]) -> bool {
+    std::str::from_utf8(file).is_ok_and(|svg| svg.contains("<svg"))
+}
+
+/// Number of bytes which can be stored in coordinates: one bit per precise enough coordinate.
+pub fn capacity(svg: &str) -> usize {
+    carrier_digits(svg).len() / 8
+}
+
+/// Stores data bits in the parity of the last fractional digit of coordinates.
+/// All the other bytes of the document are left untouched.
+pub fn write(svg: &str, data: &[u8]) -> Result<String> {
+    let digits = carrier_digits(svg);
+    if data.len() > digits.len() / 8 {
+        return Err(anyhow!("Too much data to encode in SVG coordinates."));
+    }
+
+    let bits = data
+        .iter()
+        .flat_map(|byte| (0..8).rev().map(move |index| (byte >> index) & 1));
+    let mut output = svg.as_bytes().to_vec();
+    for (position, bit) in digits.into_iter().zip(bits) {
+        let digit = output[position] - b'0';
+        if digit % 2 != bit {
+            // Flipping the lowest bit keeps the digit within 0-9.
+            output[position] = b'0' + (digit ^ 1);
+        }
+    }
+    Ok(String::from_utf8(output)?)
+}
+
+/// Reads data bits from the parity of the last fractional digit of coordinates.
+pub fn read(svg: &str) -> Vec<u8> {
+    let bytes = svg.as_bytes();
+    carrier_digits(svg)
+        .chunks_exact(8)
+        .map(|positions| {
+            positions.iter().fold(0, |byte, &position| {
+                (byte << 1) | ((bytes[position] - b'0') % 2)
+            })
+        })
+        .collect()
+}
+
+/// Byte positions of the last fractional digit of every coordinate which can carry data.
+fn carrier_digits(svg: &str) -> Vec<usize> {
+    let bytes = svg.as_bytes();
+    let mut digits = Vec::new();
+    let mut index = 0;
+    while let Some(offset) = bytes[index..].iter().position(|&byte| byte == b'<') {
+        let start = index + offset;
+        let rest = &svg[start..];
+        let end = if rest.starts_with("<!--") {
+            find_end(svg, start, "-->")
+        } else if rest.starts_with("<![CDATA[") {
+            find_end(svg, start, "]]>")
+        } else if rest.starts_with("<?") {
+            find_end(svg, start, "?>")
+        } else if rest.starts_with("<!") || rest.starts_with("</") {
+            find_end(svg, start, ">")
+        } else {
+            tag_coordinates(bytes, start, &mut digits)
+        };
+        index = end;
+    }
+    digits
+}
+
+fn find_end(svg: &str, start: usize, terminator: &str) -> usize {
+    svg[start..]
+        .find(terminator)
+        .map_or(svg.len(), |offset| start + offset + terminator.len())
+}
+
+/// Walks over attributes of the tag starting at `start`, returning the position after the tag.
+fn tag_coordinates(bytes: &[u8], start: usize, digits: &mut Vec<usize>) -> usize {
+    let mut index = start + 1;
+    while index < bytes.len() && bytes[index] != b'>' {
+        if bytes[index] != b'=' {
+            index += 1;
+            continue;
+        }
+
+        let name_end = bytes[..index]
+            .iter()
+            .rposition(|byte| !byte.is_ascii_whitespace())
+            .map_or(index, |position| position + 1);
+        let name_start = bytes[..name_end]
+            .iter()
+            .rposition(|byte| byte.is_ascii_whitespace())
+            .map_or(0, |position| position + 1);
+        let name = &bytes[name_start..name_end];
+
+        let Some(quote_offset) = bytes[index + 1..]
+            .iter()
+            .position(|byte| !byte.is_ascii_whitespace())
+        else {
+            return bytes.len();
+        };
+        let quote_position = index + 1 + quote_offset;
+        let quote = bytes[quote_position];
+        if quote != b'"' && quote != b'\'' {
+            index = quote_position;
+            continue;
+        }
+        let value_start = quote_position + 1;
+        let value_end = bytes[value_start..]
+            .iter()
+            .position(|&byte| byte == quote)
+            .map_or(bytes.len(), |offset| value_start + offset);
+
+        if COORDINATE_ATTRIBUTES
+            .iter()
+            .any(|attribute| attribute.as_bytes() == name)
+        {
+            number_digits(bytes, value_start..value_end, digits);
+        }
+        index = value_end + 1;
+    }
+    index + 1
+}
+
+/// Finds numbers in the attribute value, skipping the ones in exponential notation.
+fn number_digits(bytes: &[u8], range: std::ops::Range<usize>, digits: &mut Vec<usize>) {
+    let mut index = range.start;
+    while index < range.end {
+        if !bytes[index].is_ascii_digit() && bytes[index] != b'.' {
+            index += 1;
+            continue;
+        }
+
+        let skip_digits = |mut index: usize| {
+            while index < range.end && bytes[index].is_ascii_digit() {
+                index += 1;
+            }
+            index
+        };
+        index = skip_digits(index);
+        if index >= range.end || bytes[index] != b'.' {
+            continue;
+        }
+        let fraction_start = index + 1;
+        index = skip_digits(fraction_start);
+        let fraction_digits = index - fraction_start;
+
+        let exponent = index < range.end && matches!(bytes[index], b'e' | b'E');
+        if exponent {
+            let sign = matches!(bytes.get(index + 1), Some(b'+' | b'-'));
+            index = skip_digits(index + 1 + sign as usize);
+        } else if fraction_digits >= MIN_FRACTION_DIGITS {
+            digits.push(index - 1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    const SVG: &str = r##"<?xml version="1.0" encoding="UTF-8"?>
+<!-- x="1.2345" -->
+<svg xmlns="http://www.w3.org/2000/svg" width="100.000" height='50' viewBox="0 0 100.125 50">
+  <path d="M10.1234,20.5678L30.111.2229 1e-3 2.5e+1234Z" fill="#123.456"/>
+  <circle cx = "12.3456" cy="7.89" r="5.0001"/>
+  <text x="1.0000">3.14159</text>
+</svg>
+"##;
+
+    #[test]
+    fn carrier_digits() {
+        let digits: Vec<char> = super::carrier_digits(SVG)
+            .into_iter()
+            .map(|position| SVG.as_bytes()[position] as char)
+            .collect();
+        // width, d (4 numbers), cx, r, x
+        assert_eq!(digits, vec!['0', '4', '8', '1', '9', '6', '1', '0']);
+    }
+
+    #[test]
+    fn capacity() {
+        assert_eq!(super::capacity(SVG), 1);
+    }
+
+    #[test]
+    fn write_read() {
+        let written = super::write(SVG, &[0b10110010]).unwrap();
+        assert_eq!(super::read(&written), vec![0b10110010]);
+
+        let changed: Vec<usize> = SVG
+            .bytes()
+            .zip(written.bytes())
+            .enumerate()
+            .filter(|(_, (original, written))| original != written)
+            .map(|(position, _)| position)
+            .collect();
+        let digits = super::carrier_digits(SVG);
+        assert!(changed.iter().all(|position| digits.contains(position)));
+        assert_eq!(SVG.len(), written.len());
+    }
+
+    #[test]
+    fn write_too_much_data() {
+        assert!(super::write(SVG, &[0; 2]).is_err());
+    }
+
+    #[test]
+    fn is_svg() {
+        assert!(super::is_svg(SVG.as_bytes()));
+        assert!(!super::is_svg(b"plain text"));
+        assert!(!super::is_svg(&[0x89, b'P', b'N', b'G']));
+    }
+}
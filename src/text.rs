@@ -0,0 +1,114 @@
+use anyhow::{anyhow, Result};
+
+/// Alphabet of invisible characters, each one carrying 4 bits of data.
+/// Variation selectors `U+FE0E` and `U+FE0F` are skipped, as they are commonly used in emoji.
+const SYMBOLS: [char; 16] = [
+    '\u{200B}', // Zero width space
+    '\u{200C}', // Zero width non-joiner
+    '\u{200D}', // Zero width joiner
+    '\u{FE00}', '\u{FE01}', '\u{FE02}', '\u{FE03}', '\u{FE04}', '\u{FE05}', '\u{FE06}', '\u{FE07}',
+    '\u{FE08}', '\u{FE09}', '\u{FE0A}', '\u{FE0B}', '\u{FE0C}',
+];
+const BITS_PER_SYMBOL: usize = 4;
+const SYMBOL_MASK: u8 = 0x0F;
+
+/// Number of bytes which can be hidden in the cover text, which is not limited by the text itself.
+pub const CAPACITY: usize = u32::MAX as usize;
+
+/// Spreads invisible symbols representing data after words of the cover text.
+pub fn write(cover: &str, data: &[u8]) -> Result<String> {
+    if cover.chars().any(|c| SYMBOLS.contains(&c)) {
+        return Err(anyhow!(
+            "Cover text already contains zero-width characters."
+        ));
+    }
+
+    let word_ends: Vec<usize> = cover
+        .char_indices()
+        .zip(cover.chars().skip(1).map(Some).chain([None]))
+        .filter(|((_, c), next)| !c.is_whitespace() && next.is_none_or(char::is_whitespace))
+        .map(|((index, c), _)| index + c.len_utf8())
+        .collect();
+    if word_ends.is_empty() {
+        return Err(anyhow!("Cover text does not contain any word."));
+    }
+
+    let symbols: Vec<char> = data
+        .iter()
+        .flat_map(|byte| [byte >> BITS_PER_SYMBOL, byte & SYMBOL_MASK])
+        .map(|nibble| SYMBOLS[nibble as usize])
+        .collect();
+    let per_word = symbols.len().div_ceil(word_ends.len());
+
+    let mut output = String::with_capacity(cover.len() + symbols.len() * 3);
+    let mut symbols = symbols.chunks(per_word.max(1));
+    let mut start = 0;
+    for end in word_ends {
+        output.push_str(&cover[start..end]);
+        output.extend(symbols.next().into_iter().flatten());
+        start = end;
+    }
+    output.push_str(&cover[start..]);
+    Ok(output)
+}
+
+/// Collects invisible symbols from the text, ignoring all the visible ones.
+pub fn read(text: &str) -> Result<Vec<u8>> {
+    let nibbles: Vec<u8> = text
+        .chars()
+        .filter_map(|c| SYMBOLS.iter().position(|&symbol| symbol == c))
+        .map(|nibble| nibble as u8)
+        .collect();
+    if !nibbles.len().is_multiple_of(2) {
+        return Err(anyhow!("Incomplete byte hidden in text."));
+    }
+
+    Ok(nibbles
+        .chunks_exact(2)
+        .map(|nibbles| (nibbles[0] << BITS_PER_SYMBOL) | nibbles[1])
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn write_read() {
+        let cover = "The quick brown fox\njumps over the lazy dog. ";
+        let data = b"zero width";
+
+        let written = super::write(cover, data).unwrap();
+        assert_eq!(super::read(&written).unwrap(), data);
+
+        let visible: String = written
+            .chars()
+            .filter(|c| !super::SYMBOLS.contains(c))
+            .collect();
+        assert_eq!(visible, cover);
+    }
+
+    #[test]
+    fn write_spreads_symbols() {
+        let written = super::write("a b", &[0x12, 0x34]).unwrap();
+        assert_eq!(written, "a\u{200C}\u{200D} b\u{FE00}\u{FE01}");
+    }
+
+    #[test]
+    fn write_no_words() {
+        assert!(super::write(" \n\t", b"data").is_err());
+    }
+
+    #[test]
+    fn write_cover_with_symbols() {
+        assert!(super::write("a\u{200B}b", b"data").is_err());
+    }
+
+    #[test]
+    fn read_ignores_emoji_selectors() {
+        assert_eq!(super::read("\u{2764}\u{FE0F} plain").unwrap(), vec![]);
+    }
+
+    #[test]
+    fn read_incomplete_byte() {
+        assert!(super::read("a\u{200B}").is_err());
+    }
+}
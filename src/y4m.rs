@@ -0,0 +1,215 @@
+use anyhow::{anyhow, Result};
+
+use crate::coder::layout::Layout;
+
+const SIGNATURE: &[u8] = b"YUV4MPEG2 ";
+const FRAME_MARKER: &[u8] = b"FRAME";
+const DEFAULT_COLORSPACE: &str = "420jpeg";
+
+#[derive(Debug, Clone)]
+pub struct Frame {
+    /// Frame marker with its optional parameters, including the terminating newline.
+    pub marker: Vec<u8>,
+    /// Y, U and V planes of the frame.
+    pub data: Vec<u8>,
+}
+
+/// Uncompressed YUV4MPEG2 video. Stream header and frame markers are kept byte-identical.
+#[derive(Debug, Clone)]
+pub struct Video {
+    /// Stream header, including the terminating newline.
+    pub header: Vec<u8>,
+    pub width: usize,
+    pub height: usize,
+    pub frames: Vec<Frame>,
+}
+
+impl Video {
+    /// Planes of all frames are coded as a single grayscale buffer.
+    pub fn layout(&self) -> Layout {
+        Layout::L
+    }
+
+    pub fn samples(&self) -> Vec<u8> {
+        self.frames
+            .iter()
+            .flat_map(|frame| frame.data.iter().copied())
+            .collect()
+    }
+
+    pub fn set_samples(&mut self, samples: &[u8]) {
+        let mut samples = samples.iter();
+        for frame in self.frames.iter_mut() {
+            frame
+                .data
+                .iter_mut()
+                .zip(samples.by_ref())
+                .for_each(|(byte, sample)| *byte = *sample);
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut output = self.header.clone();
+        for frame in &self.frames {
+            output.extend_from_slice(&frame.marker);
+            output.extend_from_slice(&frame.data);
+        }
+        output
+    }
+}
+
+pub fn is_y4m(file: &[u8]) -> bool {
+    file.starts_with(SIGNATURE)
+}
+
+pub fn parse(file: &[u8]) -> Result<Video> {
+    if !is_y4m(file) {
+        return Err(anyhow!("Missing YUV4MPEG2 signature."));
+    }
+
+    let (header, mut rest) = split_line(file).ok_or(anyhow!("Truncated Y4M stream header."))?;
+    let parameters = std::str::from_utf8(&header[SIGNATURE.len()..header.len() - 1])?;
+
+    let mut width = None;
+    let mut height = None;
+    let mut colorspace = DEFAULT_COLORSPACE;
+    for parameter in parameters
+        .split(' ')
+        .filter(|parameter| !parameter.is_empty())
+    {
+        // Tag is the first character, which may be multi-byte in unknown parameters
+        let mut chars = parameter.chars();
+        let tag = chars.next();
+        let value = chars.as_str();
+        match tag {
+            Some('W') => width = Some(value.parse::<usize>()?),
+            Some('H') => height = Some(value.parse::<usize>()?),
+            Some('C') => colorspace = value,
+            _ => {}
+        }
+    }
+    let width = width.ok_or(anyhow!("Missing width in Y4M stream header."))?;
+    let height = height.ok_or(anyhow!("Missing height in Y4M stream header."))?;
+    let frame_size = frame_size(colorspace, width, height)?;
+
+    let mut frames = Vec::new();
+    while !rest.is_empty() {
+        let (marker, data) = split_line(rest).ok_or(anyhow!("Truncated Y4M frame header."))?;
+        if !marker.starts_with(FRAME_MARKER) {
+            return Err(anyhow!("Invalid Y4M frame header."));
+        }
+        if data.len() < frame_size {
+            return Err(anyhow!("Truncated Y4M frame data."));
+        }
+        frames.push(Frame {
+            marker: marker.to_vec(),
+            data: data[..frame_size].to_vec(),
+        });
+        rest = &data[frame_size..];
+    }
+
+    Ok(Video {
+        header: header.to_vec(),
+        width,
+        height,
+        frames,
+    })
+}
+
+/// Splits the buffer after the first newline.
+fn split_line(buffer: &[u8]) -> Option<(&[u8], &[u8])> {
+    let end = buffer.iter().position(|&byte| byte == b'\n')? + 1;
+    Some(buffer.split_at(end))
+}
+
+/// Number of bytes of Y, U and V planes of a single frame.
+fn frame_size(colorspace: &str, width: usize, height: usize) -> Result<usize> {
+    let luma = width * height;
+    let chroma = match colorspace {
+        "420" | "420jpeg" | "420paldv" | "420mpeg2" => width.div_ceil(2) * height.div_ceil(2),
+        "422" => width.div_ceil(2) * height,
+        "411" => width.div_ceil(4) * height,
+        "444" => luma,
+        "444alpha" => return Ok(luma * 4),
+        "mono" => 0,
+        _ => {
+            return Err(anyhow!(
+                "Unsupported Y4M colorspace: {}. Only 8-bit videos are supported.",
+                colorspace
+            ))
+        }
+    };
+    Ok(luma + 2 * chroma)
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn frame_size() {
+        assert_eq!(super::frame_size("420jpeg", 4, 4).unwrap(), 24);
+        assert_eq!(super::frame_size("420", 5, 3).unwrap(), 27);
+        assert_eq!(super::frame_size("422", 4, 2).unwrap(), 16);
+        assert_eq!(super::frame_size("444", 2, 2).unwrap(), 12);
+        assert_eq!(super::frame_size("444alpha", 2, 2).unwrap(), 16);
+        assert_eq!(super::frame_size("mono", 2, 2).unwrap(), 4);
+        assert!(super::frame_size("420p10", 2, 2).is_err());
+    }
+
+    #[test]
+    fn parse() {
+        let file = create_y4m();
+        let video = super::parse(&file).unwrap();
+
+        assert_eq!((video.width, video.height), (4, 2));
+        assert_eq!(video.header, b"YUV4MPEG2 W4 H2 F25:1 C444 XYSCSS=444\n");
+        assert_eq!(video.frames.len(), 2);
+        assert_eq!(video.frames[0].marker, b"FRAME\n");
+        assert_eq!(video.frames[1].marker, b"FRAME Ixyz\n");
+        assert_eq!(video.frames[1].data, vec![1; 24]);
+        assert_eq!(video.to_bytes(), file);
+    }
+
+    #[test]
+    fn parse_default_colorspace() {
+        let mut file = b"YUV4MPEG2 W2 H2\nFRAME\n".to_vec();
+        file.extend_from_slice(&[0; 6]);
+        assert_eq!(super::parse(&file).unwrap().frames[0].data.len(), 6);
+    }
+
+    #[test]
+    fn parse_non_ascii_parameter() {
+        let mut file = "YUV4MPEG2 W2 H2 éx XÜ=1\nFRAME\n".as_bytes().to_vec();
+        file.extend_from_slice(&[0; 6]);
+        assert_eq!(super::parse(&file).unwrap().frames[0].data.len(), 6);
+    }
+
+    #[test]
+    fn parse_truncated_frame() {
+        let file = create_y4m();
+        assert!(super::parse(&file[..file.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn parse_missing_signature() {
+        assert!(super::parse(b"YUV4MPEG W2 H2\n").is_err());
+    }
+
+    #[test]
+    fn set_samples() {
+        let mut video = super::parse(&create_y4m()).unwrap();
+        let samples: Vec<u8> = (0..48).collect();
+        video.set_samples(&samples);
+
+        assert_eq!(video.samples(), samples);
+        assert_eq!(video.frames[1].data[0], 24);
+    }
+
+    fn create_y4m() -> Vec<u8> {
+        let mut file = b"YUV4MPEG2 W4 H2 F25:1 C444 XYSCSS=444\n".to_vec();
+        file.extend_from_slice(b"FRAME\n");
+        file.extend_from_slice(&[0; 24]);
+        file.extend_from_slice(b"FRAME Ixyz\n");
+        file.extend_from_slice(&[1; 24]);
+        file
+    }
+}
@@ -2,9 +2,14 @@
 mod tests {
     use anyhow::{Ok, Result};
     use assert_cmd::prelude::*;
-    use mythic_telegram::file;
+    use mythic_telegram::{animation, file, y4m};
     use predicates::prelude::*;
-    use std::{ffi::OsStr, fs, path::PathBuf, process::Command};
+    use std::{
+        ffi::OsStr,
+        fs,
+        path::{Path, PathBuf},
+        process::Command,
+    };
 
     #[derive(Debug)]
     struct TestData {
@@ -56,7 +61,7 @@ mod tests {
         cmd.arg("encode");
         cmd.assert()
             .failure()
-            .stderr(predicate::str::contains("Usage: mythic-telegram encode --image-file <IMAGE_FILE> --secret-file <SECRET_FILE> <COMMAND>"));
+            .stderr(predicate::str::contains("Usage: mythic-telegram encode [OPTIONS] --image-file <IMAGE_FILE> --secret-file <SECRET_FILE> <COMMAND>"));
         Ok(())
     }
 
@@ -73,18 +78,18 @@ mod tests {
     #[test]
     fn run_encode_without_mode() -> Result<()> {
         let mut cmd = Command::cargo_bin("mythic-telegram")?;
-        cmd.args(&["encode", "--image-file", "tests/data/image.png"]);
+        cmd.args(["encode", "--image-file", "tests/data/image.png"]);
         cmd.assert()
             .failure()
             .stderr(predicate::str::contains("error: 'mythic-telegram encode' requires a subcommand but one was not provided"))
-            .stderr(predicate::str::contains("Usage: mythic-telegram encode --image-file <IMAGE_FILE> --secret-file <SECRET_FILE> <COMMAND>"));
+            .stderr(predicate::str::contains("Usage: mythic-telegram encode [OPTIONS] --image-file <IMAGE_FILE> --secret-file <SECRET_FILE> <COMMAND>"));
         Ok(())
     }
 
     #[test]
     fn run_encode_rbg_mode_without_arg() -> Result<()> {
         let mut cmd = Command::cargo_bin("mythic-telegram")?;
-        cmd.args(&[
+        cmd.args([
             "encode",
             "--image-file",
             "tests/data/image.png",
@@ -98,27 +103,982 @@ mod tests {
 
     #[test]
     fn run_encode_decode_alpha() -> Result<()> {
-        test_encode_decode("run_encode_decode_alpha", &["alpha"])
+        test_encode_decode("run_encode_decode_alpha", ["alpha"])
+    }
+
+    #[test]
+    fn run_decode_alpha_with_trailing_bytes() -> Result<()> {
+        let test_data = TestData::new("run_decode_alpha_with_trailing_bytes");
+        let mut cmd = Command::cargo_bin("mythic-telegram")?;
+        cmd.args([
+            "encode",
+            "--image-file",
+            test_data.image_path.to_str().unwrap(),
+            "--secret-file",
+            test_data.original_secret_file_path.to_str().unwrap(),
+            "alpha",
+        ]);
+        cmd.assert().success();
+
+        let mut encoded = fs::read(&test_data.encoded_image_path)?;
+        encoded.extend_from_slice(b"trailing junk after IEND");
+        fs::write(&test_data.encoded_image_path, encoded)?;
+
+        let mut cmd = Command::cargo_bin("mythic-telegram")?;
+        cmd.args([
+            "decode",
+            "--image-file",
+            test_data.encoded_image_path.to_str().unwrap(),
+        ]);
+        cmd.assert().success();
+        assert_eq!(
+            file::read_bytes(&test_data.original_secret_file_path)?,
+            file::read_bytes(&test_data.decoded_secret_file_path)?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn run_encode_decode_rgb_padded() -> Result<()> {
+        test_encode_decode(
+            "run_encode_decode_rgb_padded",
+            ["--pad", "rgb", "--bits-per-channel", "2"],
+        )
     }
 
     #[test]
     fn run_encode_decode_rgb_4bits() -> Result<()> {
         test_encode_decode(
             "run_encode_decode_rgb_4bits",
-            &["rgb", "--bits-per-channel", "4"],
+            ["rgb", "--bits-per-channel", "4"],
+        )
+    }
+
+    #[test]
+    fn run_encode_decode_rgb_16bit_image() -> Result<()> {
+        let test_data = TestData::new("run_encode_decode_rgb_16bit_image");
+        let image = image::open(&test_data.image_path)?;
+        image::DynamicImage::ImageRgba16(image.to_rgba16()).save(&test_data.image_path)?;
+
+        run_encode_decode(&test_data, ["rgb", "--bits-per-channel", "2"])?;
+
+        let encoded = image::open(&test_data.encoded_image_path)?;
+        assert_eq!(encoded.color(), image::ColorType::Rgba16);
+        Ok(())
+    }
+
+    #[test]
+    fn run_encode_decode_rgb_keeps_rgb_color_type() -> Result<()> {
+        let test_data = TestData::new("run_encode_decode_rgb_keeps_rgb_color_type");
+        let image = image::open(&test_data.image_path)?;
+        image::DynamicImage::ImageRgb8(image.to_rgb8()).save(&test_data.image_path)?;
+
+        run_encode_decode(&test_data, ["rgb", "--bits-per-channel", "2"])?;
+
+        let encoded = image::open(&test_data.encoded_image_path)?;
+        assert_eq!(encoded.color(), image::ColorType::Rgb8);
+        Ok(())
+    }
+
+    #[test]
+    fn run_encode_decode_rgb_keeps_grayscale_color_type() -> Result<()> {
+        let test_data = TestData::new("run_encode_decode_rgb_keeps_grayscale_color_type");
+        let image = image::open(&test_data.image_path)?;
+        image::DynamicImage::ImageLuma8(image.to_luma8()).save(&test_data.image_path)?;
+
+        run_encode_decode(&test_data, ["rgb", "--bits-per-channel", "4"])?;
+
+        let encoded = image::open(&test_data.encoded_image_path)?;
+        assert_eq!(encoded.color(), image::ColorType::L8);
+        Ok(())
+    }
+
+    #[test]
+    fn run_encode_decode_chunk_keeps_pixels() -> Result<()> {
+        let test_data = TestData::new("run_encode_decode_chunk_keeps_pixels");
+        run_encode_decode(&test_data, ["chunk"])?;
+
+        let original = image::open(&test_data.image_path)?;
+        let encoded = image::open(&test_data.encoded_image_path)?;
+        assert_eq!(original.as_bytes(), encoded.as_bytes());
+        Ok(())
+    }
+
+    #[test]
+    fn run_encode_decode_filter_keeps_pixels() -> Result<()> {
+        let mut test_data = TestData::new("run_encode_decode_filter_keeps_pixels");
+        let secret_file_path = test_data.working_dir.join("secret.txt");
+        fs::write(&secret_file_path, "Row filters carry this message.")?;
+        test_data.original_secret_file_path = secret_file_path.clone();
+        test_data.decoded_secret_file_path = secret_file_path;
+
+        let original = file::read_bytes(&test_data.original_secret_file_path)?;
+        run_encode_decode(&test_data, ["filter"])?;
+        fs::remove_file(&test_data.decoded_secret_file_path)?;
+
+        let mut cmd = Command::cargo_bin("mythic-telegram")?;
+        cmd.args([
+            "decode",
+            "--image-file",
+            test_data.encoded_image_path.to_str().unwrap(),
+        ]);
+        cmd.assert().success();
+        assert_eq!(
+            original,
+            file::read_bytes(&test_data.decoded_secret_file_path)?
+        );
+
+        let original = image::open(&test_data.image_path)?;
+        let encoded = image::open(&test_data.encoded_image_path)?;
+        assert_eq!(original.as_bytes(), encoded.as_bytes());
+        Ok(())
+    }
+
+    #[test]
+    fn run_encode_decode_palette_keeps_pixels() -> Result<()> {
+        let mut test_data = TestData::new("run_encode_decode_palette_keeps_pixels");
+        create_indexed_png(&test_data.image_path)?;
+        let secret_file_path = test_data.working_dir.join("secret.txt");
+        fs::write(&secret_file_path, "Palette order carries this message.")?;
+        test_data.original_secret_file_path = secret_file_path.clone();
+        test_data.decoded_secret_file_path = secret_file_path;
+
+        let original = image::open(&test_data.image_path)?.to_rgba8();
+        run_encode_decode(&test_data, ["palette"])?;
+
+        let encoded = image::open(&test_data.encoded_image_path)?.to_rgba8();
+        assert_eq!(original, encoded);
+        Ok(())
+    }
+
+    #[test]
+    fn run_encode_decode_y4m_video() -> Result<()> {
+        let mut test_data = TestData::new("run_encode_decode_y4m_video");
+        test_data.image_path = test_data.working_dir.join("video.y4m");
+        test_data.encoded_image_path = test_data.working_dir.join("encoded_video.y4m");
+        let video = create_y4m_video(&test_data.image_path)?;
+        run_encode_decode(&test_data, ["rgb", "--bits-per-channel", "2"])?;
+
+        let encoded = y4m::parse(&file::read_bytes(&test_data.encoded_image_path)?)?;
+        assert_eq!(video.header, encoded.header);
+        assert_eq!(video.frames.len(), encoded.frames.len());
+        for (original, encoded) in video.frames.iter().zip(encoded.frames.iter()) {
+            assert_eq!(original.marker, encoded.marker);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn run_encode_decode_text() -> Result<()> {
+        let mut test_data = TestData::new("run_encode_decode_text");
+        let cover = "Nothing to see here.\nJust a few innocent lines of text.\n";
+        test_data.image_path = test_data.working_dir.join("cover.txt");
+        test_data.encoded_image_path = test_data.working_dir.join("encoded_cover.txt");
+        fs::write(&test_data.image_path, cover)?;
+        run_encode_decode(&test_data, ["text"])?;
+
+        let encoded = fs::read_to_string(&test_data.encoded_image_path)?;
+        let visible: String = encoded
+            .chars()
+            .filter(|c| !matches!(c, '\u{200B}'..='\u{200D}' | '\u{FE00}'..='\u{FE0F}'))
+            .collect();
+        assert_eq!(cover, visible);
+        Ok(())
+    }
+
+    #[test]
+    fn run_decode_format_version_3() -> Result<()> {
+        // Written by a build storing whole header bytes in the alpha channel
+        let test_data = TestData::new("run_decode_format_version_3");
+        let image_path = test_data.working_dir.join("legacy_v3.png");
+        fs::copy("tests/data/legacy_v3.png", &image_path)?;
+
+        let mut cmd = Command::cargo_bin("mythic-telegram")?;
+        cmd.args(["decode", "--image-file", image_path.to_str().unwrap()]);
+        cmd.assert().success();
+
+        let decoded = fs::read_to_string(test_data.working_dir.join("legacy.txt"))?;
+        assert_eq!(decoded, "Written by format version 3.\n");
+        Ok(())
+    }
+
+    #[test]
+    fn run_decode_corrupted_text() -> Result<()> {
+        let mut test_data = TestData::new("run_decode_corrupted_text");
+        test_data.image_path = test_data.working_dir.join("cover.txt");
+        test_data.encoded_image_path = test_data.working_dir.join("encoded_cover.txt");
+        fs::write(&test_data.image_path, "Nothing to see here.\n")?;
+        let mut cmd = Command::cargo_bin("mythic-telegram")?;
+        cmd.args([
+            "encode",
+            "--image-file",
+            test_data.image_path.to_str().unwrap(),
+            "--secret-file",
+            test_data.original_secret_file_path.to_str().unwrap(),
+            "text",
+        ]);
+        cmd.assert().success();
+
+        // Swaps the last invisible symbol, which belongs to the payload checksum
+        let encoded = fs::read_to_string(&test_data.encoded_image_path)?;
+        let (index, symbol) = encoded
+            .char_indices()
+            .rev()
+            .find(|(_, c)| matches!(c, '\u{200B}'..='\u{200D}' | '\u{FE00}'..='\u{FE0F}'))
+            .unwrap();
+        let other = if symbol == '\u{200B}' {
+            "\u{200C}"
+        } else {
+            "\u{200B}"
+        };
+        let corrupted = [
+            &encoded[..index],
+            other,
+            &encoded[index + symbol.len_utf8()..],
+        ]
+        .concat();
+        fs::write(&test_data.encoded_image_path, corrupted)?;
+
+        let mut cmd = Command::cargo_bin("mythic-telegram")?;
+        cmd.args([
+            "decode",
+            "--image-file",
+            test_data.encoded_image_path.to_str().unwrap(),
+        ]);
+        cmd.assert().failure().stderr(predicate::str::contains(
+            "Payload checksum mismatch, the data is corrupted.",
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn run_encode_decode_svg() -> Result<()> {
+        let mut test_data = TestData::new("run_encode_decode_svg");
+        test_data.image_path = test_data.working_dir.join("drawing.svg");
+        test_data.encoded_image_path = test_data.working_dir.join("encoded_drawing.svg");
+        let secret_file_path = test_data.working_dir.join("secret.txt");
+        fs::write(&secret_file_path, "Coordinates carry this message.")?;
+        test_data.original_secret_file_path = secret_file_path.clone();
+        test_data.decoded_secret_file_path = secret_file_path;
+
+        let drawing = create_svg();
+        fs::write(&test_data.image_path, &drawing)?;
+        run_encode_decode(&test_data, ["svg"])?;
+
+        let encoded = fs::read_to_string(&test_data.encoded_image_path)?;
+        assert_eq!(drawing.len(), encoded.len());
+        for (original, encoded) in drawing.split(' ').zip(encoded.split(' ')) {
+            if original != encoded {
+                let original: f64 = original.parse()?;
+                let encoded: f64 = encoded.parse()?;
+                assert!((original - encoded).abs() <= 0.001 + f64::EPSILON);
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn run_encode_decode_exr_keeps_channels() -> Result<()> {
+        let mut test_data = TestData::new("run_encode_decode_exr_keeps_channels");
+        test_data.image_path = test_data.working_dir.join("image.exr");
+        test_data.encoded_image_path = test_data.working_dir.join("encoded_image.exr");
+        let secret_file_path = test_data.working_dir.join("secret.txt");
+        fs::write(&secret_file_path, "Mantissa bits carry this message.")?;
+        test_data.original_secret_file_path = secret_file_path.clone();
+        test_data.decoded_secret_file_path = secret_file_path;
+        let original = create_exr_luminance(&test_data.image_path)?;
+        run_encode_decode(&test_data, ["rgb", "--bits-per-channel", "2"])?;
+
+        let encoded = exr::prelude::read_all_flat_layers_from_file(&test_data.encoded_image_path)?;
+        let channels = &encoded.layer_data[0].channel_data.list;
+        assert_eq!(channels.len(), 1);
+        assert_eq!(channels[0].name.to_string(), "Y");
+        let exr::prelude::FlatSamples::F16(samples) = &channels[0].sample_data else {
+            panic!("Sample type of encoded image changed.");
+        };
+        for (original, encoded) in original.iter().zip(samples) {
+            assert!((original.to_f32() - encoded.to_f32()).abs() <= original.to_f32() * 0.25);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn run_encode_decode_hdr_as_exr() -> Result<()> {
+        let mut test_data = TestData::new("run_encode_decode_hdr_as_exr");
+        test_data.image_path = test_data.working_dir.join("image.hdr");
+        test_data.encoded_image_path = test_data.working_dir.join("encoded_image.exr");
+        let secret_file_path = test_data.working_dir.join("secret.txt");
+        fs::write(&secret_file_path, "Mantissa bits carry this message.")?;
+        test_data.original_secret_file_path = secret_file_path.clone();
+        test_data.decoded_secret_file_path = secret_file_path;
+        let pixels: Vec<image::Rgb<f32>> = (0..200 * 200)
+            .map(|pixel| {
+                image::Rgb([
+                    (pixel % 200) as f32 * 10.0,
+                    (pixel / 200) as f32 / 10.0,
+                    1.0,
+                ])
+            })
+            .collect();
+        image::codecs::hdr::HdrEncoder::new(fs::File::create(&test_data.image_path)?)
+            .encode(&pixels, 200, 200)?;
+        run_encode_decode(&test_data, ["rgb", "--bits-per-channel", "4"])?;
+
+        let encoded = image::open(&test_data.encoded_image_path)?;
+        assert_eq!(encoded.color(), image::ColorType::Rgb32F);
+        Ok(())
+    }
+
+    #[test]
+    fn run_encode_decode_gif_animation() -> Result<()> {
+        let test_data = TestData::new("run_encode_decode_gif_animation");
+        let gif_path = test_data.working_dir.join("animation.gif");
+        create_gif_animation(&gif_path)?;
+
+        let mut cmd = Command::cargo_bin("mythic-telegram")?;
+        cmd.args([
+            "encode",
+            "--image-file",
+            gif_path.to_str().unwrap(),
+            "--secret-file",
+            test_data.original_secret_file_path.to_str().unwrap(),
+            "rgb",
+            "--bits-per-channel",
+            "4",
+        ]);
+        cmd.assert().success();
+
+        let encoded_path = test_data.working_dir.join("encoded_animation.png");
+        let original = animation::read(&gif_path)?;
+        let encoded = animation::read(&encoded_path)?;
+        assert_eq!(original.frames.len(), encoded.frames.len());
+        for (original, encoded) in original.frames.iter().zip(encoded.frames.iter()) {
+            let (original, encoded) = (original.control.unwrap(), encoded.control.unwrap());
+            assert_eq!(original.delay_num, encoded.delay_num);
+            assert_eq!(original.delay_den, encoded.delay_den);
+            assert_eq!(original.dispose_op, encoded.dispose_op);
+            assert_eq!(original.x_offset, encoded.x_offset);
+            assert_eq!(original.y_offset, encoded.y_offset);
+        }
+
+        let mut cmd = Command::cargo_bin("mythic-telegram")?;
+        cmd.args(["decode", "--image-file", encoded_path.to_str().unwrap()]);
+        cmd.assert().success();
+
+        let original = file::read_bytes(&test_data.original_secret_file_path)?;
+        let decoded = file::read_bytes(&test_data.decoded_secret_file_path)?;
+        assert_eq!(original, decoded);
+        Ok(())
+    }
+
+    #[test]
+    fn run_encode_decode_apng_animation() -> Result<()> {
+        let test_data = TestData::new("run_encode_decode_apng_animation");
+        let apng_path = test_data.working_dir.join("animation.png");
+        create_apng_animation(&apng_path)?;
+
+        let mut cmd = Command::cargo_bin("mythic-telegram")?;
+        cmd.args([
+            "encode",
+            "--image-file",
+            apng_path.to_str().unwrap(),
+            "--secret-file",
+            test_data.original_secret_file_path.to_str().unwrap(),
+            "rgb",
+            "--bits-per-channel",
+            "4",
+        ]);
+        cmd.assert().success();
+
+        let encoded_path = test_data.working_dir.join("encoded_animation.png");
+        let original = animation::read(&apng_path)?;
+        let encoded = animation::read(&encoded_path)?;
+        assert_eq!(original.num_plays, encoded.num_plays);
+        assert_eq!(original.frames.len(), encoded.frames.len());
+        for (original, encoded) in original.frames.iter().zip(encoded.frames.iter()) {
+            let (original, encoded) = (original.control.unwrap(), encoded.control.unwrap());
+            assert_eq!(original.delay_num, encoded.delay_num);
+            assert_eq!(original.delay_den, encoded.delay_den);
+            assert_eq!(original.dispose_op, encoded.dispose_op);
+            assert_eq!(original.width, encoded.width);
+            assert_eq!(original.x_offset, encoded.x_offset);
+            assert_eq!(original.y_offset, encoded.y_offset);
+        }
+        // The secret does not fit the first frame, so data spreads over the following ones
+        assert_ne!(original.frames[1].data, encoded.frames[1].data);
+
+        let mut cmd = Command::cargo_bin("mythic-telegram")?;
+        cmd.args(["decode", "--image-file", encoded_path.to_str().unwrap()]);
+        cmd.assert().success();
+
+        let original = file::read_bytes(&test_data.original_secret_file_path)?;
+        let decoded = file::read_bytes(&test_data.decoded_secret_file_path)?;
+        assert_eq!(original, decoded);
+        Ok(())
+    }
+
+    #[test]
+    fn run_encode_decode_with_passphrase() -> Result<()> {
+        let test_data = TestData::new("run_encode_decode_with_passphrase");
+        let mut cmd = Command::cargo_bin("mythic-telegram")?;
+        cmd.env("MTG_TEST_PASSPHRASE", "correct horse");
+        cmd.args([
+            "encode",
+            "--image-file",
+            test_data.image_path.to_str().unwrap(),
+            "--secret-file",
+            test_data.original_secret_file_path.to_str().unwrap(),
+            "--passphrase-env",
+            "MTG_TEST_PASSPHRASE",
+            "rgb",
+            "--bits-per-channel",
+            "4",
+        ]);
+        cmd.assert().success();
+
+        let image_file = test_data.encoded_image_path.to_str().unwrap();
+        let mut cmd = Command::cargo_bin("mythic-telegram")?;
+        cmd.args(["decode", "--image-file", image_file]);
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains("passphrase is required"));
+
+        // Passphrases are never taken as arguments, as those are visible to other processes
+        let mut cmd = Command::cargo_bin("mythic-telegram")?;
+        cmd.args(["decode", "--image-file", image_file, "--passphrase", "x"]);
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains("unexpected argument"));
+
+        let mut cmd = Command::cargo_bin("mythic-telegram")?;
+        cmd.env("MTG_TEST_PASSPHRASE", "wrong");
+        cmd.args([
+            "decode",
+            "--image-file",
+            image_file,
+            "--passphrase-env",
+            "MTG_TEST_PASSPHRASE",
+        ]);
+        cmd.assert().failure().stderr(predicate::str::contains(
+            "Wrong passphrase or tampered data",
+        ));
+        assert!(!test_data.decoded_secret_file_path.exists());
+
+        let mut cmd = Command::cargo_bin("mythic-telegram")?;
+        cmd.env("MTG_TEST_PASSPHRASE", "correct horse");
+        cmd.args([
+            "decode",
+            "--image-file",
+            image_file,
+            "--passphrase-env",
+            "MTG_TEST_PASSPHRASE",
+        ]);
+        cmd.assert().success();
+
+        let original = file::read_bytes(&test_data.original_secret_file_path)?;
+        let decoded = file::read_bytes(&test_data.decoded_secret_file_path)?;
+        assert_eq!(original, decoded);
+        Ok(())
+    }
+
+    #[test]
+    fn run_encode_decode_with_passphrase_sources() -> Result<()> {
+        let test_data = TestData::new("run_encode_decode_with_passphrase_sources");
+        let keyfile = test_data.working_dir.join("secret.key");
+        std::fs::write(&keyfile, "keyfile contents")?;
+        let passphrase_file = test_data.working_dir.join("passphrase.txt");
+        std::fs::write(&passphrase_file, "correct horse\n")?;
+
+        let mut cmd = Command::cargo_bin("mythic-telegram")?;
+        cmd.env("MTG_TEST_PASSPHRASE", "correct horse");
+        cmd.args([
+            "encode",
+            "--image-file",
+            test_data.image_path.to_str().unwrap(),
+            "--secret-file",
+            test_data.original_secret_file_path.to_str().unwrap(),
+            "--passphrase-env",
+            "MTG_TEST_PASSPHRASE",
+            "--keyfile",
+            keyfile.to_str().unwrap(),
+            "alpha",
+        ]);
+        cmd.assert().success();
+
+        let image_file = test_data.encoded_image_path.to_str().unwrap();
+        let mut cmd = Command::cargo_bin("mythic-telegram")?;
+        cmd.args(["decode", "--image-file", image_file, "--passphrase-fd", "0"]);
+        cmd.stdin(std::fs::File::open(&passphrase_file)?);
+        cmd.assert().failure().stderr(predicate::str::contains(
+            "Wrong passphrase or tampered data",
+        ));
+
+        let mut cmd = Command::cargo_bin("mythic-telegram")?;
+        cmd.args([
+            "decode",
+            "--image-file",
+            image_file,
+            "--passphrase-fd",
+            "0",
+            "--keyfile",
+            keyfile.to_str().unwrap(),
+        ]);
+        cmd.stdin(std::fs::File::open(&passphrase_file)?);
+        cmd.assert().success();
+
+        let original = file::read_bytes(&test_data.original_secret_file_path)?;
+        let decoded = file::read_bytes(&test_data.decoded_secret_file_path)?;
+        assert_eq!(original, decoded);
+        Ok(())
+    }
+
+    #[test]
+    fn run_encode_decode_crc32_checksum() -> Result<()> {
+        let test_data = TestData::new("run_encode_decode_crc32_checksum");
+        let mut cmd = Command::cargo_bin("mythic-telegram")?;
+        cmd.args([
+            "encode",
+            "--image-file",
+            test_data.image_path.to_str().unwrap(),
+            "--secret-file",
+            test_data.original_secret_file_path.to_str().unwrap(),
+            "--checksum",
+            "crc32",
+            "alpha",
+        ]);
+        cmd.assert().success();
+
+        let mut cmd = Command::cargo_bin("mythic-telegram")?;
+        cmd.args([
+            "decode",
+            "--image-file",
+            test_data.encoded_image_path.to_str().unwrap(),
+        ]);
+        cmd.assert().success();
+
+        let original = file::read_bytes(&test_data.original_secret_file_path)?;
+        let decoded = file::read_bytes(&test_data.decoded_secret_file_path)?;
+        assert_eq!(original, decoded);
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn run_encode_decode_preserve_metadata() -> Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+        use std::time::{Duration, UNIX_EPOCH};
+
+        let mut test_data = TestData::new("run_encode_decode_preserve_metadata");
+        // Kept apart from the encoded image, next to which the decoded file is written
+        let original_dir = test_data.working_dir.join("original");
+        fs::create_dir_all(&original_dir)?;
+        test_data.original_secret_file_path = original_dir.join("run.sh");
+        test_data.decoded_secret_file_path = test_data.working_dir.join("run.sh");
+        fs::write(
+            &test_data.original_secret_file_path,
+            "#!/bin/sh\necho secret\n",
+        )?;
+        fs::set_permissions(
+            &test_data.original_secret_file_path,
+            fs::Permissions::from_mode(0o750),
+        )?;
+        let modified = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        fs::File::options()
+            .write(true)
+            .open(&test_data.original_secret_file_path)?
+            .set_modified(modified)?;
+
+        let mut cmd = Command::cargo_bin("mythic-telegram")?;
+        cmd.args([
+            "encode",
+            "--image-file",
+            test_data.image_path.to_str().unwrap(),
+            "--secret-file",
+            test_data.original_secret_file_path.to_str().unwrap(),
+            "--preserve-metadata",
+            "alpha",
+        ]);
+        cmd.assert().success();
+
+        let mut cmd = Command::cargo_bin("mythic-telegram")?;
+        cmd.args([
+            "decode",
+            "--image-file",
+            test_data.encoded_image_path.to_str().unwrap(),
+        ]);
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains("Content type: application/x-sh"));
+
+        let original = file::read_bytes(&test_data.original_secret_file_path)?;
+        let decoded = file::read_bytes(&test_data.decoded_secret_file_path)?;
+        assert_eq!(original, decoded);
+        let attributes = fs::metadata(&test_data.decoded_secret_file_path)?;
+        assert_eq!(attributes.permissions().mode() & 0o777, 0o750);
+        assert_eq!(attributes.modified()?, modified);
+        Ok(())
+    }
+
+    #[test]
+    fn run_encode_decode_stealth() -> Result<()> {
+        let test_data = TestData::new("run_encode_decode_stealth");
+        let mut cmd = Command::cargo_bin("mythic-telegram")?;
+        cmd.env("MTG_TEST_PASSPHRASE", "correct horse");
+        cmd.args([
+            "encode",
+            "--image-file",
+            test_data.image_path.to_str().unwrap(),
+            "--secret-file",
+            test_data.original_secret_file_path.to_str().unwrap(),
+            "--passphrase-env",
+            "MTG_TEST_PASSPHRASE",
+            "stealth",
+        ]);
+        cmd.assert().success();
+
+        let mut cmd = Command::cargo_bin("mythic-telegram")?;
+        cmd.env("MTG_TEST_PASSPHRASE", "correct horse");
+        cmd.args([
+            "decode",
+            "--image-file",
+            test_data.encoded_image_path.to_str().unwrap(),
+            "--passphrase-env",
+            "MTG_TEST_PASSPHRASE",
+        ]);
+        cmd.assert().success();
+
+        let original = file::read_bytes(&test_data.original_secret_file_path)?;
+        let decoded = file::read_bytes(&test_data.decoded_secret_file_path)?;
+        assert_eq!(original, decoded);
+        Ok(())
+    }
+
+    #[test]
+    fn run_encode_decode_sealed_record() -> Result<()> {
+        let test_data = TestData::new("run_encode_decode_sealed_record");
+        let mut cmd = Command::cargo_bin("mythic-telegram")?;
+        cmd.args([
+            "encode",
+            "--image-file",
+            test_data.image_path.to_str().unwrap(),
+            "--secret-file",
+            test_data.original_secret_file_path.to_str().unwrap(),
+            "--seal-record",
+            "alpha",
+        ]);
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains("requires encryption"));
+
+        let mut cmd = Command::cargo_bin("mythic-telegram")?;
+        cmd.env("MTG_TEST_PASSPHRASE", "correct horse");
+        cmd.args([
+            "encode",
+            "--image-file",
+            test_data.image_path.to_str().unwrap(),
+            "--secret-file",
+            test_data.original_secret_file_path.to_str().unwrap(),
+            "--passphrase-env",
+            "MTG_TEST_PASSPHRASE",
+            "--seal-record",
+            "alpha",
+        ]);
+        cmd.assert().success();
+
+        let mut cmd = Command::cargo_bin("mythic-telegram")?;
+        cmd.env("MTG_TEST_PASSPHRASE", "correct horse");
+        cmd.args([
+            "decode",
+            "--image-file",
+            test_data.encoded_image_path.to_str().unwrap(),
+            "--passphrase-env",
+            "MTG_TEST_PASSPHRASE",
+        ]);
+        cmd.assert().success();
+
+        let original = file::read_bytes(&test_data.original_secret_file_path)?;
+        let decoded = file::read_bytes(&test_data.decoded_secret_file_path)?;
+        assert_eq!(original, decoded);
+        Ok(())
+    }
+
+    #[test]
+    fn run_encode_decode_with_recipients() -> Result<()> {
+        let test_data = TestData::new("run_encode_decode_with_recipients");
+        let alice_path = test_data.working_dir.join("alice.key");
+        let bob_path = test_data.working_dir.join("bob.key");
+        let eve_path = test_data.working_dir.join("eve.key");
+        let alice = run_keygen(&alice_path)?;
+        let bob = run_keygen(&bob_path)?;
+        run_keygen(&eve_path)?;
+
+        let mut cmd = Command::cargo_bin("mythic-telegram")?;
+        cmd.args(["keygen", "--output", alice_path.to_str().unwrap()]);
+        cmd.assert().failure();
+
+        let mut cmd = Command::cargo_bin("mythic-telegram")?;
+        cmd.args([
+            "encode",
+            "--image-file",
+            test_data.image_path.to_str().unwrap(),
+            "--secret-file",
+            test_data.original_secret_file_path.to_str().unwrap(),
+            "--recipient",
+            &alice,
+            "--recipient",
+            &bob,
+            "rgb",
+            "--bits-per-channel",
+            "4",
+        ]);
+        cmd.assert().success();
+
+        let image_file = test_data.encoded_image_path.to_str().unwrap();
+        let mut cmd = Command::cargo_bin("mythic-telegram")?;
+        cmd.args(["decode", "--image-file", image_file]);
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains("identity is required"));
+
+        let mut cmd = Command::cargo_bin("mythic-telegram")?;
+        cmd.args(["decode", "--image-file", image_file, "--identity"]);
+        cmd.arg(&eve_path);
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains("not encrypted to this identity"));
+
+        let original = file::read_bytes(&test_data.original_secret_file_path)?;
+        for identity_path in [alice_path, bob_path] {
+            let mut cmd = Command::cargo_bin("mythic-telegram")?;
+            cmd.args(["decode", "--image-file", image_file, "--identity"]);
+            cmd.arg(identity_path);
+            cmd.assert().success();
+
+            let decoded = file::read_bytes(&test_data.decoded_secret_file_path)?;
+            assert_eq!(original, decoded);
+            fs::remove_file(&test_data.decoded_secret_file_path)?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn run_encode_decode_signed() -> Result<()> {
+        let test_data = TestData::new("run_encode_decode_signed");
+        let signing_key_path = test_data.working_dir.join("signing.key");
+        let verifying_key = run_keygen_signing(&signing_key_path)?;
+        let other_verifying_key = run_keygen_signing(&test_data.working_dir.join("other.key"))?;
+
+        let mut cmd = Command::cargo_bin("mythic-telegram")?;
+        cmd.args([
+            "encode",
+            "--image-file",
+            test_data.image_path.to_str().unwrap(),
+            "--secret-file",
+            test_data.original_secret_file_path.to_str().unwrap(),
+            "--sign-key",
+            signing_key_path.to_str().unwrap(),
+            "chunk",
+        ]);
+        cmd.assert().success();
+
+        let image_file = test_data.encoded_image_path.to_str().unwrap();
+        let mut cmd = Command::cargo_bin("mythic-telegram")?;
+        cmd.args(["decode", "--image-file", image_file]);
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains("Signature: unverified"));
+        fs::remove_file(&test_data.decoded_secret_file_path)?;
+
+        let mut cmd = Command::cargo_bin("mythic-telegram")?;
+        cmd.args(["decode", "--image-file", image_file, "--verify-key"]);
+        cmd.arg(&other_verifying_key);
+        cmd.assert()
+            .failure()
+            .stdout(predicate::str::contains("Signature: invalid"))
+            .stderr(predicate::str::contains("Signature check failed"));
+        assert!(!test_data.decoded_secret_file_path.exists());
+
+        let mut cmd = Command::cargo_bin("mythic-telegram")?;
+        cmd.args(["decode", "--image-file", image_file, "--verify-key"]);
+        cmd.args([&other_verifying_key, "--verify-key", &verifying_key]);
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains("Signature: valid"));
+
+        let original = file::read_bytes(&test_data.original_secret_file_path)?;
+        let decoded = file::read_bytes(&test_data.decoded_secret_file_path)?;
+        assert_eq!(original, decoded);
+        Ok(())
+    }
+
+    fn run_keygen(path: &Path) -> Result<String> {
+        let mut cmd = Command::cargo_bin("mythic-telegram")?;
+        cmd.args(["keygen", "--output", path.to_str().unwrap()]);
+        let output = cmd.assert().success().get_output().stdout.clone();
+        let public_key = String::from_utf8(output)?
+            .trim()
+            .strip_prefix("Public key: ")
+            .unwrap()
+            .to_string();
+        Ok(public_key)
+    }
+
+    fn run_keygen_signing(path: &Path) -> Result<String> {
+        let mut cmd = Command::cargo_bin("mythic-telegram")?;
+        cmd.args(["keygen", "--signing", "--output", path.to_str().unwrap()]);
+        let output = cmd.assert().success().get_output().stdout.clone();
+        let verifying_key = String::from_utf8(output)?
+            .trim()
+            .strip_prefix("Verifying key: ")
+            .unwrap()
+            .to_string();
+        Ok(verifying_key)
+    }
+
+    fn create_indexed_png(path: &PathBuf) -> Result<()> {
+        let (width, height) = (32, 32);
+        let mut encoder = png::Encoder::new(fs::File::create(path)?, width, height);
+        encoder.set_color(png::ColorType::Indexed);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.set_palette(
+            (0..=255u8)
+                .flat_map(|color| [color, 255 - color, color / 2])
+                .collect::<Vec<u8>>(),
+        );
+
+        let mut writer = encoder.write_header()?;
+        let indices: Vec<u8> = (0..width * height)
+            .map(|pixel| (pixel % 256) as u8)
+            .collect();
+        writer.write_image_data(&indices)?;
+        writer.finish()?;
+        Ok(())
+    }
+
+    fn create_y4m_video(path: &PathBuf) -> Result<y4m::Video> {
+        let (width, height) = (160, 120);
+        let mut video = b"YUV4MPEG2 W160 H120 F30:1 Ip A1:1 C420jpeg\n".to_vec();
+        for index in 0..10 {
+            video.extend_from_slice(b"FRAME\n");
+            video.extend((0..width * height * 3 / 2).map(|pixel| ((pixel + index) % 256) as u8));
+        }
+        fs::write(path, &video)?;
+        y4m::parse(&video)
+    }
+
+    fn create_svg() -> String {
+        let points: Vec<String> = (0..1000)
+            .map(|point| format!("{:.4}", point as f64 * 0.3579))
+            .collect();
+        format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"0 0 400 400\">\n\
+             <polyline points=\" {} \" fill=\"none\" stroke=\"black\"/>\n</svg>\n",
+            points.join(" ")
         )
     }
 
+    fn create_exr_luminance(path: &PathBuf) -> Result<Vec<half::f16>> {
+        use exr::prelude::{
+            AnyChannel, AnyChannels, FlatSamples, Image, ImageAttributes, IntegerBounds, Layer,
+            LayerAttributes, SmallVec, WritableImage,
+        };
+
+        let size = (160, 160);
+        let samples: Vec<half::f16> = (0..size.0 * size.1)
+            .map(|pixel| half::f16::from_f32(1.0 + (pixel % 1000) as f32 / 100.0))
+            .collect();
+        let channel = AnyChannel::new("Y", FlatSamples::F16(samples.clone()));
+        let layer = Layer::new(
+            size,
+            LayerAttributes::default(),
+            Default::default(),
+            AnyChannels::sort(SmallVec::from_vec(vec![channel])),
+        );
+        Image::from_layers(
+            ImageAttributes::new(IntegerBounds::from_dimensions(size)),
+            SmallVec::from_vec(vec![layer]),
+        )
+        .write()
+        .to_file(path)?;
+        Ok(samples)
+    }
+
+    fn create_apng_animation(path: &PathBuf) -> Result<()> {
+        let (width, height) = (120, 120);
+        let mut encoder = png::Encoder::new(fs::File::create(path)?, width, height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.set_animated(3, 2)?;
+
+        let mut writer = encoder.write_header()?;
+        for (index, dispose) in [
+            png::DisposeOp::None,
+            png::DisposeOp::Background,
+            png::DisposeOp::Previous,
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            let size = width - index as u32 * 20;
+            let pixels: Vec<u8> = (0..size as usize * size as usize)
+                .flat_map(|pixel| [(pixel % 256) as u8, index as u8 * 60, 128, 255])
+                .collect();
+            writer.set_frame_delay(10 * (index as u16 + 1), 100)?;
+            writer.set_dispose_op(dispose)?;
+            writer.reset_frame_position()?;
+            writer.set_frame_dimension(size, size)?;
+            writer.set_frame_position(index as u32 * 10, index as u32 * 10)?;
+            writer.write_image_data(&pixels)?;
+        }
+        writer.finish()?;
+        Ok(())
+    }
+
+    fn create_gif_animation(path: &PathBuf) -> Result<()> {
+        let (width, height) = (160, 160);
+        let mut encoder = gif::Encoder::new(fs::File::create(path)?, width, height, &[])?;
+        encoder.set_repeat(gif::Repeat::Infinite)?;
+
+        for (index, dispose) in [
+            gif::DisposalMethod::Keep,
+            gif::DisposalMethod::Background,
+            gif::DisposalMethod::Previous,
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            let size = width - index as u16 * 20;
+            let mut pixels: Vec<u8> = (0..size as usize * size as usize)
+                .flat_map(|pixel| [(pixel % 256) as u8, index as u8 * 60, 128, 255])
+                .collect();
+            let mut frame = gif::Frame::from_rgba_speed(size, size, &mut pixels, 30);
+            frame.left = index as u16 * 10;
+            frame.top = index as u16 * 10;
+            frame.delay = 10 * (index as u16 + 1);
+            frame.dispose = dispose;
+            encoder.write_frame(&frame)?;
+        }
+        Ok(())
+    }
+
     fn test_encode_decode<I, S>(name: &str, additional_encode_args: I) -> Result<()>
     where
         I: IntoIterator<Item = S>,
         S: AsRef<OsStr>,
     {
         let test_data = TestData::new(name);
+        run_encode_decode(&test_data, additional_encode_args)
+    }
+
+    fn run_encode_decode<I, S>(test_data: &TestData, additional_encode_args: I) -> Result<()>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
         let original = file::read_bytes(&test_data.original_secret_file_path)?;
 
         let mut cmd = Command::cargo_bin("mythic-telegram")?;
-        cmd.args(&[
+        cmd.args([
             "encode",
             "--image-file",
             test_data.image_path.to_str().unwrap(),
@@ -129,7 +1089,7 @@ mod tests {
         cmd.assert().success();
 
         let mut cmd = Command::cargo_bin("mythic-telegram")?;
-        cmd.args(&[
+        cmd.args([
             "decode",
             "--image-file",
             test_data.encoded_image_path.to_str().unwrap(),
@@ -1,10 +1,21 @@
 #[cfg(test)]
 mod tests {
-    use image::RgbaImage;
+    use image::{GrayAlphaImage, GrayImage, ImageBuffer, RgbImage, Rgba, RgbaImage};
     use mythic_telegram::{
-        coder::{decoder, encoder},
-        config::{Algorithm, RgbAlgorithmConfig},
+        coder::{
+            crypto::{Identity, Protection},
+            decoder,
+            encoder::{self, Envelope},
+            error::{DecodeError, DecryptError, EncodeError, HeaderDecodeError},
+            layout::Layout,
+            metadata::Metadata,
+            sample::Sample,
+            signature::{SignatureStatus, SigningKey},
+        },
+        config::{Algorithm, Checksum, RgbAlgorithmConfig},
+        text,
     };
+    use std::time::{Duration, UNIX_EPOCH};
 
     #[test]
     fn encode_decode_rgb_1bit() {
@@ -39,22 +50,647 @@ mod tests {
         test_encode_decode(Algorithm::Alpha);
     }
 
-    fn test_encode_decode(algorithm: Algorithm) {
+    #[test]
+    fn encode_decode_rgb_2bit_16bit_samples() {
+        test_encode_decode_samples(
+            Algorithm::Rgb(RgbAlgorithmConfig {
+                bits_per_channel: 2,
+            }),
+            Layout::Rgba,
+            ImageBuffer::<Rgba<u16>, Vec<u16>>::new(120, 120).into_vec(),
+        );
+    }
+
+    #[test]
+    fn encode_decode_alpha_16bit_samples() {
+        test_encode_decode_samples(
+            Algorithm::Alpha,
+            Layout::Rgba,
+            ImageBuffer::<Rgba<u16>, Vec<u16>>::new(120, 120).into_vec(),
+        );
+    }
+
+    #[test]
+    fn decode_16bit_encoded_as_8bit() {
+        let algorithm = Algorithm::Rgb(RgbAlgorithmConfig {
+            bits_per_channel: 1,
+        });
+        let image_buffer = RgbaImage::new(120, 120).into_vec();
+        let encoded_data = encoder::encode(
+            &algorithm,
+            Layout::Rgba,
+            image_buffer,
+            "secret".as_bytes().to_vec(),
+            "secret.txt".to_string(),
+            &Envelope::default(),
+        )
+        .unwrap();
+
+        let widened: Vec<u16> = encoded_data.into_iter().map(u16::from).collect();
+        assert!(decoder::decode(Layout::Rgba, widened, &Protection::None, &[]).is_err());
+    }
+
+    #[test]
+    fn decode_image_without_payload() {
+        let mut image = RgbaImage::new(120, 120);
+        image
+            .pixels_mut()
+            .for_each(|pixel| *pixel = Rgba([10, 20, 30, 1]));
+
+        let decoded = decoder::decode(Layout::Rgba, image.into_vec(), &Protection::None, &[]);
+        assert_eq!(
+            decoded
+                .unwrap_err()
+                .downcast::<HeaderDecodeError>()
+                .unwrap(),
+            HeaderDecodeError("No mythic-telegram payload found.".to_string())
+        );
+    }
+
+    #[test]
+    fn encode_decode_rgb_2bit_rgb_layout() {
+        test_encode_decode_samples(
+            Algorithm::Rgb(RgbAlgorithmConfig {
+                bits_per_channel: 2,
+            }),
+            Layout::Rgb,
+            RgbImage::new(120, 120).into_vec(),
+        );
+    }
+
+    #[test]
+    fn encode_decode_rgb_4bit_grayscale_layout() {
+        test_encode_decode_samples(
+            Algorithm::Rgb(RgbAlgorithmConfig {
+                bits_per_channel: 4,
+            }),
+            Layout::L,
+            GrayImage::new(120, 120).into_vec(),
+        );
+    }
+
+    #[test]
+    fn encode_decode_alpha_grayscale_alpha_layout() {
+        test_encode_decode_samples(
+            Algorithm::Alpha,
+            Layout::La,
+            GrayAlphaImage::new(120, 120).into_vec(),
+        );
+    }
+
+    #[test]
+    fn encode_decode_bytes_chunk() {
         let secret_message = "The quick brown fox jumps over the lazy dog".as_bytes();
         let secret_filename = "secret.txt";
 
+        let encoded_data = encoder::encode_bytes(
+            &Algorithm::Chunk,
+            1024,
+            secret_message.to_vec(),
+            secret_filename.to_string(),
+            &Envelope::default(),
+        )
+        .unwrap();
+
+        let decoded = decoder::decode_bytes(&encoded_data, &Protection::None, &[]).unwrap();
+        assert_eq!(secret_filename, *decoded.file_name);
+        assert_eq!(secret_message, *decoded.data);
+    }
+
+    #[test]
+    fn encode_bytes_not_enough_capacity() {
+        let encoded = encoder::encode_bytes(
+            &Algorithm::Chunk,
+            8,
+            "secret".as_bytes().to_vec(),
+            "secret.txt".to_string(),
+            &Envelope::default(),
+        );
+        assert!(encoded.is_err());
+    }
+
+    #[test]
+    fn encode_chunk_in_pixels() {
+        let encoded = encoder::encode(
+            &Algorithm::Chunk,
+            Layout::Rgba,
+            RgbaImage::new(120, 120).into_vec(),
+            "secret".as_bytes().to_vec(),
+            "secret.txt".to_string(),
+            &Envelope::default(),
+        );
+        assert!(encoded.is_err());
+    }
+
+    #[test]
+    fn encode_alpha_without_alpha_channel() {
+        let encoded = encoder::encode(
+            &Algorithm::Alpha,
+            Layout::Rgb,
+            RgbImage::new(120, 120).into_vec(),
+            "secret".as_bytes().to_vec(),
+            "secret.txt".to_string(),
+            &Envelope::default(),
+        );
+        assert!(encoded.is_err());
+    }
+
+    #[test]
+    fn encode_decode_rgb_with_passphrase() {
+        let protection = Protection::Passphrase("correct horse battery staple".into());
+        let encoded_data = encode_with_passphrase(&protection);
+
+        let decoded = decoder::decode(Layout::Rgba, encoded_data, &protection, &[]).unwrap();
+        assert_eq!("secret.txt", *decoded.file_name);
+        assert_eq!("secret".as_bytes(), *decoded.data);
+    }
+
+    #[test]
+    fn decode_with_wrong_passphrase() {
+        let encoded_data = encode_with_passphrase(&Protection::Passphrase("correct horse".into()));
+
+        let wrong = Protection::Passphrase("wrong horse".into());
+        let error = decoder::decode(Layout::Rgba, encoded_data.clone(), &wrong, &[]).unwrap_err();
+        assert!(error.is::<DecryptError>());
+
+        let error =
+            decoder::decode(Layout::Rgba, encoded_data, &Protection::None, &[]).unwrap_err();
+        assert!(error.is::<DecryptError>());
+    }
+
+    #[test]
+    fn decode_tampered_encrypted_data() {
+        let protection = Protection::Passphrase("correct horse".into());
+        let mut encoded_data = encode_with_passphrase(&protection);
+        // Flips a bit of the ciphertext, which follows the file name and the data length.
+        let file_name_position = encoded_data
+            .windows(10)
+            .position(|window| window == "secret.txt".as_bytes())
+            .unwrap();
+        encoded_data[file_name_position + 10 + 8] ^= 1;
+
+        // Caught by the payload checksum before decrypting
+        let error = decoder::decode(Layout::Rgba, encoded_data, &protection, &[]).unwrap_err();
+        assert_eq!(
+            error.downcast::<DecodeError>().unwrap(),
+            DecodeError("Payload checksum mismatch, the data is corrupted.".to_string())
+        );
+    }
+
+    #[test]
+    fn decode_corrupted_payload() {
+        for checksum in [Checksum::Crc32, Checksum::Sha256] {
+            let envelope = Envelope {
+                checksum,
+                ..Default::default()
+            };
+            let mut encoded_data = encoder::encode(
+                &Algorithm::Alpha,
+                Layout::Rgba,
+                RgbaImage::new(120, 120).into_vec(),
+                "secret".as_bytes().to_vec(),
+                "secret.txt".to_string(),
+                &envelope,
+            )
+            .unwrap();
+            let decoded =
+                decoder::decode(Layout::Rgba, encoded_data.clone(), &Protection::None, &[]);
+            assert_eq!("secret".as_bytes(), *decoded.unwrap().data);
+
+            // Flips a bit of the first data byte, stored in the alpha channel after the file name
+            let header_size = encoded_data
+                .iter()
+                .skip(3)
+                .step_by(4)
+                .position(|&a| a == b's');
+            let data_position = (header_size.unwrap() + 10 + 8) * 4 + 3;
+            encoded_data[data_position] ^= 1;
+            let error =
+                decoder::decode(Layout::Rgba, encoded_data, &Protection::None, &[]).unwrap_err();
+            assert_eq!(
+                error.downcast::<DecodeError>().unwrap(),
+                DecodeError("Payload checksum mismatch, the data is corrupted.".to_string())
+            );
+        }
+    }
+
+    #[test]
+    fn decode_damaged_leading_header() {
+        let protection = Protection::Passphrase("correct".into());
+        let mut encoded_data = encode_with_passphrase(&protection);
+        encoded_data[..64].fill(0xFF);
+
+        let decoded = decoder::decode(Layout::Rgba, encoded_data, &protection, &[]).unwrap();
+        assert_eq!("secret".as_bytes(), *decoded.data);
+    }
+
+    #[test]
+    fn decode_corrupted_header() {
+        let mut encoded_data = encode_with_passphrase(&Protection::Passphrase("correct".into()));
+        // Flips a bit of the salt in all three copies, which still parse as valid headers, spread
+        // evenly over whole pixels
+        let samples = encoded_data.len();
+        for copy in 0..3 {
+            encoded_data[copy * samples / 3 / 4 * 4 + 14 * 8] ^= 1;
+        }
+
+        let error =
+            decoder::decode(Layout::Rgba, encoded_data, &Protection::None, &[]).unwrap_err();
+        assert_eq!(
+            error.downcast::<HeaderDecodeError>().unwrap(),
+            HeaderDecodeError("Header checksum mismatch, the header is corrupted.".to_string())
+        );
+    }
+
+    #[test]
+    fn encode_decode_bytes_with_passphrase() {
+        let protection = Protection::Passphrase("passphrase".into());
+        let encoded_data = encoder::encode_bytes(
+            &Algorithm::Chunk,
+            1024,
+            "hidden message".as_bytes().to_vec(),
+            "secret.txt".to_string(),
+            &Envelope {
+                protection: protection.clone(),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert!(!encoded_data
+            .windows(14)
+            .any(|window| window == "hidden message".as_bytes()));
+
+        let decoded = decoder::decode_bytes(&encoded_data, &protection, &[]).unwrap();
+        assert_eq!("hidden message".as_bytes(), *decoded.data);
+    }
+
+    #[test]
+    fn encode_decode_alpha_with_recipient() {
+        let identity = Identity::generate();
+        let protection = Protection::Recipients(vec![identity.recipient()]);
+        let encoded_data = encoder::encode(
+            &Algorithm::Alpha,
+            Layout::Rgba,
+            RgbaImage::new(120, 120).into_vec(),
+            "secret".as_bytes().to_vec(),
+            "secret.txt".to_string(),
+            &Envelope {
+                protection: protection.clone(),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        let decoded = decoder::decode(
+            Layout::Rgba,
+            encoded_data.clone(),
+            &Protection::Identity(identity),
+            &[],
+        )
+        .unwrap();
+        assert_eq!("secret.txt", *decoded.file_name);
+        assert_eq!("secret".as_bytes(), *decoded.data);
+
+        let other = Protection::Identity(Identity::generate());
+        let error = decoder::decode(Layout::Rgba, encoded_data, &other, &[]).unwrap_err();
+        assert!(error.is::<DecryptError>());
+    }
+
+    #[test]
+    fn encode_decode_signed_and_encrypted() {
+        let signing_key = SigningKey::generate();
+        let identity = Identity::generate();
+        let encoded_data = encoder::encode_bytes(
+            &Algorithm::Chunk,
+            1024,
+            "secret".as_bytes().to_vec(),
+            "secret.txt".to_string(),
+            &Envelope {
+                protection: Protection::Recipients(vec![identity.recipient()]),
+                signing_key: Some(signing_key.clone()),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let protection = Protection::Identity(identity);
+
+        let decoded =
+            decoder::decode_bytes(&encoded_data, &protection, &[signing_key.verifying_key()])
+                .unwrap();
+        assert_eq!(decoded.signature, SignatureStatus::Valid);
+        assert_eq!("secret".as_bytes(), *decoded.data);
+
+        let decoded = decoder::decode_bytes(&encoded_data, &protection, &[]).unwrap();
+        assert_eq!(decoded.signature, SignatureStatus::Unverified);
+
+        let other_key = SigningKey::generate().verifying_key();
+        let decoded = decoder::decode_bytes(&encoded_data, &protection, &[other_key]).unwrap();
+        assert_eq!(decoded.signature, SignatureStatus::Invalid);
+    }
+
+    #[test]
+    fn decode_unsigned() {
+        let encoded_data = encoder::encode(
+            &Algorithm::Alpha,
+            Layout::Rgba,
+            RgbaImage::new(120, 120).into_vec(),
+            "secret".as_bytes().to_vec(),
+            "secret.txt".to_string(),
+            &Envelope::default(),
+        )
+        .unwrap();
+
+        let verifying_key = SigningKey::generate().verifying_key();
+        let decoded = decoder::decode(
+            Layout::Rgba,
+            encoded_data,
+            &Protection::None,
+            &[verifying_key],
+        )
+        .unwrap();
+        assert_eq!(decoded.signature, SignatureStatus::Unsigned);
+    }
+
+    #[test]
+    fn encode_decode_sealed_record() {
+        let signing_key = SigningKey::generate();
+        let protection = Protection::Passphrase("passphrase".into());
+        let encoded_data = encoder::encode_bytes(
+            &Algorithm::Chunk,
+            1024,
+            "hidden message".as_bytes().to_vec(),
+            "secret.txt".to_string(),
+            &Envelope {
+                protection: protection.clone(),
+                signing_key: Some(signing_key.clone()),
+                seal_record: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert!(!encoded_data
+            .windows(10)
+            .any(|window| window == "secret.txt".as_bytes()));
+
+        let decoded =
+            decoder::decode_bytes(&encoded_data, &protection, &[signing_key.verifying_key()])
+                .unwrap();
+        assert_eq!("secret.txt", *decoded.file_name);
+        assert_eq!("hidden message".as_bytes(), *decoded.data);
+        assert_eq!(decoded.signature, SignatureStatus::Valid);
+
+        let error = decoder::decode_bytes(&encoded_data, &Protection::None, &[]).unwrap_err();
+        assert!(error.is::<DecryptError>());
+    }
+
+    #[test]
+    fn encode_decode_alpha_sealed_record_with_recipient() {
+        let identity = Identity::generate();
+        let encoded_data = encoder::encode(
+            &Algorithm::Alpha,
+            Layout::Rgba,
+            RgbaImage::new(120, 120).into_vec(),
+            "secret".as_bytes().to_vec(),
+            "secret.txt".to_string(),
+            &Envelope {
+                protection: Protection::Recipients(vec![identity.recipient()]),
+                seal_record: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        let protection = Protection::Identity(identity);
+        let decoded = decoder::decode(Layout::Rgba, encoded_data, &protection, &[]).unwrap();
+        assert_eq!("secret.txt", *decoded.file_name);
+        assert_eq!("secret".as_bytes(), *decoded.data);
+    }
+
+    #[test]
+    fn encode_sealed_record_without_encryption() {
+        let encoded = encoder::encode_bytes(
+            &Algorithm::Chunk,
+            1024,
+            "secret".as_bytes().to_vec(),
+            "secret.txt".to_string(),
+            &Envelope {
+                seal_record: true,
+                ..Default::default()
+            },
+        );
+        assert!(encoded.is_err());
+    }
+
+    #[test]
+    fn encode_alpha_padded() {
+        let image_buffer = RgbaImage::new(120, 120).into_vec();
+        let encoded = encoder::encode(
+            &Algorithm::Alpha,
+            Layout::Rgba,
+            image_buffer,
+            "secret".as_bytes().to_vec(),
+            "secret.txt".to_string(),
+            &Envelope {
+                pad: true,
+                ..Default::default()
+            },
+        );
+        assert_eq!(
+            encoded.unwrap_err().downcast::<EncodeError>().unwrap(),
+            EncodeError("Padding is not supported in alpha mode.".to_string())
+        );
+    }
+
+    #[test]
+    fn encode_decode_bytes_padded() {
+        let protection = Protection::Passphrase("passphrase".into());
+        let envelope = Envelope {
+            protection: protection.clone(),
+            pad: true,
+            ..Default::default()
+        };
+        let encoded_data = encoder::encode_bytes(
+            &Algorithm::Filter,
+            512,
+            "secret".as_bytes().to_vec(),
+            "secret.txt".to_string(),
+            &envelope,
+        )
+        .unwrap();
+        assert_eq!(encoded_data.len(), 512);
+
+        let decoded = decoder::decode_bytes(&encoded_data, &protection, &[]).unwrap();
+        assert_eq!("secret".as_bytes(), *decoded.data);
+    }
+
+    #[test]
+    fn encode_bytes_padded_without_capacity_limit() {
+        let envelope = Envelope {
+            pad: true,
+            ..Default::default()
+        };
+        for (algorithm, capacity) in [(Algorithm::Chunk, 512), (Algorithm::Text, text::CAPACITY)] {
+            let encoded = encoder::encode_bytes(
+                &algorithm,
+                capacity,
+                "secret".as_bytes().to_vec(),
+                "secret.txt".to_string(),
+                &envelope,
+            );
+            assert_eq!(
+                encoded.unwrap_err().downcast::<EncodeError>().unwrap(),
+                EncodeError("Padding is not supported in chunk and text modes.".to_string())
+            );
+        }
+    }
+
+    #[test]
+    fn encode_decode_stealth() {
+        let protection = Protection::Passphrase("correct horse".into());
         let image_buffer = RgbaImage::new(120, 120).into_vec();
+        let encoded_data = encoder::encode(
+            &Algorithm::Stealth,
+            Layout::Rgba,
+            image_buffer.clone(),
+            "secret".as_bytes().to_vec(),
+            "secret.txt".to_string(),
+            &Envelope {
+                protection: protection.clone(),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        // No header: the first pixel is left untouched unless data wraps around to it
+        assert_ne!(encoded_data, image_buffer);
+
+        let decoded =
+            decoder::decode(Layout::Rgba, encoded_data.clone(), &protection, &[]).unwrap();
+        assert_eq!("secret.txt", *decoded.file_name);
+        assert_eq!("secret".as_bytes(), *decoded.data);
+
+        let wrong = Protection::Passphrase("wrong horse".into());
+        assert!(decoder::decode(Layout::Rgba, encoded_data.clone(), &wrong, &[]).is_err());
+        assert!(decoder::decode(Layout::Rgba, encoded_data, &Protection::None, &[]).is_err());
+    }
+
+    #[test]
+    fn encode_stealth_without_passphrase() {
+        let encoded = encoder::encode(
+            &Algorithm::Stealth,
+            Layout::Rgba,
+            RgbaImage::new(120, 120).into_vec(),
+            "secret".as_bytes().to_vec(),
+            "secret.txt".to_string(),
+            &Envelope::default(),
+        );
+        assert!(encoded.is_err());
+    }
+
+    #[test]
+    fn encode_decode_metadata() {
+        let metadata = Metadata {
+            modified: Some(UNIX_EPOCH + Duration::new(1_700_000_000, 5)),
+            permissions: Some(0o755),
+            content_type: Some("application/x-sh".to_string()),
+        };
+        let protection = Protection::Passphrase("passphrase".into());
+        let encoded_data = encoder::encode(
+            &Algorithm::Alpha,
+            Layout::Rgba,
+            RgbaImage::new(120, 120).into_vec(),
+            "echo secret".as_bytes().to_vec(),
+            "secret.sh".to_string(),
+            &Envelope {
+                protection: protection.clone(),
+                seal_record: true,
+                metadata: metadata.clone(),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        let decoded = decoder::decode(Layout::Rgba, encoded_data, &protection, &[]).unwrap();
+        assert_eq!("secret.sh", *decoded.file_name);
+        assert_eq!("echo secret".as_bytes(), *decoded.data);
+        assert_eq!(metadata, decoded.metadata);
+
+        let encoded_data = encoder::encode_bytes(
+            &Algorithm::Chunk,
+            1024,
+            "secret".as_bytes().to_vec(),
+            "secret.txt".to_string(),
+            &Envelope::default(),
+        )
+        .unwrap();
+        let decoded = decoder::decode_bytes(&encoded_data, &Protection::None, &[]).unwrap();
+        assert_eq!("secret".as_bytes(), *decoded.data);
+        assert_eq!(Metadata::default(), decoded.metadata);
+    }
+
+    #[test]
+    fn encode_stealth_with_metadata() {
+        let encoded = encoder::encode(
+            &Algorithm::Stealth,
+            Layout::Rgba,
+            RgbaImage::new(120, 120).into_vec(),
+            "secret".as_bytes().to_vec(),
+            "secret.txt".to_string(),
+            &Envelope {
+                protection: Protection::Passphrase("correct horse".into()),
+                metadata: Metadata {
+                    permissions: Some(0o644),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        );
+        assert_eq!(
+            encoded.unwrap_err().downcast::<EncodeError>().unwrap(),
+            EncodeError("Stealth mode does not support file metadata.".to_string())
+        );
+    }
+
+    fn encode_with_passphrase(protection: &Protection) -> Vec<u8> {
+        encoder::encode(
+            &Algorithm::Rgb(RgbAlgorithmConfig {
+                bits_per_channel: 8,
+            }),
+            Layout::Rgba,
+            RgbaImage::new(120, 120).into_vec(),
+            "secret".as_bytes().to_vec(),
+            "secret.txt".to_string(),
+            &Envelope {
+                protection: protection.clone(),
+                ..Default::default()
+            },
+        )
+        .unwrap()
+    }
+
+    fn test_encode_decode(algorithm: Algorithm) {
+        test_encode_decode_samples(algorithm, Layout::Rgba, RgbaImage::new(120, 120).into_vec());
+    }
+
+    fn test_encode_decode_samples<T: Sample>(
+        algorithm: Algorithm,
+        layout: Layout,
+        image_buffer: Vec<T>,
+    ) {
+        let secret_message = "The quick brown fox jumps over the lazy dog".as_bytes();
+        let secret_filename = "secret.txt";
 
         let encoded_data = encoder::encode(
             &algorithm,
+            layout,
             image_buffer,
             secret_message.to_vec(),
             secret_filename.to_string(),
+            &Envelope::default(),
         )
         .unwrap();
 
-        let (decoded_filename, decoded_buffer) = decoder::decode(encoded_data).unwrap();
-        assert_eq!(secret_filename, decoded_filename);
-        assert_eq!(secret_message, decoded_buffer);
+        let decoded = decoder::decode(layout, encoded_data, &Protection::None, &[]).unwrap();
+        assert_eq!(secret_filename, *decoded.file_name);
+        assert_eq!(secret_message, *decoded.data);
     }
 }